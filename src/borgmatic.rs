@@ -0,0 +1,432 @@
+//! Import of borgmatic YAML configs (`import borgmatic`).
+//!
+//! Every repository of a borgmatic config becomes a borg-tool repo with one backup preset
+//! holding the source directories, excludes, compression and retention. Both layouts are
+//! read: the flat one of borgmatic 1.8+ and the older `location:`/`storage:`/`retention:`
+//! sections. Settings without a borg-tool equivalent are reported instead of dropped silently.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_norway::Value;
+
+use crate::config::{BackupConfig, Config, RepoConfig};
+use crate::template;
+
+/// Sections of borgmatic < 1.8; their keys moved to the top level later.
+const LEGACY_SECTIONS: &[&str] = &[
+    "location",
+    "storage",
+    "retention",
+    "consistency",
+    "hooks",
+    "output",
+];
+
+/// Result of converting one borgmatic config.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub repos: Vec<RepoConfig>,
+    /// Settings that were not carried over, with the reason
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Settings {
+    source_directories: Vec<String>,
+    repositories: Vec<Repository>,
+    exclude_patterns: Vec<String>,
+    exclude_from: Vec<PathBuf>,
+    patterns_from: Vec<PathBuf>,
+    exclude_caches: bool,
+    exclude_if_present: Vec<String>,
+    keep_exclude_tags: bool,
+    one_file_system: bool,
+    compression: Option<String>,
+    archive_name_format: Option<String>,
+    encryption_passcommand: Option<String>,
+    remote_path: Option<String>,
+    ssh_command: Option<String>,
+    lock_wait: Option<u64>,
+    retries: Option<u32>,
+    upload_rate_limit: Option<u64>,
+    extra_borg_options: BTreeMap<String, String>,
+    keep_daily: Option<u32>,
+    keep_weekly: Option<u32>,
+    keep_monthly: Option<u32>,
+    keep_yearly: Option<u32>,
+}
+
+/// `- /path` (borgmatic < 1.7.10) or `- path: /path` with an optional `label`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Repository {
+    Path(String),
+    Entry { path: String, label: Option<String> },
+}
+
+/// Keys of [`Settings`]; everything else ends up in [`Import::skipped`].
+const KNOWN_KEYS: &[&str] = &[
+    "source_directories",
+    "repositories",
+    "exclude_patterns",
+    "exclude_from",
+    "patterns_from",
+    "exclude_caches",
+    "exclude_if_present",
+    "keep_exclude_tags",
+    "one_file_system",
+    "compression",
+    "archive_name_format",
+    "encryption_passcommand",
+    "remote_path",
+    "ssh_command",
+    "lock_wait",
+    "retries",
+    "upload_rate_limit",
+    "extra_borg_options",
+    "keep_daily",
+    "keep_weekly",
+    "keep_monthly",
+    "keep_yearly",
+];
+
+/// Convert the borgmatic config `yaml`; each repo gets a preset named `preset`.
+pub fn convert(yaml: &str, preset: &str) -> Result<Import> {
+    let raw: Value = serde_norway::from_str(yaml).context("Not a valid borgmatic YAML config")?;
+    let Value::Mapping(top) = raw else {
+        anyhow::bail!("Not a borgmatic config: expected a mapping at the top level");
+    };
+
+    let mut flat = serde_norway::Mapping::new();
+    let mut skipped = Vec::new();
+    for (key, value) in top {
+        let name = key.as_str().unwrap_or_default().to_string();
+        match value {
+            Value::Mapping(section) if LEGACY_SECTIONS.contains(&name.as_str()) => {
+                flat.extend(section);
+            }
+            value => {
+                flat.insert(key, value);
+            }
+        }
+    }
+    for key in flat.keys() {
+        let key = key.as_str().unwrap_or_default();
+        if !KNOWN_KEYS.contains(&key) {
+            skipped.push(skip_reason(key));
+        }
+    }
+    flat.retain(|key, _| KNOWN_KEYS.contains(&key.as_str().unwrap_or_default()));
+    let settings: Settings =
+        serde_norway::from_value(Value::Mapping(flat)).context("Unsupported borgmatic setting")?;
+
+    if settings.repositories.is_empty() {
+        anyhow::bail!("The borgmatic config lists no repositories");
+    }
+
+    let mut extra_args = Vec::new();
+    for (command, options) in &settings.extra_borg_options {
+        if command == "create" {
+            extra_args.extend(options.split_whitespace().map(str::to_string));
+        } else {
+            skipped.push(format!(
+                "extra_borg_options.{}: only options for `borg create` are carried over",
+                command
+            ));
+        }
+    }
+
+    let archive_name_template = match &settings.archive_name_format {
+        Some(format) if template::validate_archive_name_template(format).is_ok() => {
+            Some(format.clone())
+        }
+        Some(format) => {
+            skipped.push(format!(
+                "archive_name_format '{}': uses placeholders borg-tool does not know",
+                format
+            ));
+            None
+        }
+        None => None,
+    };
+
+    let (ssh_identity, ssh_port) = match &settings.ssh_command {
+        Some(command) => match parse_ssh_command(command) {
+            Some(parsed) => parsed,
+            None => {
+                skipped.push(format!(
+                    "ssh_command '{}': only `ssh -i <key> -p <port>` is carried over",
+                    command
+                ));
+                (None, None)
+            }
+        },
+        None => (None, None),
+    };
+
+    let backup = BackupConfig {
+        name: preset.to_string(),
+        includes: settings.source_directories,
+        excludes: settings.exclude_patterns,
+        exclude_from: settings.exclude_from,
+        patterns_from: settings.patterns_from,
+        compression: settings.compression,
+        one_file_system: settings.one_file_system,
+        exclude_caches: settings.exclude_caches,
+        exclude_if_present: settings.exclude_if_present,
+        keep_exclude_tags: settings.keep_exclude_tags,
+        archive_name_template,
+        extra_args,
+        keep_daily: settings.keep_daily,
+        keep_weekly: settings.keep_weekly,
+        keep_monthly: settings.keep_monthly,
+        keep_yearly: settings.keep_yearly,
+        ..Default::default()
+    };
+
+    let mut repos: Vec<RepoConfig> = Vec::new();
+    for repository in settings.repositories {
+        let (path, label) = match repository {
+            Repository::Path(path) => (path, None),
+            Repository::Entry { path, label } => (path, label),
+        };
+        let base = label.unwrap_or_else(|| name_for(&path));
+        let name = unique_name(&base, |name| repos.iter().any(|r| r.name == name));
+        repos.push(RepoConfig {
+            name,
+            repo: path,
+            passcommand: settings.encryption_passcommand.clone(),
+            remote_borg_path: settings.remote_path.clone(),
+            ssh_identity: ssh_identity.clone(),
+            ssh_port,
+            upload_ratelimit_kib: settings.upload_rate_limit,
+            lock_wait_seconds: settings.lock_wait,
+            retries: settings.retries,
+            backups: vec![backup.clone()],
+            ..Default::default()
+        });
+    }
+
+    Ok(Import { repos, skipped })
+}
+
+/// Add `imported` to `cfg`. A repo whose location is already configured gets the preset
+/// added to it; new repos are renamed (`-2`, `-3`, ...) when their name is taken.
+/// Returns one line per change.
+pub fn merge_into(cfg: &mut Config, imported: Vec<RepoConfig>) -> Result<Vec<String>> {
+    let mut changes = Vec::new();
+    for mut repo in imported {
+        if let Some(existing) = cfg.repos.iter_mut().find(|r| r.repo == repo.repo) {
+            for backup in repo.backups {
+                if existing.backups.iter().any(|b| b.name == backup.name) {
+                    anyhow::bail!(
+                        "Repo '{}' already has a preset '{}'; choose another name with --preset",
+                        existing.name,
+                        backup.name
+                    );
+                }
+                changes.push(format!(
+                    "New preset '{}' in repo '{}'",
+                    backup.name, existing.name
+                ));
+                existing.backups.push(backup);
+            }
+            continue;
+        }
+        repo.name = unique_name(&repo.name, |name| cfg.repos.iter().any(|r| r.name == name));
+        changes.push(format!("New repo '{}' ({})", repo.name, repo.repo));
+        cfg.repos.push(repo);
+    }
+    Ok(changes)
+}
+
+fn skip_reason(key: &str) -> String {
+    match key {
+        "encryption_passphrase" => {
+            "encryption_passphrase: set passcommand or passphrase_file instead".to_string()
+        }
+        "keep_within" | "keep_secondly" | "keep_minutely" | "keep_hourly" => {
+            format!(
+                "{}: borg-tool prunes by daily/weekly/monthly/yearly only",
+                key
+            )
+        }
+        _ => format!("{}: no borg-tool equivalent", key),
+    }
+}
+
+/// Repo name from its location: the ssh host, or the last path component.
+fn name_for(path: &str) -> String {
+    let name = crate::borg::extract_ssh_host(path).unwrap_or_else(|| {
+        path.trim_end_matches('/')
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    });
+    if name.is_empty() {
+        "borgmatic".to_string()
+    } else {
+        name
+    }
+}
+
+fn unique_name(base: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|name| !taken(name))
+        .expect("unbounded range")
+}
+
+/// `ssh -i <key> -p <port>` → identity and port; `None` for anything else.
+fn parse_ssh_command(command: &str) -> Option<(Option<std::path::PathBuf>, Option<u16>)> {
+    let mut words = command.split_whitespace();
+    if words.next()? != "ssh" {
+        return None;
+    }
+    let (mut identity, mut port) = (None, None);
+    while let Some(word) = words.next() {
+        match word {
+            "-i" => identity = Some(words.next()?.into()),
+            "-p" => port = Some(words.next()?.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some((identity, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_flat_config() {
+        let import = convert(
+            r#"
+source_directories:
+    - /home
+    - /etc
+repositories:
+    - path: ssh://backup@nas.example/./borg
+      label: nas
+    - path: /mnt/usb/borg
+exclude_patterns:
+    - '*.pyc'
+    - /home/*/.cache
+exclude_from:
+    - /etc/borgmatic/excludes
+exclude_caches: true
+exclude_if_present:
+    - .nobackup
+compression: zstd,6
+encryption_passcommand: pass show borg
+ssh_command: ssh -i /root/.ssh/borg -p 2222
+archive_name_format: '{hostname}-{now:%Y-%m-%dT%H:%M:%S}'
+keep_daily: 7
+keep_weekly: 4
+keep_hourly: 24
+checks:
+    - name: repository
+"#,
+            "home",
+        )
+        .unwrap();
+
+        assert_eq!(import.repos.len(), 2);
+        let nas = &import.repos[0];
+        assert_eq!(nas.name, "nas");
+        assert_eq!(nas.passcommand.as_deref(), Some("pass show borg"));
+        assert_eq!(nas.ssh_port, Some(2222));
+        let home = &nas.backups[0];
+        assert_eq!(home.name, "home");
+        assert_eq!(home.includes, ["/home", "/etc"]);
+        assert_eq!(home.excludes, ["*.pyc", "/home/*/.cache"]);
+        assert_eq!(
+            home.exclude_from,
+            [PathBuf::from("/etc/borgmatic/excludes")]
+        );
+        assert_eq!(home.compression.as_deref(), Some("zstd,6"));
+        assert!(home.exclude_caches);
+        assert_eq!(home.exclude_if_present, [".nobackup"]);
+        assert_eq!(home.keep_daily, Some(7));
+        assert_eq!(
+            home.archive_name_template.as_deref(),
+            Some("{hostname}-{now:%Y-%m-%dT%H:%M:%S}")
+        );
+        assert_eq!(import.repos[1].name, "borg");
+        assert_eq!(import.skipped.len(), 2);
+        assert!(import.skipped[0].starts_with("keep_hourly"));
+        assert!(import.skipped[1].starts_with("checks"));
+    }
+
+    #[test]
+    fn converts_sectioned_config() {
+        let import = convert(
+            r#"
+location:
+    source_directories: [/srv]
+    repositories:
+        - backup@nas:srv.borg
+        - backup@nas:srv2.borg
+    one_file_system: true
+storage:
+    archive_name_format: '{fqdn}-{now}'
+    encryption_passphrase: secret
+retention:
+    keep_monthly: 6
+"#,
+            "srv",
+        )
+        .unwrap();
+
+        let names: Vec<&str> = import.repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["nas", "nas-2"]);
+        let srv = &import.repos[0].backups[0];
+        assert!(srv.one_file_system);
+        assert_eq!(srv.keep_monthly, Some(6));
+        assert_eq!(srv.archive_name_template, None);
+        assert_eq!(import.skipped.len(), 2);
+    }
+
+    #[test]
+    fn merge_adds_presets_to_known_repos() {
+        let mut cfg: Config = toml::from_str(
+            r#"
+[[repos]]
+name = "nas"
+repo = "/srv/borg"
+
+[[repos.backups]]
+name = "etc"
+includes = ["/etc"]
+"#,
+        )
+        .unwrap();
+        let import = convert(
+            "source_directories: [/home]\nrepositories: [/srv/borg, /mnt/nas]\n",
+            "home",
+        )
+        .unwrap();
+
+        let changes = merge_into(&mut cfg, import.repos.clone()).unwrap();
+        assert_eq!(
+            changes,
+            [
+                "New preset 'home' in repo 'nas'",
+                "New repo 'nas-2' (/mnt/nas)"
+            ]
+        );
+        assert_eq!(cfg.repos[0].backups.len(), 2);
+        cfg.validate().unwrap();
+
+        let err = merge_into(&mut cfg, import.repos).unwrap_err();
+        assert!(err.to_string().contains("--preset"));
+    }
+}
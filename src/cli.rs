@@ -1,6 +1,11 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use chrono::NaiveDateTime;
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+
+use crate::borg;
+use crate::export::{self, ExportFormat};
+use crate::filter::{self, ItemKind, ItemSort};
 
 /// CLI entry point.
 #[derive(Parser, Debug)]
@@ -13,9 +18,42 @@ pub struct Cli {
     /// Path to the config file
     #[arg(short, long)]
     pub config: Option<PathBuf>,
+    /// Use config.<PROFILE>.toml from the config directory (default: `default_profile` in meta.toml)
+    #[arg(short, long, conflicts_with = "config")]
+    pub profile: Option<String>,
     /// Which configured repo to use (by name)
     #[arg(short, long)]
     pub repo: Option<String>,
+    /// Use this repository path/URL directly instead of a configured repo (no config needed)
+    #[arg(long, value_name = "PATH_OR_URL", conflicts_with = "repo")]
+    pub repo_url: Option<String>,
+    /// Increase verbosity: -v logs every borg command with its exit status, -vv also reports
+    /// timing and output size of every borg process
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Only errors on stderr: no spinners, status lines or notes (for scripts)
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Timestamped status lines instead of spinners, no screen clearing (default when not a TTY or CI=true)
+    #[arg(long)]
+    pub plain: bool,
+    /// Never prompt: fail with an error wherever input would be needed (for cron and scripts)
+    #[arg(long, visible_alias = "batch")]
+    pub non_interactive: bool,
+    /// Skip the ssh reachability check of remote repos (like `probe_ssh = false`)
+    #[arg(long)]
+    pub no_probe: bool,
+    /// Print the first borg command line that would run (passphrase redacted) and exit without
+    /// changing anything. Commands that pick archives or files from a listing first (files
+    /// without an archive, delete, find, grep) print that `borg list` call.
+    #[arg(long)]
+    pub print_command: bool,
+    /// Echo every borg command line to stderr as it runs (passphrase redacted)
+    #[arg(long)]
+    pub print_commands: bool,
+    /// Print machine-readable JSON on stdout (list, files, info, backup, status, history, config show)
+    #[arg(long)]
+    pub json: bool,
 
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -24,30 +62,433 @@ pub struct Cli {
 #[derive(Subcommand, Debug, Clone)]
 pub enum Commands {
     /// List all archives in the configured repository
-    List,
+    List {
+        /// Only archives created at or after this date (YYYY-MM-DD[ HH:MM[:SS]] or an age like 7d, 2w, 12h)
+        #[arg(long, value_name = "DATE", value_parser = filter::parse_since)]
+        since: Option<NaiveDateTime>,
+        /// Only archives created at or before this date (a bare date includes the whole day)
+        #[arg(long, value_name = "DATE", value_parser = filter::parse_until)]
+        until: Option<NaiveDateTime>,
+        /// Only archives whose name starts with this
+        #[arg(long, conflicts_with = "glob")]
+        prefix: Option<String>,
+        /// Only archives whose name matches this shell pattern (borg --glob-archives)
+        #[arg(long, value_name = "PATTERN")]
+        glob: Option<String>,
+        /// Only the last N archives in sort order
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
+        /// Sort by timestamp (default), name or id (borg --sort-by)
+        #[arg(long, value_name = "KEY", value_parser = filter::ARCHIVE_SORT_KEYS)]
+        sort: Option<String>,
+        /// Print each archive with this template, e.g. '{name}\t{time}' ({comment} also works)
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
+        /// Also show the .checkpoint archives of interrupted backups
+        #[arg(long)]
+        include_checkpoints: bool,
+    },
     /// List files inside a chosen archive
     Files {
         /// Archive name; if omitted, you will be prompted to choose
         archive: Option<String>,
+        /// Show an indented tree with per-directory item counts and sizes
+        #[arg(long)]
+        tree: bool,
+        /// Write the full listing (path, type, size, mtime) to this file instead of printing it
+        #[arg(long, value_name = "PATH", conflicts_with = "tree")]
+        output: Option<PathBuf>,
+        /// Include a per-file checksum computed by borg (sha256 or xxh64; slow, reads all data)
+        #[arg(
+            long,
+            value_name = "ALGO",
+            num_args = 0..=1,
+            default_missing_value = "sha256",
+            value_parser = borg::CHECKSUM_ALGORITHMS,
+            conflicts_with = "tree"
+        )]
+        checksums: Option<String>,
+        /// Format of the --output file (csv or jsonl; inferred from the extension if omitted),
+        /// or a line template like '{path}\t{size}\t{mtime}' for printing
+        #[arg(long, value_name = "FORMAT", value_parser = parse_files_format)]
+        format: Option<FilesFormat>,
+        /// Sort by path, size (largest first) or mtime (newest first)
+        #[arg(long, value_name = "KEY", value_parser = filter::parse_item_sort, conflicts_with = "tree")]
+        sort: Option<ItemSort>,
+        /// Reverse the order of --sort
+        #[arg(long, requires = "sort")]
+        reverse: bool,
+        /// Only list files (f), directories (d) or symlinks (l)
+        #[arg(long = "type", value_name = "TYPE", value_parser = filter::parse_item_kind)]
+        kind: Option<ItemKind>,
+        /// Only list paths matching this glob; without a `/` it matches the file name at any depth
+        #[arg(long, value_name = "PATTERN")]
+        glob: Option<String>,
+    },
+    /// Search file contents inside an archive (extracts every candidate file; slow on remote repos)
+    Grep {
+        /// Archive name
+        archive: String,
+        /// Text to search for (plain substring)
+        pattern: String,
+        /// Only search files below this path inside the archive
+        path_prefix: Option<String>,
+        /// Match case-insensitively
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// Lines of context around each match
+        #[arg(short = 'C', long, value_name = "N", default_value_t = 2)]
+        context: usize,
+    },
+    /// Find which archives contain paths matching a glob (or regex), newest archive first
+    Find {
+        /// Shell glob like `*.conf` or `home/*/notes.md`; without a `/` it matches the file name at any depth
+        pattern: String,
+        /// Treat the pattern as a regular expression on the full path
+        #[arg(long)]
+        regex: bool,
+        /// Only search the newest N archives
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
     },
     /// Start interactive navigation
     Interactive,
-    /// Mount an archive to a target path
+    /// Full-screen view with repo, archive, file and detail panes
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Mount an archive, or the whole repository, to a target path
     Mount {
-        /// Archive name
-        archive: String,
+        /// Archive name; without it every archive shows up as a subdirectory
+        archive: Option<String>,
         /// Target mountpoint
         #[arg(short, long)]
         target: Option<PathBuf>,
+        /// Open the mountpoint in the file manager (xdg-open/open)
+        #[arg(long, conflicts_with = "shell")]
+        open: bool,
+        /// Start $SHELL in the mountpoint; offers to unmount when it exits
+        #[arg(long)]
+        shell: bool,
     },
+    /// Put paths from an archive back at their original locations, after listing what would be overwritten
+    Restore {
+        /// Archive name; chosen interactively if omitted
+        archive: Option<String>,
+        /// Paths inside the archive (e.g. etc/nginx); asked for if omitted
+        paths: Vec<String>,
+        /// Restore owners by archived numeric UID/GID instead of user/group names (as root)
+        #[arg(long)]
+        numeric_ids: bool,
+        /// Restore holes of sparse files (disk images, databases) as holes instead of zeros
+        #[arg(long)]
+        sparse: bool,
+        /// Show what would be restored and overwritten without extracting
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Guided full-system restore of an archive into a target root (disaster recovery)
+    RestoreSystem,
     /// Unmount a mounted archive (by mountpoint)
     Umount {
         /// Mountpoint to unmount
-        mountpoint: PathBuf,
+        #[arg(required_unless_present = "all")]
+        mountpoint: Option<PathBuf>,
+        /// Unmount everything borg-tool mounted (only of --repo, if given)
+        #[arg(long, conflicts_with = "mountpoint")]
+        all: bool,
+        /// Detach a busy mount now and finish unmounting once nothing uses it
+        #[arg(long)]
+        lazy: bool,
     },
+    /// Show archives and repositories mounted by borg-tool (filter by repo with --repo)
+    Mounts,
     /// Create a configured backup
     Backup {
         /// Backup configuration name; if omitted, you will be prompted
         backup: Option<String>,
+        /// Run every preset of every repo (or only of --repo) one after another
+        #[arg(long, conflicts_with = "backup")]
+        all: bool,
+        /// Upload limit in KiB/s for this run, overriding upload_ratelimit_kib (0 = unlimited)
+        #[arg(long, value_name = "KIB")]
+        ratelimit: Option<u64>,
+        /// Store this comment with the new archive(s), overriding the preset's `comment`
+        #[arg(long, value_name = "TEXT")]
+        comment: Option<String>,
+        /// Print the borg create command (with the automatic repo exclude) instead of running it
+        #[arg(long, conflicts_with = "all")]
+        dry_run: bool,
+        /// With --dry-run, also let borg list the files it would archive (create --dry-run --list)
+        #[arg(long, requires = "dry_run")]
+        list: bool,
+    },
+    /// Show what changed between two archives (added, removed and modified paths)
+    Diff {
+        /// Older archive
+        older: String,
+        /// Newer archive
+        newer: String,
+    },
+    /// Show size, compression and deduplication statistics of the repository or an archive
+    Info {
+        /// Archive name; without it, repository-wide statistics are shown
+        archive: Option<String>,
+    },
+    /// Verify repository consistency (borg check); can take hours on large repos
+    Check {
+        /// Check only this archive instead of the whole repository
+        archive: Option<String>,
+        /// Try to fix problems found (asks for confirmation)
+        #[arg(long)]
+        repair: bool,
+        /// Also read and verify all data chunks (much slower)
+        #[arg(long)]
+        verify_data: bool,
+        /// Skip the --repair confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Apply the retention policy (keep_daily, keep_weekly, ...) of backup presets
+    Prune {
+        /// Backup configuration name; if omitted, every preset with a retention policy is pruned
+        backup: Option<String>,
+        /// Show which archives would be removed without removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Extract paths (or the whole archive) into a directory, without prompting
+    Extract {
+        /// Archive name
+        archive: String,
+        /// Paths inside the archive; everything if omitted
+        paths: Vec<String>,
+        /// Directory to extract into (created if missing)
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        dest: PathBuf,
+        /// Drop this many leading path elements from extracted files
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        strip_components: usize,
+    },
+    /// Write an archive (or paths inside it) to a tarball, for restores without borg
+    ExportTar {
+        /// Archive name
+        archive: String,
+        /// Tarball to create
+        file: PathBuf,
+        /// Only export these paths inside the archive
+        paths: Vec<String>,
+        /// gz, zstd or none; inferred from the file name (.tar.gz, .tgz, .tar.zst) if omitted
+        #[arg(long, value_parser = borg::TAR_COMPRESSIONS)]
+        compression: Option<String>,
+    },
+    /// Reclaim space freed by delete/prune (borg compact, borg >= 1.2)
+    Compact,
+    /// Remove a stale repository lock left by a crashed borg (borg break-lock)
+    BreakLock,
+    /// Delete the named archives, all but the newest N with --keep-last, or checkpoints
+    #[command(group(
+        ArgGroup::new("selection")
+            .required(true)
+            .args(["archives", "keep_last", "checkpoints"])
+    ))]
+    Delete {
+        /// Archives to delete
+        #[arg(conflicts_with = "keep_last")]
+        archives: Vec<String>,
+        /// Delete all but this many newest archives
+        #[arg(long, value_name = "N")]
+        keep_last: Option<usize>,
+        /// With --keep-last: only consider archives whose name starts with this prefix
+        #[arg(long, requires = "keep_last")]
+        prefix: Option<String>,
+        /// Delete the .checkpoint archives left behind by interrupted backups
+        #[arg(long, conflicts_with_all = ["archives", "keep_last"])]
+        checkpoints: bool,
+        /// Show which archives would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Show the newest archive of every preset and flag those older than their max_age
+    Status,
+    /// Monitoring plugin (Nagios/Icinga): one status line, exit 0 OK, 1 WARNING, 2 CRITICAL, 3 UNKNOWN
+    CheckHealth {
+        /// Warn when the newest archive is older (default: each preset's max_age)
+        #[arg(short, long, value_name = "AGE", value_parser = filter::parse_age)]
+        warning: Option<chrono::Duration>,
+        /// Critical when the newest archive is older (default: twice the warning age)
+        #[arg(short, long, value_name = "AGE", value_parser = filter::parse_age)]
+        critical: Option<chrono::Duration>,
     },
+    /// Show recorded backup, prune and check runs (filter by repo with --repo)
+    History {
+        /// Only runs of this action
+        #[arg(long, value_parser = ["backup", "prune", "check"])]
+        action: Option<String>,
+        /// Only failed runs
+        #[arg(long)]
+        failed: bool,
+        /// Show at most this many of the newest runs
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show running backups and borg/ssh processes started by borg-tool (this or earlier runs)
+    Ps {
+        /// Send SIGTERM to these PIDs (must be listed by `ps`); a backup run stops like on Ctrl+C
+        #[arg(long, value_name = "PID", num_args = 1..)]
+        kill: Vec<u32>,
+    },
+    /// Generate files for running borg-tool unattended
+    Generate {
+        #[command(subcommand)]
+        target: GenerateCommands,
+    },
+    /// Manage the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+    /// Add repos and presets from another backup tool's config to the config file
+    Import {
+        #[command(subcommand)]
+        source: ImportCommands,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ImportCommands {
+    /// Convert a borgmatic YAML config (repositories, sources, excludes, compression, retention)
+    Borgmatic {
+        /// borgmatic config file, e.g. /etc/borgmatic/config.yaml
+        path: PathBuf,
+        /// Name of the preset created in each repo (default: the file name without extension)
+        #[arg(long)]
+        preset: Option<String>,
+        /// Print the converted repos instead of saving them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum GenerateCommands {
+    /// systemd .service and .timer units running a backup preset of --repo
+    Systemd {
+        /// Backup preset to run
+        #[arg(long)]
+        preset: String,
+        /// systemd calendar expression for the timer
+        #[arg(long, value_name = "CALENDAR", default_value = "daily")]
+        on_calendar: String,
+        /// Write the units to ~/.config/systemd/user instead of printing them
+        #[arg(long)]
+        install: bool,
+    },
+    /// Man pages (borg-tool.1 with the config file reference, one page per subcommand)
+    #[command(hide = true)]
+    Man {
+        /// Write all pages to this directory instead of printing borg-tool.1
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommands {
+    /// Print the effective configuration and where each value comes from
+    Show {
+        /// Print the fully resolved config instead (defaults and per-repo fallbacks filled
+        /// in, secrets redacted); `--json` implies json
+        #[arg(long, value_enum, value_name = "FORMAT")]
+        format: Option<ConfigFormat>,
+    },
+    /// Write a commented example config to the config path (--config or the XDG default)
+    Init {
+        /// Write it here instead
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Download a config from an https URL into the config path (--config or the XDG default)
+    Fetch {
+        /// https URL of the config file
+        url: String,
+        /// Expected SHA-256 of the downloaded file (hex)
+        #[arg(long)]
+        sha256: Option<String>,
+        /// https URL of a detached GPG signature to verify with `gpg --verify`
+        #[arg(long)]
+        signature_url: Option<String>,
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// `files --format`: an export format for `--output`, or a line template.
+#[derive(Debug, Clone)]
+pub enum FilesFormat {
+    Export(ExportFormat),
+    Template(String),
+}
+
+fn parse_files_format(raw: &str) -> Result<FilesFormat, String> {
+    if raw.contains('{') {
+        return Ok(FilesFormat::Template(raw.to_string()));
+    }
+    export::parse_format(raw)
+        .map(FilesFormat::Export)
+        .map_err(|e| format!("{}, or a template like '{{path}}\\t{{size}}'", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delete_takes_exactly_one_selection() {
+        let cli = Cli::try_parse_from(["borg-tool", "delete", "--checkpoints"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Delete {
+                checkpoints: true,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["borg-tool", "delete", "--keep-last", "3"]).is_ok());
+        assert!(Cli::try_parse_from(["borg-tool", "delete", "a-1"]).is_ok());
+
+        assert!(Cli::try_parse_from(["borg-tool", "delete"]).is_err());
+        assert!(Cli::try_parse_from(["borg-tool", "delete", "--checkpoints", "a-1"]).is_err());
+        assert!(
+            Cli::try_parse_from(["borg-tool", "delete", "--checkpoints", "--keep-last", "3"])
+                .is_err()
+        );
+    }
+    #[test]
+    fn json_and_files_output_are_separate_flags() {
+        let cli = Cli::try_parse_from(["borg-tool", "--json", "files", "a-1", "--output", "a.csv"])
+            .unwrap();
+        assert!(cli.json);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Files {
+                output: Some(ref path),
+                ..
+            }) if path == std::path::Path::new("a.csv")
+        ));
+    }
 }
@@ -0,0 +1,483 @@
+//! Flows of the `borg-tool` subcommands that take more than one library call: which archives
+//! a delete removes, what a restore replaces, `backup --all`, `status`/`check-health` and the
+//! borgmatic import. The binary parses arguments, prompts and prints around them.
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+
+use crate::borg::{self, BackupRun, BorgArchive, BorgItem};
+use crate::borgmatic;
+use crate::config::{self, BackupConfig, Config, ConfigOrigin, RepoConfig, RepoCtx};
+use crate::filter::ArchiveFilter;
+use crate::history::HistoryEntry;
+use crate::process;
+use crate::status::{self, Health, HealthThresholds, PresetStatus};
+
+/// The preset `name` of `repo_ctx`, or an error listing the presets there are.
+pub fn find_backup<'a>(repo_ctx: &'a RepoCtx, name: &str) -> Result<&'a BackupConfig> {
+    repo_ctx
+        .backups
+        .iter()
+        .find(|b| b.name == name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = repo_ctx.backups.iter().map(|b| b.name.as_str()).collect();
+            anyhow::anyhow!(
+                "Backup '{}' not found. Available: {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+/// `backup --ratelimit` and `--comment`: replace the configured upload limit and archive
+/// comment of every preset of `repo_ctx` for this run.
+pub fn override_presets(repo_ctx: &mut RepoCtx, ratelimit: Option<u64>, comment: Option<&str>) {
+    for preset in &mut repo_ctx.backups {
+        if ratelimit.is_some() {
+            preset.upload_ratelimit_kib = ratelimit;
+        }
+        if let Some(comment) = comment {
+            preset.comment = Some(comment.to_string());
+        }
+    }
+}
+
+/// Which archives `delete` removes.
+#[derive(Debug, Clone, Copy)]
+pub enum DeleteSelection<'a> {
+    Named(&'a [String]),
+    /// All but the newest `keep` archives, of those starting with `prefix` if given
+    KeepLast {
+        keep: usize,
+        prefix: Option<&'a str>,
+    },
+    Checkpoints,
+}
+
+impl DeleteSelection<'_> {
+    /// The listing to pick from: `--keep-last` counts complete archives only, names and
+    /// `--checkpoints` may pick any.
+    pub fn listing(&self) -> ArchiveFilter {
+        ArchiveFilter {
+            include_checkpoints: !matches!(self, DeleteSelection::KeepLast { .. }),
+            ..Default::default()
+        }
+    }
+}
+
+/// The archives of `listed` that `selection` deletes; named archives must all exist.
+pub fn archives_to_delete(
+    listed: &[BorgArchive],
+    selection: DeleteSelection,
+) -> Result<Vec<BorgArchive>> {
+    match selection {
+        DeleteSelection::Checkpoints => Ok(borg::checkpoint_archives(listed)),
+        DeleteSelection::KeepLast { keep, prefix } => {
+            Ok(borg::archives_beyond_keep_last(listed, keep, prefix))
+        }
+        DeleteSelection::Named(names) => {
+            let missing: Vec<&str> = names
+                .iter()
+                .filter(|name| !listed.iter().any(|a| a.name == **name))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                anyhow::bail!("Archive(s) not found: {}", missing.join(", "));
+            }
+            Ok(listed
+                .iter()
+                .filter(|a| names.contains(&a.name))
+                .cloned()
+                .collect())
+        }
+    }
+}
+
+/// `restore` paths as borg archives them: relative, so `/etc/nginx` is accepted for
+/// `etc/nginx`. A restore needs at least one.
+pub fn restore_paths_from_args(paths: &[String]) -> Result<Vec<String>> {
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|p| p.trim_start_matches('/').to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if paths.is_empty() {
+        anyhow::bail!("No paths to restore given; use restore-system to restore a whole archive");
+    }
+    Ok(paths)
+}
+
+/// What a restore of some paths of an archive puts in place, for the confirmation.
+#[derive(Debug, Clone)]
+pub struct RestorePlan {
+    pub items: Vec<BorgItem>,
+    /// Existing files and links the restore replaces
+    pub overwritten: Vec<PathBuf>,
+}
+
+/// List what restoring `paths` of `archive` below `target_root` would write and replace.
+pub fn plan_restore(
+    ctx: &RepoCtx,
+    archive: &str,
+    paths: &[String],
+    target_root: &Path,
+    passphrase: Option<&str>,
+) -> Result<RestorePlan> {
+    let items = borg::list_paths(ctx, archive, paths, passphrase)?;
+    if items.is_empty() {
+        anyhow::bail!("Nothing in {} matches {}", archive, paths.join(", "));
+    }
+    let overwritten = borg::existing_targets(target_root, &items);
+    Ok(RestorePlan { items, overwritten })
+}
+
+/// Run every preset of `repos`, one after the other; a failing preset or repo does not stop
+/// the rest. Stops early on an interrupt.
+pub fn backup_all(repos: &[RepoCtx]) -> Result<Vec<BackupRun>> {
+    let mut runs = Vec::new();
+    for repo_ctx in repos {
+        // repos may have different passphrases; one failing must not stop the rest
+        let pass = borg::ensure_passphrase(repo_ctx);
+        for preset in &repo_ctx.backups {
+            if process::interrupted() {
+                return Ok(runs);
+            }
+            let started = Instant::now();
+            let result = match &pass {
+                Ok(pass) => borg::run_backup(repo_ctx, preset, pass.as_deref()),
+                Err(err) => Err(anyhow::anyhow!("{:#}", err)),
+            };
+            runs.push(BackupRun::new(
+                &repo_ctx.name,
+                &preset.name,
+                started.elapsed(),
+                result,
+            ));
+        }
+    }
+    if runs.is_empty() {
+        anyhow::bail!("No backup presets configured");
+    }
+    Ok(runs)
+}
+
+/// Fail when any of `runs` failed.
+pub fn ensure_backups_succeeded(runs: &[BackupRun]) -> Result<()> {
+    let failed = runs.iter().filter(|r| !r.succeeded()).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} backup(s) failed", failed, runs.len());
+    }
+    Ok(())
+}
+
+/// Freshness of every preset of `repos`; a repo that cannot be listed marks its presets.
+pub fn collect_statuses(repos: &[RepoCtx]) -> Vec<PresetStatus> {
+    let mut statuses = Vec::new();
+    for repo_ctx in repos.iter().filter(|r| !r.backups.is_empty()) {
+        let archives = borg::ensure_passphrase(repo_ctx)
+            .and_then(|pass| borg::list_archives(repo_ctx, pass.as_deref()));
+        statuses.extend(match archives {
+            Ok(archives) => status::preset_statuses_now(repo_ctx, &archives),
+            Err(err) => status::unavailable(repo_ctx, &format!("{:#}", err)),
+        });
+    }
+    statuses
+}
+
+/// Fail when any of `statuses` is stale or could not be checked.
+pub fn ensure_no_problems(statuses: &[PresetStatus]) -> Result<()> {
+    let problems = statuses.iter().filter(|s| s.is_problem()).count();
+    if problems > 0 {
+        anyhow::bail!("{} preset(s) need attention", problems);
+    }
+    Ok(())
+}
+
+/// `check-health`: the state of the presets of `repos` and its plugin output line.
+pub fn check_health(
+    repos: &[RepoCtx],
+    history: &[HistoryEntry],
+    thresholds: HealthThresholds,
+) -> (Health, String) {
+    status::evaluate_health(&collect_statuses(repos), history, thresholds)
+}
+
+/// The config at `cli_config` (or the default path) for `config show`; the built-in defaults
+/// when no config file was given and there is none at the default path.
+pub fn load_config_for_show(
+    cli_config: Option<PathBuf>,
+) -> Result<(Config, PathBuf, ConfigOrigin)> {
+    match config::load_config_with_origin(cli_config.clone()) {
+        Err(err) if cli_config.is_none() && config::is_not_found(&err) => Ok((
+            Config::default(),
+            config::default_config_path(),
+            ConfigOrigin::Defaults,
+        )),
+        loaded => loaded,
+    }
+}
+
+/// A borgmatic config merged into the borg-tool config, not saved yet.
+#[derive(Debug, Clone)]
+pub struct BorgmaticImport {
+    pub config: Config,
+    /// What the merge added or changed, one line each
+    pub changes: Vec<String>,
+    /// Settings that were not carried over, with the reason
+    pub skipped: Vec<String>,
+    /// The repos that gained a preset, as TOML
+    pub preview: String,
+}
+
+/// Merge the borgmatic config at `path` into the config at `config_path` (empty if there is
+/// none) as the preset `preset`, named after the file when `None`.
+pub fn import_borgmatic(
+    path: &Path,
+    preset: Option<&str>,
+    config_path: &Path,
+) -> Result<BorgmaticImport> {
+    let yaml = std::fs::read_to_string(path).with_context(|| format!("Read {}", path.display()))?;
+    let preset = match preset {
+        Some(preset) => preset.to_string(),
+        None => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "borgmatic".to_string()),
+    };
+    let import = borgmatic::convert(&yaml, &preset)
+        .with_context(|| format!("Cannot import {}", path.display()))?;
+
+    let mut cfg = if config_path.exists() {
+        config::load_config(&config_path.to_path_buf())?
+    } else {
+        Config::default()
+    };
+    let presets_before: Vec<usize> = cfg.repos.iter().map(|r| r.backups.len()).collect();
+    let changes = borgmatic::merge_into(&mut cfg, import.repos)?;
+    cfg.validate()
+        .context("The imported settings do not form a valid config")?;
+
+    let touched: Vec<&RepoConfig> = cfg
+        .repos
+        .iter()
+        .enumerate()
+        .filter(|(i, r)| presets_before.get(*i) != Some(&r.backups.len()))
+        .map(|(_, r)| r)
+        .collect();
+    let mut table = toml::Table::new();
+    table.insert("repos".to_string(), toml::Value::try_from(touched)?);
+    let preview = toml::to_string_pretty(&table)?;
+    Ok(BorgmaticImport {
+        config: cfg,
+        changes,
+        skipped: import.skipped,
+        preview,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(name: &str, time: &str) -> BorgArchive {
+        BorgArchive {
+            name: name.into(),
+            time_utc: Some(time.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn delete_selection_picks_named_checkpoint_or_surplus_archives() {
+        let listed = vec![
+            archive("home-1", "2025-01-01T10:00:00"),
+            archive("home-2", "2025-01-02T10:00:00"),
+            archive("home-3.checkpoint", "2025-01-03T10:00:00"),
+            archive("home-3", "2025-01-03T11:00:00"),
+        ];
+        let names = |archives: Vec<BorgArchive>| -> Vec<String> {
+            archives.into_iter().map(|a| a.name).collect()
+        };
+
+        let named = ["home-2".to_string()];
+        assert_eq!(
+            names(archives_to_delete(&listed, DeleteSelection::Named(&named)).unwrap()),
+            ["home-2"]
+        );
+        let unknown = ["home-2".to_string(), "etc-1".to_string()];
+        let err = archives_to_delete(&listed, DeleteSelection::Named(&unknown)).unwrap_err();
+        assert_eq!(err.to_string(), "Archive(s) not found: etc-1");
+
+        assert_eq!(
+            names(archives_to_delete(&listed, DeleteSelection::Checkpoints).unwrap()),
+            ["home-3.checkpoint"]
+        );
+
+        let complete: Vec<BorgArchive> = listed
+            .iter()
+            .filter(|a| !a.name.contains("checkpoint"))
+            .cloned()
+            .collect();
+        let keep_last = DeleteSelection::KeepLast {
+            keep: 2,
+            prefix: Some("home-"),
+        };
+        assert!(!keep_last.listing().include_checkpoints);
+        assert!(DeleteSelection::Checkpoints.listing().include_checkpoints);
+        assert_eq!(
+            names(archives_to_delete(&complete, keep_last).unwrap()),
+            ["home-1"]
+        );
+    }
+
+    #[test]
+    fn restore_paths_are_relative_and_required() {
+        let paths = restore_paths_from_args(&["/etc/nginx".into(), "home/ann".into()]).unwrap();
+        assert_eq!(paths, ["etc/nginx", "home/ann"]);
+        assert!(restore_paths_from_args(&["/".into()]).is_err());
+        assert!(restore_paths_from_args(&[]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restore_plan_lists_files_it_would_replace() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        std::fs::create_dir_all(root.join("etc/nginx")).unwrap();
+        std::fs::write(root.join("etc/nginx/nginx.conf"), "old").unwrap();
+        let borg_bin = tmp.path().join("borg");
+        std::fs::write(
+            &borg_bin,
+            "#!/bin/sh\n\
+             echo '{\"path\": \"etc/nginx\", \"type\": \"d\"}'\n\
+             echo '{\"path\": \"etc/nginx/nginx.conf\", \"type\": \"-\"}'\n\
+             echo '{\"path\": \"etc/nginx/mime.types\", \"type\": \"-\"}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&borg_bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let ctx = RepoCtx {
+            name: "r".into(),
+            repo: "/repo".into(),
+            borg_bin: borg_bin.to_string_lossy().into_owned().into(),
+            ..Default::default()
+        };
+
+        let plan = plan_restore(&ctx, "host-1", &["etc/nginx".into()], &root, None).unwrap();
+        assert_eq!(plan.items.len(), 3);
+        assert_eq!(plan.overwritten, [root.join("etc/nginx/nginx.conf")]);
+    }
+
+    #[test]
+    fn backup_all_runs_every_preset_and_counts_failures() {
+        let preset = |name: &str| BackupConfig {
+            name: name.into(),
+            includes: vec!["/data".into()],
+            ..Default::default()
+        };
+        let printing = RepoCtx {
+            name: "printing".into(),
+            repo: "/srv/printing.borg".into(),
+            borg_bin: "/nonexistent/borg".into(),
+            print_command: true,
+            backups: vec![preset("home"), preset("etc")],
+            ..Default::default()
+        };
+        let broken = RepoCtx {
+            name: "broken".into(),
+            repo: "/srv/broken.borg".into(),
+            passcommand: Some("true".into()),
+            backups: vec![BackupConfig {
+                name: "empty".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let runs = backup_all(std::slice::from_ref(&printing)).unwrap();
+        assert_eq!(runs.len(), 2);
+        ensure_backups_succeeded(&runs).unwrap();
+
+        let runs = backup_all(&[broken, printing]).unwrap();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].preset, "empty");
+        assert!(!runs[0].succeeded());
+        let err = ensure_backups_succeeded(&runs).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 3 backup(s) failed");
+
+        assert!(backup_all(&[]).is_err());
+    }
+
+    #[test]
+    fn override_presets_replaces_only_what_is_given() {
+        let mut repo_ctx = RepoCtx {
+            backups: vec![BackupConfig {
+                name: "home".into(),
+                upload_ratelimit_kib: Some(500),
+                comment: Some("nightly".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        override_presets(&mut repo_ctx, None, Some("pre-upgrade"));
+        assert_eq!(repo_ctx.backups[0].upload_ratelimit_kib, Some(500));
+        assert_eq!(repo_ctx.backups[0].comment.as_deref(), Some("pre-upgrade"));
+
+        override_presets(&mut repo_ctx, Some(100), None);
+        assert_eq!(repo_ctx.backups[0].upload_ratelimit_kib, Some(100));
+        assert_eq!(repo_ctx.backups[0].comment.as_deref(), Some("pre-upgrade"));
+
+        assert!(find_backup(&repo_ctx, "home").is_ok());
+        let err = find_backup(&repo_ctx, "etc").unwrap_err();
+        assert_eq!(err.to_string(), "Backup 'etc' not found. Available: home");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_health_reports_unlisted_repos_as_critical() {
+        let repos = [RepoCtx {
+            name: "nas".into(),
+            repo: "/srv/nas.borg".into(),
+            borg_bin: "/nonexistent/borg".into(),
+            passcommand: Some("true".into()),
+            backups: vec![BackupConfig {
+                name: "home".into(),
+                includes: vec!["/home".into()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }];
+
+        let statuses = collect_statuses(&repos);
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].error.is_some());
+        assert!(ensure_no_problems(&statuses).is_err());
+        let (health, line) = check_health(&repos, &[], HealthThresholds::default());
+        assert_eq!(health, Health::Critical);
+        assert!(line.starts_with("BORG CRITICAL - nas/home: "), "{line}");
+    }
+
+    #[test]
+    fn borgmatic_import_merges_into_an_empty_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("nightly.yaml");
+        std::fs::write(
+            &source,
+            "repositories:\n  - path: /srv/nas.borg\nsource_directories:\n  - /home\nkeep_daily: 7\n",
+        )
+        .unwrap();
+
+        let import = import_borgmatic(&source, None, &tmp.path().join("config.toml")).unwrap();
+        assert_eq!(import.config.repos.len(), 1);
+        assert_eq!(import.config.repos[0].backups[0].name, "nightly");
+        assert!(
+            import.preview.contains("/srv/nas.borg"),
+            "{}",
+            import.preview
+        );
+        assert!(!import.changes.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Dump commands of `database` presets.
+//!
+//! A preset's `database` block is turned into a shell command whose stdout is streamed into
+//! `borg create --stdin-name`, like a hand-written `stdin_command`. Credentials are not part
+//! of the config: the dump tools read `~/.pgpass`/`PGPASSWORD` and `~/.my.cnf`/`MYSQL_PWD`.
+
+use crate::borg::shell_quote;
+use crate::config::{DatabaseConfig, DatabaseEngine};
+
+/// Shell command printing the dump of `db` on stdout.
+///
+/// PostgreSQL databases are dumped as plain SQL with `--create`, one `pg_dump` per database
+/// (restorable with `psql -f`); an empty list runs `pg_dumpall`. MySQL dumps all listed
+/// databases in one consistent `mysqldump --single-transaction --databases` call.
+pub fn dump_command(db: &DatabaseConfig) -> String {
+    let invocations: Vec<Vec<String>> = match db.engine {
+        DatabaseEngine::Postgresql if db.databases.is_empty() => {
+            vec![tool_args(db, "pg_dumpall", &[])]
+        }
+        DatabaseEngine::Postgresql => db
+            .databases
+            .iter()
+            .map(|name| tool_args(db, "pg_dump", &["--create", name]))
+            .collect(),
+        DatabaseEngine::Mysql => {
+            let mut args = vec!["--single-transaction"];
+            if db.databases.is_empty() {
+                args.push("--all-databases");
+            } else {
+                args.push("--databases");
+                args.extend(db.databases.iter().map(String::as_str));
+            }
+            vec![tool_args(db, "mysqldump", &args)]
+        }
+    };
+    invocations
+        .iter()
+        .map(|argv| {
+            argv.iter()
+                .map(|arg| shell_quote(arg))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" && ")
+}
+
+/// File name of the dump inside the archive when the preset sets no `stdin_name`.
+pub fn dump_name(preset: &str, db: &DatabaseConfig) -> String {
+    match db.databases.as_slice() {
+        [name] => format!("{}.sql", name),
+        _ => format!("{}.sql", preset),
+    }
+}
+
+/// `tool` with the connection options of `db`, its `options` and then `args`, run through
+/// `docker exec` when a container is configured.
+fn tool_args(db: &DatabaseConfig, tool: &str, args: &[&str]) -> Vec<String> {
+    let mut argv = Vec::new();
+    if let Some(container) = &db.container {
+        argv.extend(["docker".to_string(), "exec".to_string(), container.clone()]);
+    }
+    argv.push(tool.to_string());
+    if let Some(host) = &db.host {
+        argv.push(format!("--host={}", host));
+    }
+    if let Some(port) = db.port {
+        argv.push(format!("--port={}", port));
+    }
+    if let Some(user) = &db.user {
+        match db.engine {
+            DatabaseEngine::Postgresql => argv.push(format!("--username={}", user)),
+            DatabaseEngine::Mysql => argv.push(format!("--user={}", user)),
+        }
+    }
+    argv.extend(db.options.iter().cloned());
+    argv.extend(args.iter().map(|arg| arg.to_string()));
+    argv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn postgres_dumps_each_database_and_all_without_a_list() {
+        let mut db = DatabaseConfig {
+            databases: vec!["app".into(), "my db".into()],
+            host: Some("db.lan".into()),
+            user: Some("backup".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            dump_command(&db),
+            "pg_dump --host=db.lan --username=backup --create app \
+             && pg_dump --host=db.lan --username=backup --create 'my db'"
+        );
+        assert_eq!(dump_name("pg", &db), "pg.sql");
+
+        db.databases.clear();
+        db.container = Some("postgres".into());
+        assert_eq!(
+            dump_command(&db),
+            "docker exec postgres pg_dumpall --host=db.lan --username=backup"
+        );
+    }
+
+    #[test]
+    fn mysql_dumps_all_databases_in_one_transaction() {
+        let mut db = DatabaseConfig {
+            engine: DatabaseEngine::Mysql,
+            databases: vec!["shop".into()],
+            port: Some(3307),
+            options: vec!["--routines".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            dump_command(&db),
+            "mysqldump --port=3307 --routines --single-transaction --databases shop"
+        );
+        assert_eq!(dump_name("mysql", &db), "shop.sql");
+
+        db.databases.clear();
+        assert!(dump_command(&db).ends_with("--single-transaction --all-databases"));
+    }
+}
@@ -0,0 +1,207 @@
+//! Explanations for borg/ssh failures users commonly run into.
+
+/// A recognized failure: what went wrong and what to do about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnosis {
+    pub problem: &'static str,
+    pub fix: &'static str,
+}
+
+struct Rule {
+    /// Lowercase fragment groups; the rule matches when all fragments of any group occur.
+    needles: &'static [&'static [&'static str]],
+    diagnosis: Diagnosis,
+}
+
+// more specific rules first: "permission denied (publickey" beats the generic ssh rule
+const RULES: &[Rule] = &[
+    Rule {
+        needles: &[
+            &["failed to create/acquire the lock"],
+            &["locktimeout"],
+            &["lock.exclusive"],
+        ],
+        diagnosis: Diagnosis {
+            problem: "The repository is locked by another borg process.",
+            fix: "Wait for the other backup/check to finish (see `borg-tool ps`). If nothing is running any more, remove the stale lock with `borg-tool break-lock` (or `borg break-lock <repo>`).",
+        },
+    },
+    Rule {
+        needles: &[
+            &["is not a valid repository"],
+            &["repository ", " does not exist"],
+        ],
+        diagnosis: Diagnosis {
+            problem: "No borg repository was found at the configured path.",
+            fix: "Check the `repo` path/URL in the config (including the ssh user and host), or initialize it first with `borg init`.",
+        },
+    },
+    Rule {
+        needles: &[
+            &["cache is newer than repository"],
+            &["cache is out of sync"],
+            &["security directory is newer"],
+        ],
+        diagnosis: Diagnosis {
+            problem: "The local cache does not match the repository (e.g. the repo was restored from an older copy).",
+            fix: "If you trust the repository, drop the local cache with `borg delete --cache-only <repo>` and run again.",
+        },
+    },
+    Rule {
+        needles: &[
+            &["no fuse support"],
+            &["fusermount: not found"],
+            &["fusermount3: not found"],
+            &["llfuse"],
+            &["pyfuse3"],
+        ],
+        diagnosis: Diagnosis {
+            problem: "This borg installation cannot mount archives (FUSE support missing).",
+            fix: "Install FUSE and borg's FUSE bindings (e.g. `apt install fuse3 python3-pyfuse3`, or the distro's borgbackup package with FUSE), or browse/extract files instead of mounting.",
+        },
+    },
+    Rule {
+        needles: &[
+            &["key file with matching id not found"],
+            &["no key file for repository"],
+            &["keyfile not found"],
+        ],
+        diagnosis: Diagnosis {
+            problem: "The repository uses keyfile encryption and the key is not on this machine.",
+            fix: "Copy the key into ~/.config/borg/keys/ (or point BORG_KEY_FILE at it); restore it from your key backup (`borg key export`) if needed.",
+        },
+    },
+    Rule {
+        needles: &[
+            &["passphrase supplied in"],
+            &["passphrase is incorrect"],
+            &["passcommand supplied"],
+        ],
+        diagnosis: Diagnosis {
+            problem: "The passphrase was rejected.",
+            fix: "Re-run and enter the correct passphrase, or fix BORG_PASSPHRASE / BORG_PASSCOMMAND.",
+        },
+    },
+    Rule {
+        needles: &[
+            &["borg: command not found"],
+            &["borg: not found"],
+            &["exec: borg"],
+        ],
+        diagnosis: Diagnosis {
+            problem: "borg is not installed (or not on PATH) on the remote host.",
+            fix: "Install borg on the server, or set `remote_borg_path` for the repo to where it is installed.",
+        },
+    },
+    Rule {
+        needles: &[&["remote host identification has changed"]],
+        diagnosis: Diagnosis {
+            problem: "The repository host's SSH key changed; this can mean the connection is being intercepted.",
+            fix: "Verify the new host key with the server's admin, then remove the old entry with `ssh-keygen -R <host>` and connect once with ssh.",
+        },
+    },
+    Rule {
+        needles: &[&["host key verification failed"]],
+        diagnosis: Diagnosis {
+            problem: "ssh could not verify the repository host's key (the host is not in known_hosts).",
+            fix: "Connect once with `ssh <host>` and compare the fingerprint with the one the server's admin gives you.",
+        },
+    },
+    Rule {
+        needles: &[&["permission denied (publickey"]],
+        diagnosis: Diagnosis {
+            problem: "The ssh server rejected the login.",
+            fix: "Check the ssh user and key (`ssh -v user@host`), and that the key is loaded in ssh-agent or configured in ~/.ssh/config.",
+        },
+    },
+    Rule {
+        needles: &[
+            &["connection closed"],
+            &["connection refused"],
+            &["connection timed out"],
+            &["could not resolve hostname"],
+            &["no route to host"],
+            &["broken pipe"],
+        ],
+        diagnosis: Diagnosis {
+            problem: "The ssh connection to the repository host failed.",
+            fix: "Check that the host is reachable and ssh works (`ssh user@host`); retry once the network is back.",
+        },
+    },
+];
+
+pub fn diagnose(stderr: &str) -> Option<Diagnosis> {
+    let lower = stderr.to_lowercase();
+    RULES
+        .iter()
+        .find(|rule| {
+            rule.needles
+                .iter()
+                .any(|group| group.iter().all(|n| lower.contains(n)))
+        })
+        .map(|rule| rule.diagnosis)
+}
+
+/// The last non-empty stderr line, which is where borg puts its error message.
+pub fn last_line(stderr: &str) -> &str {
+    stderr
+        .lines()
+        .map(str::trim)
+        .rfind(|l| !l.is_empty())
+        .unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnose_recognizes_common_failures() {
+        let cases = [
+            (
+                "Failed to create/acquire the lock /repo/lock.exclusive (timeout).",
+                "locked",
+            ),
+            ("Repository /srv/x does not exist.", "No borg repository"),
+            (
+                "Cache is newer than repository - do you have multiple, independently updated repos with same ID?",
+                "local cache",
+            ),
+            (
+                "borg mount not available: no FUSE support, BORG_FUSE_IMPL=pyfuse3,llfuse.",
+                "FUSE",
+            ),
+            ("Key file with matching ID not found", "keyfile"),
+            (
+                "Remote: user@nas: Permission denied (publickey).\nConnection closed by remote host",
+                "rejected the login",
+            ),
+            (
+                "Remote: WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!\nHost key verification failed.\nConnection closed by remote host",
+                "SSH key changed",
+            ),
+            (
+                "Remote: bash: line 1: borg: command not found",
+                "not installed",
+            ),
+            (
+                "ssh: Could not resolve hostname nas: Name or service not known",
+                "ssh connection",
+            ),
+        ];
+        for (stderr, expected) in cases {
+            let d = diagnose(stderr).unwrap_or_else(|| panic!("no diagnosis for {stderr}"));
+            assert!(d.problem.contains(expected), "{stderr} -> {}", d.problem);
+        }
+        assert_eq!(diagnose("something entirely different"), None);
+    }
+
+    #[test]
+    fn last_line_skips_trailing_blank_lines() {
+        assert_eq!(
+            last_line("first\nRepository x does not exist.\n\n"),
+            "Repository x does not exist."
+        );
+        assert_eq!(last_line(""), "");
+    }
+}
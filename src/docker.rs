@@ -0,0 +1,155 @@
+//! Docker volumes of backup presets (`docker_volumes`, `stop_containers`).
+//!
+//! Volumes are backed up from their mountpoint on the host as reported by
+//! `docker volume inspect`, which requires read access to the docker data directory
+//! (usually root). Containers writing to them can be stopped for the duration of the backup.
+
+use std::fs;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+
+/// Run `docker` with `args` and return its stdout.
+fn docker(args: &[&str]) -> Result<String> {
+    let output = Command::new("docker")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to invoke docker (is it installed?)")?;
+    log::debug!(
+        "docker {}: exit code {}",
+        args.join(" "),
+        output.status.code().map_or_else(
+            || "none (killed by a signal)".to_string(),
+            |c| c.to_string()
+        )
+    );
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "docker {} failed with status {}: {}",
+            args.first().copied().unwrap_or_default(),
+            output.status,
+            stderr.trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Host directories holding `volumes`, in the same order. With `check_readable` they must be
+/// readable by borg-tool, which is pointless when borg runs through sudo.
+pub fn volume_mountpoints(volumes: &[String], check_readable: bool) -> Result<Vec<String>> {
+    if volumes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut args = vec!["volume", "inspect", "--format", "{{.Mountpoint}}"];
+    args.extend(volumes.iter().map(String::as_str));
+    let stdout = docker(&args)?;
+    let mountpoints: Vec<String> = stdout.lines().map(str::to_string).collect();
+    if mountpoints.len() != volumes.len() {
+        anyhow::bail!(
+            "docker volume inspect returned {} mountpoints for {} volumes",
+            mountpoints.len(),
+            volumes.len()
+        );
+    }
+    for (volume, mountpoint) in volumes.iter().zip(&mountpoints) {
+        // borg would only warn per file and create an empty archive
+        if check_readable && fs::read_dir(mountpoint).is_err() {
+            anyhow::bail!(
+                "Mountpoint {} of docker volume '{}' is not readable (set require_root = true or \
+                 run borg-tool as root; rootless docker and Docker Desktop keep volumes out of \
+                 reach)",
+                mountpoint,
+                volume
+            );
+        }
+    }
+    Ok(mountpoints)
+}
+
+/// Containers stopped by [`stop_running`]; [`StoppedContainers::start`] brings them back.
+#[derive(Debug, Default)]
+pub struct StoppedContainers {
+    names: Vec<String>,
+}
+
+/// Containers stopped and not started again yet, for [`start_stopped`] on an interrupt.
+static STOPPED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Stop those of `containers` that are running; stopped ones stay untouched and are not
+/// started afterwards either.
+pub fn stop_running(containers: &[String]) -> Result<StoppedContainers> {
+    if containers.is_empty() {
+        return Ok(StoppedContainers::default());
+    }
+    let running = docker(&["ps", "--format", "{{.Names}}"])?;
+    let names = running_of(containers, &running);
+    if !names.is_empty() {
+        log::info!("Stopping containers: {}", names.join(", "));
+        // registered first: an interrupt during `docker stop` must start them again too
+        if let Ok(mut stopped) = STOPPED.lock() {
+            stopped.extend(names.iter().cloned());
+        }
+        let mut args = vec!["stop"];
+        args.extend(names.iter().map(String::as_str));
+        if let Err(err) = docker(&args) {
+            // some may have stopped before the failure
+            let _ = StoppedContainers { names }.start();
+            return Err(err);
+        }
+    }
+    Ok(StoppedContainers { names })
+}
+
+impl StoppedContainers {
+    pub fn start(self) -> Result<()> {
+        if self.names.is_empty() {
+            return Ok(());
+        }
+        if let Ok(mut stopped) = STOPPED.lock() {
+            stopped.retain(|name| !self.names.contains(name));
+        }
+        log::info!("Starting containers: {}", self.names.join(", "));
+        let mut args = vec!["start"];
+        args.extend(self.names.iter().map(String::as_str));
+        docker(&args)
+            .map(|_| ())
+            .with_context(|| format!("Containers {} are still stopped", self.names.join(", ")))
+    }
+}
+
+/// Start the containers of backups that were interrupted, logging failures; for the exit
+/// path of an interrupted run.
+pub fn start_stopped() {
+    let names = STOPPED
+        .lock()
+        .map(|mut s| std::mem::take(&mut *s))
+        .unwrap_or_default();
+    if let Err(err) = (StoppedContainers { names }).start() {
+        log::error!("{:#}", err);
+    }
+}
+
+/// `containers` listed in the `docker ps --format {{.Names}}` output `running`.
+fn running_of(containers: &[String], running: &str) -> Vec<String> {
+    containers
+        .iter()
+        .filter(|name| running.lines().any(|line| line.trim() == name.as_str()))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_running_containers_are_stopped() {
+        let wanted = vec!["nextcloud".to_string(), "db".to_string(), "old".to_string()];
+        let running = "db\nnextcloud-cron\nnextcloud\n";
+        assert_eq!(running_of(&wanted, running), ["nextcloud", "db"]);
+        assert!(running_of(&wanted, "").is_empty());
+    }
+}
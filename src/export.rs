@@ -0,0 +1,188 @@
+//! Writing archive file listings to CSV or JSON-lines files.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::borg::{BorgItem, with_spinner};
+
+/// How often the progress counter is refreshed while writing.
+const PROGRESS_EVERY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+impl ExportFormat {
+    /// Format implied by the file extension, if it is a known one.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "jsonl" | "ndjson" | "json" => Some(ExportFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// clap value parser for `--format`.
+pub fn parse_format(raw: &str) -> Result<ExportFormat, String> {
+    match raw.to_ascii_lowercase().as_str() {
+        "csv" => Ok(ExportFormat::Csv),
+        "jsonl" | "json-lines" | "ndjson" => Ok(ExportFormat::Jsonl),
+        other => Err(format!("unknown format '{}', expected csv or jsonl", other)),
+    }
+}
+
+/// `format`, or the one implied by the extension of `path`.
+pub fn resolve_format(path: &Path, format: Option<ExportFormat>) -> Result<ExportFormat> {
+    format
+        .or_else(|| ExportFormat::from_path(path))
+        .with_context(|| {
+            format!(
+                "Cannot infer the format of {}; pass --format csv or --format jsonl",
+                path.display()
+            )
+        })
+}
+
+pub fn export_items(path: &Path, format: ExportFormat, items: &[BorgItem]) -> Result<()> {
+    let message = format!("Writing {} item(s) to {}", items.len(), path.display());
+    with_spinner(&message, |pb| {
+        let file = File::create(path).with_context(|| format!("Create {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        write_listing(&mut out, format, items, |done| {
+            pb.set_message(format!("{} ({}/{})", message, done, items.len()));
+        })
+        .and_then(|_| out.flush())
+        .with_context(|| format!("Write {}", path.display()))
+    })
+}
+
+pub fn write_listing<W: Write>(
+    out: &mut W,
+    format: ExportFormat,
+    items: &[BorgItem],
+    mut progress: impl FnMut(usize),
+) -> io::Result<()> {
+    let with_checksum = items.iter().any(|i| i.checksum().is_some());
+    if format == ExportFormat::Csv {
+        let extra = if with_checksum { ",checksum" } else { "" };
+        writeln!(out, "path,type,size,mtime{}", extra)?;
+    }
+    for (idx, item) in items.iter().enumerate() {
+        match format {
+            ExportFormat::Csv => {
+                write!(
+                    out,
+                    "{},{},{},{}",
+                    csv_field(&item.path),
+                    csv_field(item.item_type.as_deref().unwrap_or("")),
+                    item.size.map(|s| s.to_string()).unwrap_or_default(),
+                    csv_field(item.mtime.as_deref().unwrap_or(""))
+                )?;
+                if with_checksum {
+                    write!(out, ",{}", item.checksum().unwrap_or(""))?;
+                }
+                writeln!(out)?;
+            }
+            ExportFormat::Jsonl => {
+                serde_json::to_writer(&mut *out, item)?;
+                writeln!(out)?;
+            }
+        }
+        if (idx + 1) % PROGRESS_EVERY == 0 {
+            progress(idx + 1);
+        }
+    }
+    progress(items.len());
+    Ok(())
+}
+
+/// Quote a CSV field when needed (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Vec<BorgItem> {
+        vec![
+            BorgItem {
+                path: "etc/hosts".into(),
+                item_type: Some("-".into()),
+                size: Some(120),
+                mtime: Some("2024-05-01T10:00:00.000000".into()),
+                ..Default::default()
+            },
+            BorgItem {
+                path: "home/u/a, \"b\".txt".into(),
+                item_type: Some("-".into()),
+                size: None,
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn render(format: ExportFormat) -> String {
+        let mut out = Vec::new();
+        write_listing(&mut out, format, &items(), |_| {}).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_has_header_and_quotes_fields() {
+        assert_eq!(
+            render(ExportFormat::Csv),
+            "path,type,size,mtime\n\
+             etc/hosts,-,120,2024-05-01T10:00:00.000000\n\
+             \"home/u/a, \"\"b\"\".txt\",-,,\n"
+        );
+    }
+
+    #[test]
+    fn csv_adds_checksum_column_when_present() {
+        let mut listed = items();
+        listed[0].sha256 = Some("ab12".into());
+        let mut out = Vec::new();
+        write_listing(&mut out, ExportFormat::Csv, &listed, |_| {}).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "path,type,size,mtime,checksum");
+        assert!(lines[1].ends_with(",ab12"));
+        assert!(lines[2].ends_with(",,"));
+    }
+
+    #[test]
+    fn jsonl_writes_one_object_per_line() {
+        let rendered = render(ExportFormat::Jsonl);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["path"], "etc/hosts");
+        assert_eq!(first["type"], "-");
+        assert_eq!(first["size"], 120);
+        assert!(first.get("sha256").is_none());
+    }
+
+    #[test]
+    fn format_from_flag_or_extension() {
+        assert_eq!(parse_format("CSV"), Ok(ExportFormat::Csv));
+        assert!(parse_format("xml").is_err());
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out/list.ndjson")),
+            Some(ExportFormat::Jsonl)
+        );
+        assert_eq!(ExportFormat::from_path(Path::new("list.txt")), None);
+    }
+}
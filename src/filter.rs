@@ -0,0 +1,433 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::borg::{BorgArchive, BorgItem};
+
+/// Inclusive time window for archive listings, compared in local time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+}
+
+impl DateRange {
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// Archives without a parseable timestamp never match a non-empty range.
+    pub fn matches(&self, archive: &BorgArchive) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(time) = archive.time_utc.as_deref().and_then(parse_archive_time) else {
+            return false;
+        };
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time <= until)
+    }
+
+    pub fn apply(&self, archives: &[BorgArchive]) -> Vec<BorgArchive> {
+        archives
+            .iter()
+            .filter(|a| self.matches(a))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Parse a `--since` bound: a date (start of day), a date-time, or a relative age like `7d`.
+pub fn parse_since(raw: &str) -> Result<NaiveDateTime> {
+    parse_bound(raw, Local::now().naive_local(), NaiveTime::MIN)
+}
+
+/// Parse an `--until` bound: a date (end of day), a date-time, or a relative age like `2w`.
+pub fn parse_until(raw: &str) -> Result<NaiveDateTime> {
+    let end_of_day = NaiveTime::from_hms_micro_opt(23, 59, 59, 999_999).expect("valid time");
+    parse_bound(raw, Local::now().naive_local(), end_of_day)
+}
+
+fn parse_bound(raw: &str, now: NaiveDateTime, date_only_time: NaiveTime) -> Result<NaiveDateTime> {
+    let raw = raw.trim();
+    if let Some(age) = parse_relative(raw)? {
+        return Ok(now - age);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        return Ok(date.and_time(date_only_time));
+    }
+    for fmt in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(raw, fmt) {
+            return Ok(dt);
+        }
+    }
+    anyhow::bail!(
+        "Invalid date '{}': use YYYY-MM-DD, YYYY-MM-DD HH:MM[:SS] or a relative age like 12h, 7d, 2w",
+        raw
+    )
+}
+
+/// Parse an age like `36h`, `2d` or `1w` (e.g. `max_age`).
+pub fn parse_age(raw: &str) -> Result<Duration> {
+    parse_relative(raw.trim())?
+        .with_context(|| format!("Invalid age '{}': use e.g. 36h, 2d or 1w", raw))
+}
+
+fn parse_relative(raw: &str) -> Result<Option<Duration>> {
+    let Some(unit) = raw.chars().last().filter(|c| "hdw".contains(*c)) else {
+        return Ok(None);
+    };
+    let digits = &raw[..raw.len() - 1];
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let n: i64 = digits
+        .parse()
+        .with_context(|| format!("Relative age '{}' is too large", raw))?;
+    let age = match unit {
+        'h' => Duration::try_hours(n),
+        'd' => Duration::try_days(n),
+        _ => Duration::try_weeks(n),
+    };
+    age.map(Some)
+        .with_context(|| format!("Relative age '{}' is too large", raw))
+}
+
+/// Borg prints naive local timestamps (1.x) or RFC 3339 with offset (2.x).
+pub fn parse_archive_time(raw: &str) -> Option<NaiveDateTime> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(dt.with_timezone(&Local).naive_local());
+    }
+    NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f").ok()
+}
+
+/// borg keys accepted by `list --sort`.
+pub const ARCHIVE_SORT_KEYS: [&str; 3] = ["timestamp", "name", "id"];
+
+/// Name and count selection of archives, passed to `borg list` as `--glob-archives`,
+/// `--sort-by` and `--last`, or applied to a list fetched already.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveFilter {
+    /// Archive names starting with this; exclusive with `glob`
+    pub prefix: Option<String>,
+    /// Shell-style pattern for the archive name (`*`, `?`, `[...]`)
+    pub glob: Option<String>,
+    /// Keep only the last N archives in sort order
+    pub last: Option<usize>,
+    /// One of [`ARCHIVE_SORT_KEYS`]; borg sorts by timestamp when unset
+    pub sort_by: Option<String>,
+    /// Keep the `.checkpoint` archives of interrupted backups, hidden otherwise
+    pub include_checkpoints: bool,
+}
+
+/// `<name>.checkpoint` or `<name>.checkpoint.<n>`: the partial archive borg leaves behind
+/// when a backup is interrupted.
+pub fn is_checkpoint(name: &str) -> bool {
+    name.rsplit_once(".checkpoint").is_some_and(|(_, rest)| {
+        rest.is_empty()
+            || rest
+                .strip_prefix('.')
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+impl ArchiveFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == ArchiveFilter::default()
+    }
+
+    /// The name pattern, with a prefix turned into `<prefix>*` as for prune.
+    fn name_glob(&self) -> Option<String> {
+        self.glob.clone().or_else(|| {
+            self.prefix
+                .as_deref()
+                .map(|p| format!("{}*", crate::borg::escape_glob(p)))
+        })
+    }
+
+    /// Options for `borg list <repo>`.
+    pub fn borg_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(glob) = self.name_glob() {
+            args.extend(["--glob-archives".to_string(), glob]);
+        }
+        if let Some(key) = &self.sort_by {
+            args.extend(["--sort-by".to_string(), key.clone()]);
+        }
+        if let Some(n) = self.last {
+            args.extend(["--last".to_string(), n.to_string()]);
+        }
+        args
+    }
+
+    /// The same selection on `archives` (in borg's default timestamp order); sorting by id
+    /// keeps the given order.
+    pub fn apply(&self, archives: &[BorgArchive]) -> Vec<BorgArchive> {
+        let glob = self.name_glob();
+        let mut shown: Vec<BorgArchive> = archives
+            .iter()
+            .filter(|a| glob.as_deref().is_none_or(|g| glob_matches(g, &a.name)))
+            .filter(|a| self.include_checkpoints || !is_checkpoint(&a.name))
+            .cloned()
+            .collect();
+        match self.sort_by.as_deref() {
+            Some("name") => shown.sort_by(|a, b| a.name.cmp(&b.name)),
+            Some("id") => {}
+            _ => shown.sort_by_key(|a| a.time_utc.as_deref().and_then(parse_archive_time)),
+        }
+        if let Some(n) = self.last {
+            shown.drain(..shown.len().saturating_sub(n));
+        }
+        shown
+    }
+}
+
+/// fnmatch-style match of the whole `name`: `*` any run, `?` one character, `[...]` a set.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    fn matches(p: &[char], n: &[char]) -> bool {
+        match p.first() {
+            None => n.is_empty(),
+            Some('*') => (0..=n.len()).any(|skip| matches(&p[1..], &n[skip..])),
+            Some('?') => !n.is_empty() && matches(&p[1..], &n[1..]),
+            Some('[') => match p.iter().position(|c| *c == ']') {
+                Some(end) if end > 1 => {
+                    let set = &p[1..end];
+                    let (negate, set) = match set.first() {
+                        Some('!') => (true, &set[1..]),
+                        _ => (false, set),
+                    };
+                    n.first().is_some_and(|c| set.contains(c) != negate)
+                        && matches(&p[end + 1..], &n[1..])
+                }
+                _ => n.first() == Some(&'[') && matches(&p[1..], &n[1..]),
+            },
+            Some(c) => n.first() == Some(c) && matches(&p[1..], &n[1..]),
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    matches(&p, &n)
+}
+
+/// `files --type`: which kind of item to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl ItemKind {
+    pub fn matches(self, item: &BorgItem) -> bool {
+        let letter = match self {
+            ItemKind::File => "-",
+            ItemKind::Dir => "d",
+            ItemKind::Symlink => "l",
+        };
+        item.item_type.as_deref() == Some(letter)
+    }
+}
+
+/// clap value parser for `--type`.
+pub fn parse_item_kind(raw: &str) -> Result<ItemKind, String> {
+    match raw {
+        "f" => Ok(ItemKind::File),
+        "d" => Ok(ItemKind::Dir),
+        "l" => Ok(ItemKind::Symlink),
+        other => Err(format!("unknown type '{}', expected f, d or l", other)),
+    }
+}
+
+/// `files --sort` key. Size and mtime put the largest/newest first, like `ls -S`/`ls -t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemSort {
+    Path,
+    Size,
+    Mtime,
+}
+
+/// clap value parser for `--sort`.
+pub fn parse_item_sort(raw: &str) -> Result<ItemSort, String> {
+    match raw {
+        "path" => Ok(ItemSort::Path),
+        "size" => Ok(ItemSort::Size),
+        "mtime" => Ok(ItemSort::Mtime),
+        other => Err(format!(
+            "unknown sort key '{}', expected path, size or mtime",
+            other
+        )),
+    }
+}
+
+/// Sort `items` by `key`; `reverse` flips the order. Ties keep path order.
+pub fn sort_items(items: &mut [BorgItem], key: ItemSort, reverse: bool) {
+    items.sort_by(|a, b| {
+        let order = match key {
+            ItemSort::Path => a.path.cmp(&b.path),
+            ItemSort::Size => b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)),
+            // borg's ISO timestamps sort chronologically as strings
+            ItemSort::Mtime => b.mtime.cmp(&a.mtime).then_with(|| a.path.cmp(&b.path)),
+        };
+        if reverse { order.reverse() } else { order }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_filter_builds_borg_args_and_applies_locally() {
+        let archive = |name: &str, time: &str| BorgArchive {
+            name: name.into(),
+            time_utc: Some(time.into()),
+            ..Default::default()
+        };
+        let archives = [
+            archive("home-2", "2025-01-02T10:00:00.000000"),
+            archive("etc-1", "2025-01-01T11:00:00.000000"),
+            archive("home-1", "2025-01-01T10:00:00.000000"),
+            archive("home-3", "2025-01-03T10:00:00.000000"),
+        ];
+        let names = |v: Vec<BorgArchive>| v.into_iter().map(|a| a.name).collect::<Vec<_>>();
+
+        let filter = ArchiveFilter {
+            prefix: Some("home-".into()),
+            last: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.borg_args(),
+            ["--glob-archives", "home-*", "--last", "2"]
+        );
+        assert_eq!(names(filter.apply(&archives)), ["home-2", "home-3"]);
+
+        let filter = ArchiveFilter {
+            glob: Some("*-1".into()),
+            sort_by: Some("name".into()),
+            ..Default::default()
+        };
+        assert_eq!(names(filter.apply(&archives)), ["etc-1", "home-1"]);
+    }
+
+    #[test]
+    fn checkpoints_are_recognized_by_name() {
+        assert!(is_checkpoint("home-2025-01-01.checkpoint"));
+        assert!(is_checkpoint("home-2025-01-01.checkpoint.3"));
+        assert!(!is_checkpoint("home-2025-01-01"));
+        assert!(!is_checkpoint("pre.checkpoint-upgrade"));
+        assert!(!is_checkpoint("home.checkpoint.x"));
+
+        let archives = [
+            BorgArchive {
+                name: "a".into(),
+                ..Default::default()
+            },
+            BorgArchive {
+                name: "b.checkpoint".into(),
+                ..Default::default()
+            },
+        ];
+        let shown = ArchiveFilter::default().apply(&archives);
+        assert_eq!(shown.len(), 1);
+        let filter = ArchiveFilter {
+            include_checkpoints: true,
+            ..Default::default()
+        };
+        assert_eq!(filter.apply(&archives).len(), 2);
+    }
+
+    #[test]
+    fn glob_matches_wildcards_and_sets() {
+        assert!(glob_matches("host-*", "host-2025-01-01"));
+        assert!(glob_matches("*-0?", "a-01"));
+        assert!(glob_matches("a[0-]b", "a-b"));
+        assert!(glob_matches("a[!x]b", "ayb"));
+        assert!(!glob_matches("a[!x]b", "axb"));
+        assert!(!glob_matches("host-*", "other"));
+        assert!(glob_matches("x[*]", "x*"));
+    }
+
+    #[test]
+    fn sort_items_puts_largest_first_and_reverses() {
+        let item = |path: &str, size: u64| BorgItem {
+            path: path.into(),
+            item_type: Some("-".into()),
+            size: Some(size),
+            ..Default::default()
+        };
+        let mut items = vec![item("b", 10), item("a", 300), item("c", 10)];
+        let paths = |items: &[BorgItem]| items.iter().map(|i| i.path.clone()).collect::<Vec<_>>();
+
+        sort_items(&mut items, ItemSort::Size, false);
+        assert_eq!(paths(&items), ["a", "b", "c"]);
+        sort_items(&mut items, ItemSort::Size, true);
+        assert_eq!(paths(&items), ["c", "b", "a"]);
+        sort_items(&mut items, ItemSort::Path, false);
+        assert_eq!(paths(&items), ["a", "b", "c"]);
+
+        assert!(ItemKind::File.matches(&items[0]));
+        assert!(!ItemKind::Dir.matches(&items[0]));
+        assert_eq!(
+            parse_item_kind("x").unwrap_err(),
+            "unknown type 'x', expected f, d or l"
+        );
+    }
+
+    fn at(raw: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn archive(time: Option<&str>) -> BorgArchive {
+        BorgArchive {
+            name: "a".into(),
+            time_utc: time.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_bound_handles_absolute_and_relative() {
+        let now = at("2025-03-10 12:00:00");
+        let cases = [
+            ("2025-03-01", NaiveTime::MIN, at("2025-03-01 00:00:00")),
+            (
+                "2025-03-01 08:30",
+                NaiveTime::MIN,
+                at("2025-03-01 08:30:00"),
+            ),
+            ("7d", NaiveTime::MIN, at("2025-03-03 12:00:00")),
+            ("2w", NaiveTime::MIN, at("2025-02-24 12:00:00")),
+            ("12h", NaiveTime::MIN, at("2025-03-10 00:00:00")),
+        ];
+        for (raw, time, expected) in cases {
+            assert_eq!(parse_bound(raw, now, time).unwrap(), expected, "{raw}");
+        }
+        assert!(parse_bound("last tuesday", now, NaiveTime::MIN).is_err());
+        assert!(parse_bound("d", now, NaiveTime::MIN).is_err());
+    }
+
+    #[test]
+    fn until_date_covers_whole_day() {
+        let until = parse_until("2025-03-01").unwrap();
+        let range = DateRange {
+            since: None,
+            until: Some(until),
+        };
+        assert!(range.matches(&archive(Some("2025-03-01T23:10:00.000000"))));
+        assert!(!range.matches(&archive(Some("2025-03-02T00:00:01.000000"))));
+    }
+
+    #[test]
+    fn date_range_skips_archives_without_time() {
+        let range = DateRange {
+            since: Some(at("2025-01-01 00:00:00")),
+            until: None,
+        };
+        assert!(!range.matches(&archive(None)));
+        assert!(DateRange::default().matches(&archive(None)));
+    }
+}
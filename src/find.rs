@@ -0,0 +1,112 @@
+//! Which archives contain a path: one filtered `borg list` per archive, newest first.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::borg::{BorgArchive, list_matching, with_spinner};
+use crate::config::RepoCtx;
+use crate::filter::parse_archive_time;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct FindHit {
+    pub archive: String,
+    /// Archive timestamp as returned by borg
+    pub time: Option<String>,
+    pub path: String,
+    #[serde(rename = "type")]
+    pub item_type: Option<String>,
+    pub size: Option<u64>,
+    pub mtime: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FindReport {
+    /// Number of archives searched
+    pub searched: usize,
+    pub hits: Vec<FindHit>,
+}
+
+/// borg pattern for `pattern`: a regex with `regex`, else a shell glob. Globs without a `/`
+/// match the file name at any depth, like `find -name`.
+pub fn borg_pattern(pattern: &str, regex: bool) -> String {
+    if regex {
+        return format!("re:{}", pattern);
+    }
+    let glob = pattern.trim_start_matches('/');
+    if glob.contains('/') {
+        format!("sh:{}", glob)
+    } else {
+        format!("sh:**/{}", glob)
+    }
+}
+
+/// `archives` sorted newest first, cut to the newest `last` if given.
+pub fn newest_first(archives: &[BorgArchive], last: Option<usize>) -> Vec<BorgArchive> {
+    let mut sorted = archives.to_vec();
+    sorted.sort_by_key(|a| std::cmp::Reverse(a.time_utc.as_deref().and_then(parse_archive_time)));
+    sorted.truncate(last.unwrap_or(usize::MAX));
+    sorted
+}
+
+/// Search `archives` in the given order for items matching the borg `pattern`.
+pub fn find_in_archives(
+    ctx: &RepoCtx,
+    archives: &[BorgArchive],
+    pattern: &str,
+    passphrase: Option<&str>,
+) -> Result<FindReport> {
+    with_spinner(&format!("Searching {} archive(s)", archives.len()), |pb| {
+        let mut report = FindReport::default();
+        for (idx, archive) in archives.iter().enumerate() {
+            pb.set_message(format!(
+                "Searching [{}/{}] {} ({} hit(s))",
+                idx + 1,
+                archives.len(),
+                archive.name,
+                report.hits.len()
+            ));
+            for item in list_matching(ctx, &archive.name, pattern, passphrase)? {
+                report.hits.push(FindHit {
+                    archive: archive.name.clone(),
+                    time: archive.time_utc.clone(),
+                    path: item.path,
+                    item_type: item.item_type,
+                    size: item.size,
+                    mtime: item.mtime,
+                });
+            }
+            report.searched += 1;
+        }
+        Ok(report)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borg_pattern_matches_bare_names_at_any_depth() {
+        assert_eq!(borg_pattern("*.conf", false), "sh:**/*.conf");
+        assert_eq!(borg_pattern("/etc/nginx/*", false), "sh:etc/nginx/*");
+        assert_eq!(borg_pattern(r"notes\.md$", true), r"re:notes\.md$");
+    }
+
+    #[test]
+    fn newest_first_sorts_and_limits() {
+        let archive = |name: &str, time: &str| BorgArchive {
+            name: name.into(),
+            time_utc: Some(time.into()),
+            ..Default::default()
+        };
+        let archives = [
+            archive("b", "2025-01-02T10:00:00.000000"),
+            archive("c", "2025-01-03T10:00:00.000000"),
+            archive("a", "2025-01-01T10:00:00.000000"),
+        ];
+        let names = |v: Vec<BorgArchive>| v.into_iter().map(|a| a.name).collect::<Vec<_>>();
+
+        assert_eq!(names(newest_first(&archives, None)), ["c", "b", "a"]);
+        assert_eq!(names(newest_first(&archives, Some(2))), ["c", "b"]);
+    }
+}
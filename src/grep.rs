@@ -0,0 +1,194 @@
+use anyhow::Result;
+
+use crate::borg::{BorgItem, extract_to_stdout, with_spinner};
+use crate::config::RepoCtx;
+
+/// Files larger than this are skipped instead of being pulled into memory.
+pub const MAX_GREP_FILE_BYTES: u64 = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct GrepOptions {
+    pub pattern: String,
+    pub ignore_case: bool,
+    /// Lines of context printed around each match.
+    pub context: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepLine {
+    /// 1-based line number.
+    pub number: usize,
+    pub text: String,
+    pub is_match: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileMatches {
+    pub path: String,
+    /// Runs of adjacent lines; overlapping context windows are merged.
+    pub blocks: Vec<Vec<GrepLine>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GrepReport {
+    pub searched: usize,
+    pub files: Vec<FileMatches>,
+    pub skipped_binary: usize,
+    pub skipped_large: usize,
+}
+
+/// Regular files below `prefix` (archive paths carry no leading slash).
+pub fn candidates(items: &[BorgItem], prefix: Option<&str>) -> Vec<BorgItem> {
+    let prefix = prefix.map(|p| p.trim_start_matches('/'));
+    items
+        .iter()
+        .filter(|i| i.item_type.as_deref() == Some("-"))
+        .filter(|i| prefix.is_none_or(|p| i.path.starts_with(p)))
+        .cloned()
+        .collect()
+}
+
+/// Extract each candidate with `borg extract --stdout` and search it line by line.
+pub fn search_archive(
+    ctx: &RepoCtx,
+    archive: &str,
+    files: &[BorgItem],
+    opts: &GrepOptions,
+    passphrase: Option<&str>,
+) -> Result<GrepReport> {
+    with_spinner(
+        &format!("Searching {} file(s) in {}", files.len(), archive),
+        |pb| {
+            let mut report = GrepReport::default();
+            for (idx, file) in files.iter().enumerate() {
+                if file.size.is_some_and(|s| s > MAX_GREP_FILE_BYTES) {
+                    report.skipped_large += 1;
+                    continue;
+                }
+                pb.set_message(format!(
+                    "Searching [{}/{}] {}",
+                    idx + 1,
+                    files.len(),
+                    file.path
+                ));
+                let content = extract_to_stdout(ctx, archive, &file.path, passphrase)?;
+                report.searched += 1;
+                if looks_binary(&content) {
+                    report.skipped_binary += 1;
+                    continue;
+                }
+                let blocks = grep_text(&String::from_utf8_lossy(&content), opts);
+                if !blocks.is_empty() {
+                    report.files.push(FileMatches {
+                        path: file.path.clone(),
+                        blocks,
+                    });
+                }
+            }
+            Ok(report)
+        },
+    )
+}
+
+/// Same heuristic as GNU grep: a NUL byte near the start means binary.
+pub fn looks_binary(content: &[u8]) -> bool {
+    content.iter().take(8192).any(|b| *b == 0)
+}
+
+pub fn grep_text(text: &str, opts: &GrepOptions) -> Vec<Vec<GrepLine>> {
+    let needle = if opts.ignore_case {
+        opts.pattern.to_lowercase()
+    } else {
+        opts.pattern.clone()
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let is_match = |line: &str| {
+        if opts.ignore_case {
+            line.to_lowercase().contains(&needle)
+        } else {
+            line.contains(&needle)
+        }
+    };
+
+    let mut blocks: Vec<Vec<GrepLine>> = Vec::new();
+    let mut shown_until = 0; // exclusive index of the last line already emitted
+    for (idx, line) in lines.iter().enumerate() {
+        if !is_match(line) {
+            continue;
+        }
+        let start = idx.saturating_sub(opts.context).max(shown_until);
+        let end = (idx + opts.context + 1).min(lines.len());
+        if blocks.is_empty() || start > shown_until {
+            blocks.push(Vec::new());
+        }
+        let block = blocks.last_mut().expect("block pushed above");
+        for (n, text) in lines.iter().enumerate().take(end).skip(start) {
+            block.push(GrepLine {
+                number: n + 1,
+                text: text.to_string(),
+                is_match: is_match(text),
+            });
+        }
+        shown_until = shown_until.max(end);
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(pattern: &str, context: usize) -> GrepOptions {
+        GrepOptions {
+            pattern: pattern.into(),
+            ignore_case: false,
+            context,
+        }
+    }
+
+    fn numbers(blocks: &[Vec<GrepLine>]) -> Vec<Vec<usize>> {
+        blocks
+            .iter()
+            .map(|b| b.iter().map(|l| l.number).collect())
+            .collect()
+    }
+
+    #[test]
+    fn grep_text_merges_overlapping_context() {
+        let text = "a\nfoo\nb\nc\nfoo\nd\ne\nf\ng\nfoo\n";
+        let blocks = grep_text(text, &opts("foo", 1));
+        assert_eq!(numbers(&blocks), [vec![1, 2, 3, 4, 5, 6], vec![9, 10]]);
+        assert!(blocks[0][1].is_match && !blocks[0][0].is_match);
+    }
+
+    #[test]
+    fn grep_text_ignore_case() {
+        let mut o = opts("ERROR", 0);
+        assert!(grep_text("an error here", &o).is_empty());
+        o.ignore_case = true;
+        assert_eq!(numbers(&grep_text("ok\nan error here", &o)), [vec![2]]);
+    }
+
+    #[test]
+    fn candidates_keep_regular_files_under_prefix() {
+        let item = |path: &str, kind: &str| BorgItem {
+            path: path.into(),
+            item_type: Some(kind.into()),
+            size: None,
+            ..Default::default()
+        };
+        let items = [
+            item("etc", "d"),
+            item("etc/hosts", "-"),
+            item("etc/link", "l"),
+            item("home/u/notes", "-"),
+        ];
+        let paths: Vec<String> = candidates(&items, Some("/etc"))
+            .into_iter()
+            .map(|i| i.path)
+            .collect();
+        assert_eq!(paths, ["etc/hosts"]);
+        assert!(looks_binary(b"abc\0def"));
+        assert!(!looks_binary(b"plain text"));
+    }
+}
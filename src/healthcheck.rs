@@ -0,0 +1,45 @@
+//! Healthchecks.io-style ping URLs around backup runs.
+
+use crate::http;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ping {
+    Start,
+    Success,
+    Fail,
+}
+
+/// `<url>/start`, `<url>` or `<url>/fail`.
+pub fn ping_url(base: &str, ping: Ping) -> String {
+    let base = base.trim_end_matches('/');
+    match ping {
+        Ping::Start => format!("{}/start", base),
+        Ping::Success => base.to_string(),
+        Ping::Fail => format!("{}/fail", base),
+    }
+}
+
+/// Send `ping` with `body` as the log excerpt. A monitoring outage must not fail the backup,
+/// so errors are only logged.
+pub fn ping(base: &str, ping: Ping, body: &str) {
+    let url = ping_url(base, ping);
+    if let Err(err) = http::post(&url, &[], body.as_bytes()) {
+        log::warn!("healthcheck ping failed: {:#}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ping_urls_follow_healthchecks_conventions() {
+        let base = "https://hc-ping.com/0b5a/";
+        assert_eq!(
+            ping_url(base, Ping::Start),
+            "https://hc-ping.com/0b5a/start"
+        );
+        assert_eq!(ping_url(base, Ping::Success), "https://hc-ping.com/0b5a");
+        assert_eq!(ping_url(base, Ping::Fail), "https://hc-ping.com/0b5a/fail");
+    }
+}
@@ -0,0 +1,178 @@
+//! Local record of finished backup, prune and check runs (`history` subcommand).
+//!
+//! One JSON object per line in `$XDG_STATE_HOME/borg-tool/history.jsonl`, so the trail
+//! survives a broken or unreachable repository and can be read with standard tools.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Local start time, RFC 3339
+    pub started: String,
+    /// `backup`, `prune` or `check`
+    pub action: String,
+    pub repo: String,
+    /// Preset or archive the operation ran on
+    pub target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<String>,
+    pub duration_secs: f64,
+    pub success: bool,
+    /// Summary on success (archive, warnings, kept/pruned counts), error chain on failure
+    pub message: String,
+    /// borg's statistics, e.g. `borg create --stats` sizes and file count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<serde_json::Value>,
+}
+
+/// `$XDG_STATE_HOME/borg-tool` (default `~/.local/state/borg-tool`).
+pub fn state_dir() -> PathBuf {
+    let state_home = match env::var("XDG_STATE_HOME") {
+        Ok(xdg) => PathBuf::from(xdg),
+        Err(_) => match env::var("HOME") {
+            Ok(home) => PathBuf::from(home).join(".local").join("state"),
+            Err(_) => env::temp_dir(),
+        },
+    };
+    state_home.join("borg-tool")
+}
+
+pub fn default_history_path() -> PathBuf {
+    state_dir().join("history.jsonl")
+}
+
+/// Append `entry` to the history file at `path`, creating it (and its directory) on first use.
+pub fn append(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Create {}", dir.display()))?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("Append to {}", path.display()))
+}
+
+/// [`append`] for finished operations: a full disk or read-only home must not fail the
+/// operation itself, so errors are only logged.
+pub fn record(path: &Path, entry: &HistoryEntry) {
+    if let Err(err) = append(path, entry) {
+        log::warn!("could not record run history: {:#}", err);
+    }
+}
+
+/// All entries in `path`, oldest first; a missing file is an empty history. Lines that do
+/// not parse (e.g. cut off by a crash) are skipped.
+pub fn load(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("Read {}", path.display())),
+    };
+    Ok(raw
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Which entries `history` shows.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub repo: Option<String>,
+    pub action: Option<String>,
+    pub failed_only: bool,
+    /// Keep only the newest `limit` matches
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    pub fn apply(&self, entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+        let mut matching: Vec<HistoryEntry> = entries
+            .into_iter()
+            .filter(|e| self.repo.as_ref().is_none_or(|r| *r == e.repo))
+            .filter(|e| self.action.as_ref().is_none_or(|a| *a == e.action))
+            .filter(|e| !self.failed_only || !e.success)
+            .collect();
+        if let Some(limit) = self.limit {
+            let skip = matching.len().saturating_sub(limit);
+            matching.drain(..skip);
+        }
+        matching
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(repo: &str, action: &str, success: bool) -> HistoryEntry {
+        HistoryEntry {
+            started: "2024-05-01T03:00:00+02:00".into(),
+            action: action.into(),
+            repo: repo.into(),
+            target: "home".into(),
+            archive: None,
+            duration_secs: 1.5,
+            success,
+            message: String::new(),
+            stats: None,
+        }
+    }
+
+    #[test]
+    fn append_and_load_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("state").join("history.jsonl");
+        assert!(load(&path).unwrap().is_empty());
+
+        append(&path, &entry("nas", "backup", true)).unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"truncated\n")
+            .unwrap();
+        append(&path, &entry("nas", "prune", false)).unwrap();
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[1], entry("nas", "prune", false));
+    }
+
+    #[test]
+    fn filter_keeps_newest_matches() {
+        let entries = vec![
+            entry("nas", "backup", false),
+            entry("usb", "backup", false),
+            entry("nas", "check", true),
+            entry("nas", "backup", true),
+            entry("nas", "backup", false),
+        ];
+        let filter = HistoryFilter {
+            repo: Some("nas".into()),
+            action: Some("backup".into()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let shown = filter.apply(entries.clone());
+        assert_eq!(
+            shown.iter().map(|e| e.success).collect::<Vec<_>>(),
+            [true, false]
+        );
+
+        let failed = HistoryFilter {
+            failed_only: true,
+            ..Default::default()
+        };
+        assert_eq!(failed.apply(entries).len(), 3);
+    }
+}
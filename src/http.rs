@@ -0,0 +1,101 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+
+/// Maximum response size accepted by [`get`], to avoid filling memory with a wrong URL.
+const MAX_DOWNLOAD_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Fetch `url` via the system `curl`, restricted to HTTPS (including redirects).
+pub fn get(url: &str) -> Result<Vec<u8>> {
+    if !url.starts_with("https://") {
+        anyhow::bail!(
+            "Refusing to download '{}': only https:// URLs are allowed",
+            url
+        );
+    }
+
+    let output = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--proto",
+            "=https",
+            "--proto-redir",
+            "=https",
+            "--max-time",
+            "60",
+            "--max-filesize",
+            &MAX_DOWNLOAD_BYTES.to_string(),
+            url,
+        ])
+        .output()
+        .context("Failed to invoke curl (is it installed?)")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "Download of {} failed with status {}: {}",
+            url,
+            output.status,
+            stderr.trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// POST `body` to `url` via the system `curl` (http or https), retrying transient failures.
+pub fn post(url: &str, headers: &[(&str, &str)], body: &[u8]) -> Result<()> {
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        anyhow::bail!(
+            "Refusing to post to '{}': only http(s):// URLs are allowed",
+            url
+        );
+    }
+
+    let mut cmd = Command::new("curl");
+    for (name, value) in headers {
+        cmd.arg("--header").arg(format!("{}: {}", name, value));
+    }
+    let mut child = cmd
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--output",
+            "/dev/null",
+            "--proto",
+            "=http,https",
+            "--max-time",
+            "10",
+            "--retry",
+            "3",
+            "--data-binary",
+            "@-",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to invoke curl (is it installed?)")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(body)
+            .context("Write request body to curl")?;
+    }
+    let output = child.wait_with_output().context("Wait for curl")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "POST to {} failed with status {}: {}",
+            url,
+            output.status,
+            stderr.trim()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Core of borg-tool: config handling and a thin wrapper around the `borg` CLI.
+//!
+//! The interactive menus and command line parser live behind the default `cli` feature;
+//! build with `default-features = false` to use only the core.
+
+pub mod audit;
+pub mod borg;
+pub mod borgmatic;
+pub mod commands;
+pub mod config;
+pub mod database;
+pub mod diagnose;
+pub mod docker;
+pub mod export;
+pub mod filter;
+pub mod find;
+pub mod grep;
+pub mod healthcheck;
+pub mod history;
+pub mod http;
+pub mod logging;
+pub mod mounts;
+pub mod notify;
+pub mod probes;
+pub mod process;
+pub mod ps;
+pub mod runlock;
+pub mod snapshot;
+pub mod status;
+pub mod systemd;
+pub mod template;
+pub mod tree;
+
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "cli")]
+pub mod man;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "cli")]
+pub mod ui;
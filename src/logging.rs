@@ -0,0 +1,204 @@
+//! Diagnostics through the `log` facade.
+//!
+//! Messages go to stderr filtered by `-v`/`--quiet`; with `log_file` configured every entry
+//! from debug up (each borg command with its exit status among them) is also appended there
+//! with a timestamp, whatever the verbosity.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Level written to `log_file`.
+const FILE_LEVEL: LevelFilter = LevelFilter::Debug;
+
+struct Logger {
+    /// `LevelFilter as usize` shown on stderr
+    stderr_level: AtomicUsize,
+    /// Nothing on stderr while the full-screen TUI owns the terminal
+    stderr_muted: AtomicBool,
+    file: Mutex<Option<File>>,
+}
+
+static LOGGER: Logger = Logger {
+    stderr_level: AtomicUsize::new(LevelFilter::Info as usize),
+    stderr_muted: AtomicBool::new(false),
+    file: Mutex::new(None),
+};
+
+impl Logger {
+    fn stderr_level(&self) -> LevelFilter {
+        if self.stderr_muted.load(Ordering::Relaxed) {
+            LevelFilter::Off
+        } else {
+            self.configured_level()
+        }
+    }
+
+    /// The `-v`/`--quiet` level, muted or not
+    fn configured_level(&self) -> LevelFilter {
+        match self.stderr_level.load(Ordering::Relaxed) {
+            0 => LevelFilter::Off,
+            1 => LevelFilter::Error,
+            2 => LevelFilter::Warn,
+            3 => LevelFilter::Info,
+            4 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+
+    fn has_file(&self) -> bool {
+        self.file.lock().is_ok_and(|file| file.is_some())
+    }
+
+    fn write_file(&self, level: Level, message: &str) {
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+        {
+            // a full disk must not turn into a failed backup
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                level,
+                message
+            );
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.stderr_level()
+            || (metadata.level() <= FILE_LEVEL && self.has_file())
+    }
+
+    fn log(&self, record: &Record) {
+        // only our own messages, not those of dependencies
+        if !record.target().starts_with("borg_tool_rs") {
+            return;
+        }
+        let message = record.args().to_string();
+        if record.level() <= self.stderr_level() {
+            eprintln!("{}", stderr_line(record.level(), &message));
+        }
+        if record.level() <= FILE_LEVEL {
+            self.write_file(record.level(), &message);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Level shown on stderr: errors only with `quiet`, info by default, debug with `-v`
+/// (every borg command and its exit status), trace with `-vv`.
+pub fn stderr_level(verbosity: u8, quiet: bool) -> LevelFilter {
+    match (quiet, verbosity) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    }
+}
+
+/// Install the logger; call once at startup.
+pub fn init(verbosity: u8, quiet: bool) {
+    let level = stderr_level(verbosity, quiet);
+    LOGGER.stderr_level.store(level as usize, Ordering::Relaxed);
+    // a second call (tests) keeps the installed logger
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
+
+/// Also append every entry from debug up to `path` (the `log_file` setting).
+pub fn set_log_file(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        fs::create_dir_all(dir).with_context(|| format!("Create {}", dir.display()))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Open log_file {}", path.display()))?;
+    if let Ok(mut slot) = LOGGER.file.lock() {
+        *slot = Some(file);
+    }
+    log::set_max_level(LOGGER.configured_level().max(FILE_LEVEL));
+    Ok(())
+}
+
+/// Keep stderr free of log lines (the file still gets them), e.g. while the TUI runs.
+pub fn set_stderr_muted(muted: bool) {
+    LOGGER.stderr_muted.store(muted, Ordering::Relaxed);
+}
+
+/// Record `message` in the log file only, e.g. the error a run ends with (which is printed
+/// on stderr separately).
+pub fn to_file(level: Level, message: &str) {
+    LOGGER.write_file(level, message);
+}
+
+fn stderr_line(level: Level, message: &str) -> String {
+    match level {
+        Level::Error => format!("Error: {}", message),
+        Level::Warn => format!("Warning: {}", message),
+        Level::Info => message.to_string(),
+        Level::Debug | Level::Trace => format!("[{}] {}", level.as_str().to_lowercase(), message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_maps_to_stderr_level() {
+        assert_eq!(stderr_level(0, false), LevelFilter::Info);
+        assert_eq!(stderr_level(1, false), LevelFilter::Debug);
+        assert_eq!(stderr_level(3, false), LevelFilter::Trace);
+        assert_eq!(stderr_level(2, true), LevelFilter::Error);
+        assert_eq!(stderr_line(Level::Warn, "disk full"), "Warning: disk full");
+        assert_eq!(stderr_line(Level::Debug, "ran borg"), "[debug] ran borg");
+    }
+
+    #[test]
+    fn log_file_gets_debug_entries_with_timestamps() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("logs").join("borg-tool.log");
+        init(0, true);
+        set_log_file(&path).unwrap();
+        log::debug!("borg list exited with 0");
+        log::trace!("not written");
+        to_file(Level::Error, "run failed");
+        LOGGER.flush();
+
+        *LOGGER.file.lock().unwrap() = None;
+
+        // other tests may log concurrently; only look at ours
+        let written = fs::read_to_string(&path).unwrap();
+        let line = written
+            .lines()
+            .find(|line| line.ends_with(" DEBUG borg list exited with 0"))
+            .unwrap();
+        assert!(line.starts_with(&Local::now().format("%Y-").to_string()));
+        assert!(
+            written
+                .lines()
+                .any(|line| line.ends_with(" ERROR run failed"))
+        );
+        assert!(!written.contains("not written"));
+    }
+}
@@ -1,39 +1,168 @@
-mod borg;
-mod cli;
-mod config;
-mod ui;
-
-use std::io::ErrorKind;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 use anyhow::{Context, Result};
-use clap::Parser;
-
-fn is_not_found(err: &anyhow::Error) -> bool {
-    err.chain().any(|cause| {
-        cause
-            .downcast_ref::<std::io::Error>()
-            .map(|ioe| ioe.kind() == ErrorKind::NotFound)
-            .unwrap_or(false)
-    })
+use borg_tool_rs::{
+    audit, borg, cli, commands, config, export, filter, find, grep, history, logging, man, mounts,
+    process, ps, runlock, status, systemd, template, tree, ui,
+};
+use clap::{CommandFactory, Parser};
+
+fn main() -> Result<ExitCode> {
+    let cli = cli::Cli::parse();
+    let verbose = cli.verbose;
+    logging::init(verbose, cli.quiet);
+    borg::set_quiet(cli.quiet);
+    borg::set_plain_output(cli.plain || ui::plain_by_default());
+    borg::set_non_interactive(cli.non_interactive);
+    borg::set_no_probe(cli.no_probe);
+    borg::set_print_commands(cli.print_commands);
+    audit::enable(audit::default_audit_path());
+    runlock::enable(runlock::default_locks_dir());
+    let check_health = matches!(cli.command, Some(cli::Commands::CheckHealth { .. }));
+    let interactive = match cli.command {
+        None | Some(cli::Commands::Interactive) => true,
+        #[cfg(feature = "tui")]
+        Some(cli::Commands::Tui) => true,
+        _ => false,
+    };
+    process::install_interrupt_handler(borg::cleanup_interrupted, interactive)?;
+
+    let result = run(cli);
+    // --print-command ends at the first borg call, with its command line on stdout
+    let result = if borg::take_printed() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        result
+    };
+    if let Err(err) = &result {
+        logging::to_file(log::Level::Error, &format!("{:#}", err));
+    }
+    if process::interrupted() {
+        if let Err(err) = &result
+            && !process::is_interrupted(err)
+        {
+            eprintln!("Error: {:#}", err);
+        }
+        process::exit_interrupted(borg::cleanup_interrupted);
+    }
+    if verbose >= 2 {
+        ui::print_timing_report(&borg::timings());
+    }
+    if check_health && let Err(err) = &result {
+        // monitoring plugins report their own failures as UNKNOWN on stdout
+        println!("BORG UNKNOWN - {}", format!("{:#}", err).replace('\n', " "));
+        return Ok(ExitCode::from(status::Health::Unknown.exit_code()));
+    }
+    result
 }
 
-fn main() -> Result<()> {
+fn run(cli: cli::Cli) -> Result<ExitCode> {
     let cli::Cli {
         config: cli_config,
+        profile,
         repo: cli_repo,
+        repo_url,
+        verbose: _,
+        quiet: _,
+        plain: _,
+        non_interactive,
+        no_probe: _,
+        print_command,
+        print_commands: _,
+        json,
         command: cmd,
-    } = cli::Cli::parse();
+    } = cli;
+
+    let cli_config = match cli_config {
+        Some(path) => Some(path),
+        None => config::profile_config_path(profile.as_deref())?,
+    };
+
+    // config management must work before (or without) a loadable config
+    if let Some(cli::Commands::Config { action }) = &cmd {
+        return run_config_command(action, cli_config, json).map(|()| ExitCode::SUCCESS);
+    }
+    if let Some(cli::Commands::Import { source }) = &cmd {
+        return run_import_command(source, cli_config).map(|()| ExitCode::SUCCESS);
+    }
+    if let Some(cli::Commands::Generate {
+        target: cli::GenerateCommands::Man { out_dir },
+    }) = &cmd
+    {
+        match out_dir {
+            Some(dir) => {
+                for path in man::write_all(cli::Cli::command(), dir)? {
+                    println!("Wrote {}", path.display());
+                }
+            }
+            None => std::io::stdout().write_all(&man::render_main(cli::Cli::command())?)?,
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(cli::Commands::Ps { kill }) = &cmd {
+        for pid in kill {
+            ps::terminate(*pid)?;
+            println!("Sent SIGTERM to {}", pid);
+        }
+        if kill.is_empty() {
+            ui::print_running_backups(&runlock::running_backups());
+            ui::print_tool_processes(&ps::list()?);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(cli::Commands::History {
+        action,
+        failed,
+        limit,
+    }) = &cmd
+    {
+        let filter = history::HistoryFilter {
+            repo: cli_repo.clone(),
+            action: action.clone(),
+            failed_only: *failed,
+            limit: Some(*limit),
+        };
+        let entries = filter.apply(history::load(&history::default_history_path())?);
+        if json {
+            ui::print_json(&entries)?;
+        } else {
+            ui::print_history(&entries);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(cli::Commands::Mounts) = &cmd {
+        let records: Vec<mounts::MountRecord> = mounts::load(&mounts::default_mounts_path())?
+            .into_iter()
+            .filter(|r| cli_repo.as_ref().is_none_or(|name| *name == r.repo))
+            .collect();
+        if json {
+            ui::print_json(&records)?;
+        } else {
+            ui::print_mounts(&records);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
 
     let (mut config, config_path) = match config::load_config_resolved(cli_config.clone())
         .with_context(|| {
             "Failed to load config (searched default path and ./config.toml when unset)".to_string()
         }) {
         Ok(cfg) => cfg,
+        Err(err) if repo_url.is_some() && cli_config.is_none() && config::is_not_found(&err) => {
+            // ad-hoc repo: built-in defaults are all we need
+            (config::Config::default(), config::default_config_path())
+        }
         Err(err)
-            if matches!(cmd, None | Some(cli::Commands::Interactive)) && is_not_found(&err) =>
+            if matches!(cmd, None | Some(cli::Commands::Interactive))
+                && !non_interactive
+                && config::is_not_found(&err) =>
         {
             let config_path = cli_config.unwrap_or_else(config::default_config_path);
-            eprintln!(
+            log::info!(
                 "No config file found ({}). Starting interactive setup…",
                 err
             );
@@ -41,50 +170,158 @@ fn main() -> Result<()> {
         }
         Err(err) => return Err(err),
     };
+    if let Some(path) = &config.log_file {
+        logging::set_log_file(path)?;
+        let args: Vec<String> = std::env::args().collect();
+        logging::to_file(log::Level::Debug, &format!("started: {}", args.join(" ")));
+    }
 
     let theme = ui::dialog_theme();
     let mut passphrase_cache: Option<String> = None;
 
-    match cmd {
-        None | Some(cli::Commands::Interactive) => loop {
-            let repo_ctx = match ui::select_repo_ctx(
-                &mut config,
+    let select_repo = |config: &mut config::Config| -> Result<Option<config::RepoCtx>> {
+        let repo_ctx = match &repo_url {
+            Some(url) => Some(ui::adhoc_repo_ctx(config, url)),
+            None => ui::select_repo_ctx(
+                config,
                 &config_path,
                 cli_repo.as_deref(),
                 cmd.as_ref(),
                 &theme,
-            )? {
-                Some(r) => r,
-                None => break,
-            };
-            match ui::run_interactive(&mut config, &config_path, repo_ctx, &mut passphrase_cache)? {
-                ui::InteractiveOutcome::Quit => break,
-                ui::InteractiveOutcome::ChangeRepo => continue,
-            }
-        },
-        Some(cli::Commands::List) => {
-            let repo_ctx = ui::select_repo_ctx(
-                &mut config,
-                &config_path,
-                cli_repo.as_deref(),
-                cmd.as_ref(),
-                &theme,
-            )?
-            .ok_or_else(|| anyhow::anyhow!("No repository selected"))?;
+            )?,
+        };
+        Ok(repo_ctx.map(|mut ctx| {
+            ctx.print_command = print_command;
+            ctx
+        }))
+    };
+    let require_repo = |config: &mut config::Config| -> Result<config::RepoCtx> {
+        select_repo(config)?.ok_or_else(|| anyhow::anyhow!("No repository selected"))
+    };
+
+    match cmd {
+        None | Some(cli::Commands::Interactive) => {
+            borg::set_unmount_on_exit(config.auto_umount_on_exit);
+            let session = loop {
+                let repo_ctx = match select_repo(&mut config) {
+                    Ok(Some(r)) => r,
+                    Ok(None) => break Ok(()),
+                    Err(err) => break Err(err),
+                };
+                match ui::run_interactive(
+                    &mut config,
+                    &config_path,
+                    repo_ctx,
+                    &mut passphrase_cache,
+                ) {
+                    Ok(ui::InteractiveOutcome::Quit) => break Ok(()),
+                    Ok(ui::InteractiveOutcome::ChangeRepo) => continue,
+                    Err(err) => break Err(err),
+                }
+            };
+            borg::unmount_session_mounts();
+            session?;
+        }
+        #[cfg(feature = "tui")]
+        Some(cli::Commands::Tui) => {
+            let repos = match &repo_url {
+                Some(url) => vec![ui::adhoc_repo_ctx(&config, url)],
+                None => ui::configured_repos(&config, cli_repo.as_deref())?,
+            };
+            borg::set_unmount_on_exit(config.auto_umount_on_exit);
+            let session = borg_tool_rs::tui::run(repos);
+            borg::unmount_session_mounts();
+            session?;
+        }
+        Some(cli::Commands::List {
+            since,
+            until,
+            ref prefix,
+            ref glob,
+            last,
+            ref sort,
+            ref format,
+            include_checkpoints,
+        }) => {
+            if json && format.is_some() {
+                anyhow::bail!("--format and --json are mutually exclusive");
+            }
+            let template = format
+                .as_deref()
+                .map(|raw| template::parse_line_format(raw, template::LIST_FORMAT_VARS))
+                .transpose()?;
+            let repo_ctx = require_repo(&mut config)?;
             let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
-            let archives = borg::list_archives(&repo_ctx, pass.as_deref())?;
-            ui::print_archives(&archives);
+            let selection = filter::ArchiveFilter {
+                prefix: prefix.clone(),
+                glob: glob.clone(),
+                last,
+                sort_by: sort.clone(),
+                include_checkpoints,
+            };
+            let archives = borg::list_archives_matching(&repo_ctx, &selection, pass.as_deref())?;
+            let archives = filter::DateRange { since, until }.apply(&archives);
+            if json {
+                ui::print_json(&archives)?;
+            } else if let Some(template) = &template {
+                for archive in &archives {
+                    println!("{}", ui::format_archive(template, archive));
+                }
+            } else {
+                ui::print_archives(&archives);
+            }
         }
-        Some(cli::Commands::Files { ref archive }) => {
-            let repo_ctx = ui::select_repo_ctx(
-                &mut config,
-                &config_path,
-                cli_repo.as_deref(),
-                cmd.as_ref(),
-                &theme,
-            )?
-            .ok_or_else(|| anyhow::anyhow!("No repository selected"))?;
+        Some(cli::Commands::Files {
+            ref archive,
+            tree,
+            ref output,
+            ref format,
+            ref checksums,
+            sort,
+            reverse,
+            kind,
+            ref glob,
+        }) => {
+            if json && tree {
+                anyhow::bail!("--tree has no JSON form; use either --tree or --json");
+            }
+            let (export_format, template) = match format {
+                Some(cli::FilesFormat::Export(f)) => (Some(*f), None),
+                Some(cli::FilesFormat::Template(raw)) => (
+                    None,
+                    Some(template::parse_line_format(
+                        raw,
+                        template::FILES_FORMAT_VARS,
+                    )?),
+                ),
+                None => (None, None),
+            };
+            if export_format.is_some() && output.is_none() {
+                anyhow::bail!("--format csv/jsonl needs --output; use a template to print lines");
+            }
+            if template.is_some() && (output.is_some() || json || tree) {
+                anyhow::bail!(
+                    "a --format template cannot be combined with --output, --tree or JSON"
+                );
+            }
+            // fail on an unknown extension before the (possibly slow) listing
+            let output = output
+                .as_ref()
+                .map(|path| export::resolve_format(path, export_format).map(|f| (path, f)))
+                .transpose()?;
+            let pattern = glob.as_deref().map(|g| find::borg_pattern(g, false));
+            let repo_ctx = require_repo(&mut config)?;
             let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            if print_command && let Some(name) = archive {
+                borg::list_items_with_checksum(
+                    &repo_ctx,
+                    name,
+                    checksums.as_deref(),
+                    pattern.as_deref(),
+                    pass.as_deref(),
+                )?;
+                return Ok(ExitCode::SUCCESS);
+            }
             let archives = borg::list_archives(&repo_ctx, pass.as_deref())?;
             let selected = match archive {
                 Some(name) => archives
@@ -94,80 +331,662 @@ fn main() -> Result<()> {
                     .ok_or_else(|| anyhow::anyhow!("Archive '{}' not found", name))?,
                 None => match ui::select_archive(&archives, &theme)? {
                     Some(a) => a,
-                    None => return Ok(()),
+                    None => return Ok(ExitCode::SUCCESS),
                 },
             };
-            let items = borg::list_items(&repo_ctx, &selected.name, pass.as_deref())?;
-            ui::print_items(&items);
+            let keep = |item: &borg::BorgItem| kind.is_none_or(|k| k.matches(item));
+            let print = |item: &borg::BorgItem| match &template {
+                Some(template) => println!("{}", ui::format_item(template, item)),
+                None => ui::print_item(item, checksums.is_some()),
+            };
+            if output.is_none() && !json && !tree && sort.is_none() {
+                // plain listing: print items while borg is still producing them
+                let mut count = 0;
+                borg::for_each_item(
+                    &repo_ctx,
+                    &selected.name,
+                    checksums.as_deref(),
+                    pattern.as_deref(),
+                    pass.as_deref(),
+                    |item| {
+                        if keep(&item) {
+                            print(&item);
+                            count += 1;
+                        }
+                        Ok(())
+                    },
+                )?;
+                if count == 0 && template.is_none() {
+                    println!("No files in archive");
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            let mut items = borg::list_items_with_checksum(
+                &repo_ctx,
+                &selected.name,
+                checksums.as_deref(),
+                pattern.as_deref(),
+                pass.as_deref(),
+            )?;
+            items.retain(keep);
+            if let Some(key) = sort {
+                filter::sort_items(&mut items, key, reverse);
+            }
+            if let Some((path, format)) = output {
+                export::export_items(path, format, &items)?;
+                if json {
+                    ui::print_json(&serde_json::json!({
+                        "path": path,
+                        "items": items.len(),
+                    }))?;
+                } else {
+                    println!("Wrote {} item(s) to {}", items.len(), path.display());
+                }
+            } else if json {
+                ui::print_json(&items)?;
+            } else if tree {
+                ui::print_tree(&tree::build(&items));
+            } else if items.is_empty() && template.is_none() {
+                println!("No files in archive");
+            } else {
+                for item in &items {
+                    print(item);
+                }
+            }
+        }
+        Some(cli::Commands::Grep {
+            ref archive,
+            ref pattern,
+            ref path_prefix,
+            ignore_case,
+            context,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            let items = borg::list_items(&repo_ctx, archive, pass.as_deref())?;
+            let files = grep::candidates(&items, path_prefix.as_deref());
+            if borg::is_remote_repo(&repo_ctx.repo) {
+                let total: u64 = files.iter().filter_map(|f| f.size).sum();
+                log::warn!(
+                    "{} is remote; searching downloads and decrypts {} file(s) ({}), one borg process each. Narrow it down with a path prefix if this is too slow.",
+                    repo_ctx.repo,
+                    files.len(),
+                    ui::human_size(total)
+                );
+            }
+            let opts = grep::GrepOptions {
+                pattern: pattern.clone(),
+                ignore_case,
+                context,
+            };
+            let report = grep::search_archive(&repo_ctx, archive, &files, &opts, pass.as_deref())?;
+            ui::print_grep_report(&report);
+        }
+        Some(cli::Commands::Find {
+            ref pattern,
+            regex,
+            last,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            let pattern = find::borg_pattern(pattern, regex);
+            let archives =
+                find::newest_first(&borg::list_archives(&repo_ctx, pass.as_deref())?, last);
+            let report = find::find_in_archives(&repo_ctx, &archives, &pattern, pass.as_deref())?;
+            if json {
+                ui::print_json(&report)?;
+            } else {
+                ui::print_find_report(&report);
+            }
         }
         Some(cli::Commands::Mount {
             ref archive,
             ref target,
+            open,
+            shell,
         }) => {
-            let repo_ctx = ui::select_repo_ctx(
-                &mut config,
-                &config_path,
-                cli_repo.as_deref(),
-                cmd.as_ref(),
-                &theme,
-            )?
-            .ok_or_else(|| anyhow::anyhow!("No repository selected"))?;
+            let repo_ctx = require_repo(&mut config)?;
             borg::ensure_mount_available(&repo_ctx)?;
+            if !print_command {
+                ui::offer_stale_cleanup(&repo_ctx, &theme)?;
+            }
             let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
             let mountpoint = target
                 .clone()
-                .unwrap_or_else(|| borg::default_mountpoint(&repo_ctx, archive));
-            borg::mount_archive(&repo_ctx, archive, &mountpoint, pass.as_deref())?;
-            println!("Mounted {} at {}", archive, mountpoint.display());
+                .unwrap_or_else(|| borg::default_mountpoint(&repo_ctx, archive.as_deref()));
+            borg::mount_archive(&repo_ctx, archive.as_deref(), &mountpoint, pass.as_deref())?;
+            match archive {
+                Some(name) => println!("Mounted {} at {}", name, mountpoint.display()),
+                None => println!(
+                    "Mounted repository {} at {} (one directory per archive)",
+                    repo_ctx.name,
+                    mountpoint.display()
+                ),
+            }
+            let follow_up = match (open, shell) {
+                (true, _) => ui::MountFollowUp::FileManager,
+                (_, true) => ui::MountFollowUp::Shell,
+                _ => ui::MountFollowUp::Nothing,
+            };
+            if !print_command {
+                ui::follow_up_mount(&repo_ctx, &mountpoint, follow_up, pass.as_deref(), &theme)?;
+            }
         }
-        Some(cli::Commands::Umount { ref mountpoint }) => {
-            let repo_ctx = ui::select_repo_ctx(
-                &mut config,
-                &config_path,
-                cli_repo.as_deref(),
-                cmd.as_ref(),
-                &theme,
-            )?
-            .ok_or_else(|| anyhow::anyhow!("No repository selected"))?;
+        Some(cli::Commands::Restore {
+            ref archive,
+            ref paths,
+            numeric_ids,
+            sparse,
+            dry_run,
+            yes,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
             let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
-            borg::umount_archive(&repo_ctx, mountpoint, pass.as_deref())?;
+            let archive = match archive {
+                Some(name) => name.clone(),
+                None => {
+                    let archives = borg::list_archives(&repo_ctx, pass.as_deref())?;
+                    match ui::select_archive(&archives, &theme)? {
+                        Some(a) => a.name,
+                        None => return Ok(ExitCode::SUCCESS),
+                    }
+                }
+            };
+            let paths = if paths.is_empty() {
+                ui::prompt_restore_paths(&theme)?
+            } else {
+                paths.clone()
+            };
+            let paths = commands::restore_paths_from_args(&paths)?;
+            let root = Path::new("/");
+            if print_command {
+                return borg::restore_paths(
+                    &repo_ctx,
+                    &archive,
+                    &paths,
+                    root,
+                    numeric_ids,
+                    sparse,
+                    pass.as_deref(),
+                )
+                .map(|()| ExitCode::SUCCESS);
+            }
+            let plan = commands::plan_restore(&repo_ctx, &archive, &paths, root, pass.as_deref())?;
+            ui::print_restore_plan(&archive, &plan.items, &plan.overwritten, numeric_ids);
+            if dry_run {
+                println!("Dry run: nothing restored");
+                return Ok(ExitCode::SUCCESS);
+            }
+            if !yes && !ui::confirm_restore(plan.items.len(), plan.overwritten.len(), &theme)? {
+                println!("Aborted");
+                return Ok(ExitCode::SUCCESS);
+            }
+            borg::restore_paths(
+                &repo_ctx,
+                &archive,
+                &paths,
+                root,
+                numeric_ids,
+                sparse,
+                pass.as_deref(),
+            )?;
+            println!(
+                "Restored {} item(s) from {} to their original locations",
+                plan.items.len(),
+                archive
+            );
+        }
+        Some(cli::Commands::RestoreSystem) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            ui::restore_system_wizard(&repo_ctx, pass.as_deref(), &theme)?;
+        }
+        Some(cli::Commands::Umount {
+            all: true, lazy, ..
+        }) => {
+            let records = mounts::load(&mounts::default_mounts_path())?;
+            let repos = ui::configured_repos(&config, None)?;
+            let mut unmounted = 0;
+            let mut failed = 0;
+            for record in records
+                .iter()
+                .filter(|r| cli_repo.as_ref().is_none_or(|name| *name == r.repo))
+            {
+                let Some(mut repo_ctx) = repos.iter().find(|c| c.name == record.repo).cloned()
+                else {
+                    log::warn!(
+                        "skipping {}: repo '{}' is no longer configured",
+                        record.mountpoint.display(),
+                        record.repo
+                    );
+                    failed += 1;
+                    continue;
+                };
+                repo_ctx.print_command = print_command;
+                // borg umount only talks to FUSE, no passphrase needed
+                match borg::umount_archive(&repo_ctx, &record.mountpoint, None, lazy) {
+                    Ok(()) => {
+                        println!("Unmounted {}", record.mountpoint.display());
+                        unmounted += 1;
+                    }
+                    Err(err) => {
+                        log::error!(
+                            "could not unmount {}: {:#}",
+                            record.mountpoint.display(),
+                            err
+                        );
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("{} mount(s) could not be unmounted", failed);
+            }
+            if unmounted == 0 {
+                println!("Nothing mounted by borg-tool");
+            }
+        }
+        Some(cli::Commands::Umount {
+            mountpoint: Some(ref mountpoint),
+            lazy,
+            ..
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            borg::umount_archive(&repo_ctx, mountpoint, pass.as_deref(), lazy)?;
             println!("Unmounted {}", mountpoint.display());
         }
-        Some(cli::Commands::Backup { ref backup }) => {
-            let repo_ctx = ui::select_repo_ctx(
-                &mut config,
-                &config_path,
-                cli_repo.as_deref(),
-                cmd.as_ref(),
-                &theme,
-            )?
-            .ok_or_else(|| anyhow::anyhow!("No repository selected"))?;
+        Some(cli::Commands::Delete {
+            ref archives,
+            keep_last,
+            ref prefix,
+            checkpoints,
+            dry_run,
+            yes,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
             let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
-            let preset = if let Some(name) = backup {
-                repo_ctx
+            let selection = match keep_last {
+                _ if checkpoints => commands::DeleteSelection::Checkpoints,
+                Some(keep) => commands::DeleteSelection::KeepLast {
+                    keep,
+                    prefix: prefix.as_deref(),
+                },
+                None => commands::DeleteSelection::Named(archives),
+            };
+            let listed =
+                borg::list_archives_matching(&repo_ctx, &selection.listing(), pass.as_deref())?;
+            let doomed = commands::archives_to_delete(&listed, selection)?;
+            if doomed.is_empty() {
+                println!("Nothing to delete");
+                return Ok(ExitCode::SUCCESS);
+            }
+            println!("Archives to delete:");
+            ui::print_archives(&doomed);
+            if dry_run {
+                println!("Dry run: {} archive(s) would be deleted", doomed.len());
+                return Ok(ExitCode::SUCCESS);
+            }
+            let names: Vec<String> = doomed.into_iter().map(|a| a.name).collect();
+            let confirmed = yes
+                || match keep_last {
+                    Some(keep) => ui::confirm_delete(names.len(), keep, &theme)?,
+                    None => ui::confirm_delete_archives(&names, &theme)?,
+                };
+            if !confirmed {
+                println!("Aborted");
+                return Ok(ExitCode::SUCCESS);
+            }
+            borg::delete_archives(&repo_ctx, &names, pass.as_deref())?;
+            println!("Deleted {} archive(s)", names.len());
+        }
+        Some(cli::Commands::Diff {
+            ref older,
+            ref newer,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            let entries = borg::diff_archives(&repo_ctx, older, newer, pass.as_deref())?;
+            ui::print_diff(&entries);
+        }
+        Some(cli::Commands::Info { ref archive }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            let report = borg::info(&repo_ctx, archive.as_deref(), pass.as_deref())?;
+            if json {
+                ui::print_json(&report)?;
+            } else {
+                ui::print_info(&report);
+            }
+        }
+        Some(cli::Commands::Check {
+            ref archive,
+            repair,
+            verify_data,
+            yes,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            if repair && !yes && !print_command && !ui::confirm_repair(&repo_ctx.name, &theme)? {
+                return Ok(ExitCode::SUCCESS);
+            }
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            let opts = borg::CheckOptions {
+                repair,
+                verify_data,
+                ..Default::default()
+            };
+            borg::check_repo(&repo_ctx, archive.as_deref(), opts, pass.as_deref())?;
+            println!(
+                "No problems found in {}",
+                archive.as_deref().unwrap_or(&repo_ctx.name)
+            );
+        }
+        Some(cli::Commands::Prune {
+            ref backup,
+            dry_run,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let presets: Vec<&config::BackupConfig> = match backup {
+                Some(name) => vec![commands::find_backup(&repo_ctx, name)?],
+                None => repo_ctx
                     .backups
                     .iter()
-                    .find(|b| b.name == *name)
-                    .cloned()
-                    .ok_or_else(|| {
-                        let names: Vec<&str> =
-                            repo_ctx.backups.iter().map(|b| b.name.as_str()).collect();
-                        anyhow::anyhow!(
-                            "Backup '{}' not found. Available: {}",
-                            name,
-                            names.join(", ")
-                        )
-                    })?
+                    .filter(|b| !b.retention_args().is_empty())
+                    .collect(),
+            };
+            if presets.is_empty() {
+                anyhow::bail!(
+                    "No backup preset of repo '{}' has a retention policy (keep_daily, keep_weekly, keep_monthly, keep_yearly)",
+                    repo_ctx.name
+                );
+            }
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            // --print-command shows the prune call rather than the `borg info` taken first
+            let compact = repo_ctx.compact_after_prune && !dry_run && !print_command;
+            let before = if compact {
+                Some(borg::stored_size(&repo_ctx, pass.as_deref())?)
+            } else {
+                None
+            };
+            for preset in presets {
+                let report = borg::prune_archives(&repo_ctx, preset, dry_run, pass.as_deref())?;
+                ui::print_prune_report(&preset.name, &report, dry_run);
+            }
+            if let Some(before) = before {
+                borg::compact_repo(&repo_ctx, pass.as_deref())?;
+                let after = borg::stored_size(&repo_ctx, pass.as_deref())?;
+                println!("Freed {}", ui::human_size(before.saturating_sub(after)));
+            }
+        }
+        Some(cli::Commands::Extract {
+            ref archive,
+            ref paths,
+            ref dest,
+            strip_components,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            borg::extract_paths(
+                &repo_ctx,
+                archive,
+                paths,
+                dest,
+                strip_components,
+                pass.as_deref(),
+            )?;
+            println!("Extracted {} to {}", archive, dest.display());
+        }
+        Some(cli::Commands::ExportTar {
+            ref archive,
+            ref file,
+            ref paths,
+            ref compression,
+        }) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            let compression = compression
+                .as_deref()
+                .unwrap_or_else(|| borg::tar_compression_for(file));
+            borg::export_tar(
+                &repo_ctx,
+                archive,
+                file,
+                paths,
+                compression,
+                pass.as_deref(),
+            )?;
+            println!("Exported {} to {}", archive, file.display());
+        }
+        Some(cli::Commands::Compact) => {
+            let repo_ctx = require_repo(&mut config)?;
+            let pass = borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?;
+            if print_command {
+                return borg::compact_repo(&repo_ctx, pass.as_deref()).map(|()| ExitCode::SUCCESS);
+            }
+            let before = borg::stored_size(&repo_ctx, pass.as_deref())?;
+            borg::compact_repo(&repo_ctx, pass.as_deref())?;
+            let after = borg::stored_size(&repo_ctx, pass.as_deref())?;
+            println!("Freed {}", ui::human_size(before.saturating_sub(after)));
+        }
+        Some(cli::Commands::BreakLock) => {
+            let repo_ctx = require_repo(&mut config)?;
+            borg::break_lock(&repo_ctx)?;
+            println!("Removed the lock of {}", repo_ctx.name);
+        }
+        Some(cli::Commands::Status) => {
+            if repo_url.is_some() {
+                anyhow::bail!("status checks configured presets; it cannot use --repo-url");
+            }
+            let mut repos = ui::configured_repos(&config, cli_repo.as_deref())?;
+            for repo_ctx in &mut repos {
+                repo_ctx.print_command = print_command;
+            }
+            let statuses = commands::collect_statuses(&repos);
+            if json {
+                ui::print_json(&statuses)?;
+            } else {
+                ui::print_status(&statuses);
+            }
+            commands::ensure_no_problems(&statuses)?;
+        }
+        Some(cli::Commands::Generate {
+            target:
+                cli::GenerateCommands::Systemd {
+                    ref preset,
+                    ref on_calendar,
+                    install,
+                },
+        }) => {
+            if repo_url.is_some() {
+                anyhow::bail!("generate systemd needs a configured repo; it cannot use --repo-url");
+            }
+            let repo_ctx = require_repo(&mut config)?;
+            commands::find_backup(&repo_ctx, preset)?;
+            let exe = std::env::current_exe().context("Locate the borg-tool executable")?;
+            let config_path = std::path::absolute(&config_path).unwrap_or(config_path.clone());
+            let units = systemd::render_units(&exe, &config_path, &repo_ctx, preset, on_calendar);
+            if install {
+                for path in systemd::install(&units, &systemd::user_unit_dir()?)? {
+                    println!("Wrote {}", path.display());
+                }
+                println!(
+                    "Activate with: systemctl --user daemon-reload && systemctl --user enable --now {}.timer",
+                    units.name
+                );
+            } else {
+                println!("# {}.service\n{}", units.name, units.service);
+                println!("# {}.timer\n{}", units.name, units.timer);
+            }
+        }
+        Some(cli::Commands::CheckHealth { warning, critical }) => {
+            // a monitoring agent has no terminal to answer prompts on
+            borg::set_non_interactive(true);
+            let thresholds = status::HealthThresholds { warning, critical };
+            let repos = ui::configured_repos(&config, cli_repo.as_deref())?;
+            let runs = history::load(&history::default_history_path()).unwrap_or_default();
+            let (health, line) = commands::check_health(&repos, &runs, thresholds);
+            println!("{}", line);
+            return Ok(ExitCode::from(health.exit_code()));
+        }
+        Some(cli::Commands::Backup {
+            all: true,
+            ratelimit,
+            ref comment,
+            ..
+        }) => {
+            if repo_url.is_some() {
+                anyhow::bail!("backup --all runs configured presets; it cannot use --repo-url");
+            }
+            let mut repos = ui::configured_repos(&config, cli_repo.as_deref())?;
+            for repo_ctx in &mut repos {
+                repo_ctx.print_command = print_command;
+                commands::override_presets(repo_ctx, ratelimit, comment.as_deref());
+            }
+            let runs = commands::backup_all(&repos)?;
+            if print_command && runs.iter().all(borg::BackupRun::succeeded) {
+                // the command line is all there is to show
+                return Ok(ExitCode::SUCCESS);
+            }
+            if json {
+                ui::print_json(&runs)?;
+            } else {
+                ui::print_backup_runs(&runs);
+            }
+            commands::ensure_backups_succeeded(&runs)?;
+        }
+        Some(cli::Commands::Backup {
+            ref backup,
+            ratelimit,
+            ref comment,
+            dry_run,
+            list,
+            ..
+        }) => {
+            let mut repo_ctx = require_repo(&mut config)?;
+            commands::override_presets(&mut repo_ctx, ratelimit, comment.as_deref());
+            let pass = if dry_run && !list {
+                // printing the command never needs the passphrase; without one at hand the
+                // command shows no BORG_PASSPHRASE, like a repo that needs none
+                borg::passphrase_without_prompt(&repo_ctx, &passphrase_cache).flatten()
+            } else {
+                borg::ensure_passphrase_cached(&mut passphrase_cache, &repo_ctx)?
+            };
+            let preset = if let Some(name) = backup {
+                commands::find_backup(&repo_ctx, name)?.clone()
             } else {
                 match ui::select_backup(&repo_ctx.backups, &theme)? {
-                    ui::BackupChoice::Preset(p) => p,
-                    _ => return Ok(()),
+                    ui::BackupChoice::Preset(p) => *p,
+                    _ => return Ok(ExitCode::SUCCESS),
                 }
             };
 
-            borg::run_backup(&repo_ctx, &preset, pass.as_deref())?;
+            if dry_run {
+                let preview = borg::preview_backup(&repo_ctx, &preset, pass.as_deref(), list)?;
+                if json {
+                    ui::print_json(&preview)?;
+                } else {
+                    ui::print_backup_preview(&preview);
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            let outcome = borg::run_backup(&repo_ctx, &preset, pass.as_deref())?;
+            if json {
+                ui::print_json(&outcome)?;
+            } else {
+                ui::print_backup_outcome(&outcome);
+            }
+            if let Some(err) = outcome.verification_error() {
+                anyhow::bail!(err);
+            }
+        }
+        Some(cli::Commands::Config { .. })
+        | Some(cli::Commands::Import { .. })
+        | Some(cli::Commands::Generate {
+            target: cli::GenerateCommands::Man { .. },
+        })
+        | Some(cli::Commands::Ps { .. })
+        | Some(cli::Commands::Mounts)
+        | Some(cli::Commands::History { .. }) => {
+            unreachable!("handled before config loading")
+        }
+        Some(cli::Commands::Umount {
+            mountpoint: None, ..
+        }) => unreachable!("clap requires a mountpoint without --all"),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_config_command(
+    action: &cli::ConfigCommands,
+    cli_config: Option<PathBuf>,
+    json: bool,
+) -> Result<()> {
+    match action {
+        cli::ConfigCommands::Show { format } => {
+            let (cfg, path, origin) = commands::load_config_for_show(cli_config)?;
+            let format = format.or(json.then_some(cli::ConfigFormat::Json));
+            match format {
+                None => print!("{}", config::render_effective_config(&cfg, &path, origin)?),
+                Some(cli::ConfigFormat::Toml) => {
+                    print!(
+                        "{}",
+                        toml::to_string_pretty(&config::resolved_config(&cfg)?)?
+                    );
+                }
+                Some(cli::ConfigFormat::Json) => ui::print_json(&config::resolved_config(&cfg)?)?,
+            }
+        }
+        cli::ConfigCommands::Init { path, force } => {
+            let path = path
+                .clone()
+                .or(cli_config)
+                .unwrap_or_else(config::default_config_path);
+            config::init_config(&path, *force)?;
+            println!("Example config written to {}", path.display());
+        }
+        cli::ConfigCommands::Fetch {
+            url,
+            sha256,
+            signature_url,
+            force,
+        } => {
+            let path = cli_config.unwrap_or_else(config::default_config_path);
+            config::fetch_config(
+                url,
+                &path,
+                sha256.as_deref(),
+                signature_url.as_deref(),
+                *force,
+            )?;
+            println!("Config saved to {}", path.display());
         }
     }
+    Ok(())
+}
 
+fn run_import_command(source: &cli::ImportCommands, cli_config: Option<PathBuf>) -> Result<()> {
+    let cli::ImportCommands::Borgmatic {
+        path,
+        preset,
+        dry_run,
+    } = source;
+    let config_path = cli_config.unwrap_or_else(config::default_config_path);
+    let import = commands::import_borgmatic(path, preset.as_deref(), &config_path)?;
+    for skipped in &import.skipped {
+        log::warn!("not imported: {}", skipped);
+    }
+    if *dry_run {
+        print!("{}", import.preview);
+        for change in &import.changes {
+            eprintln!("{}", change);
+        }
+    } else {
+        config::save_config(&import.config, &config_path)?;
+        for change in &import.changes {
+            println!("{}", change);
+        }
+        println!("Config saved to {}", config_path.display());
+    }
     Ok(())
 }
@@ -0,0 +1,173 @@
+//! Man pages generated from the clap definitions (`generate man`), for distribution packages.
+//!
+//! `borg-tool.1` additionally describes the config file (the commented template of
+//! `config init`, so every field is covered), the files borg-tool keeps and its environment.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Command;
+use clap_mangen::Man;
+use clap_mangen::roff::{Roff, bold, italic, roman};
+
+use crate::config::CONFIG_TEMPLATE;
+
+/// `borg-tool.1`: the generated page plus CONFIGURATION, FILES and ENVIRONMENT.
+pub fn render_main(cmd: Command) -> Result<Vec<u8>> {
+    let mut page = Vec::new();
+    Man::new(cmd).render(&mut page)?;
+    page.extend(extra_sections().to_roff().into_bytes());
+    Ok(page)
+}
+
+/// Write `borg-tool.1` and a `borg-tool-<subcommand>.1` page per visible subcommand to
+/// `out_dir`; returns the written paths.
+pub fn write_all(cmd: Command, out_dir: &Path) -> Result<Vec<PathBuf>> {
+    fn write_subcommands(cmd: &Command, out_dir: &Path, written: &mut Vec<PathBuf>) -> Result<()> {
+        for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+            written.push(Man::new(sub.clone()).generate_to(out_dir)?);
+            write_subcommands(sub, out_dir, written)?;
+        }
+        Ok(())
+    }
+
+    fs::create_dir_all(out_dir).with_context(|| format!("Create {}", out_dir.display()))?;
+    let mut cmd = cmd.disable_help_subcommand(true);
+    cmd.build();
+    let main = out_dir.join(Man::new(cmd.clone()).get_filename());
+    fs::write(&main, render_main(cmd.clone())?)
+        .with_context(|| format!("Write {}", main.display()))?;
+    let mut written = vec![main];
+    write_subcommands(&cmd, out_dir, &mut written)?;
+    Ok(written)
+}
+
+fn extra_sections() -> Roff {
+    let mut roff = Roff::new();
+
+    roff.control("SH", ["CONFIGURATION"]);
+    roff.text([
+        roman("The config file is TOML. It is read from "),
+        bold("--config"),
+        roman(", else "),
+        italic("$XDG_CONFIG_HOME/borg-tool/config.toml"),
+        roman(", else "),
+        italic("./config.toml"),
+        roman(". "),
+        bold("--profile"),
+        roman(" NAME reads "),
+        italic("config.NAME.toml"),
+        roman(" from the config directory instead."),
+    ]);
+    roff.control("PP", []);
+    roff.text([
+        roman("Every "),
+        italic("*.toml"),
+        roman(" file in "),
+        italic("config.d/"),
+        roman(" next to the config file is merged in name order; repos and presets are merged by "),
+        bold("name"),
+        roman(", other keys are replaced by later files."),
+    ]);
+    roff.control("PP", []);
+    roff.text([
+        bold("borg-tool config init"),
+        roman(" writes the following template, which lists every setting; "),
+        bold("borg-tool config show"),
+        roman(" prints the effective values and where each comes from."),
+    ]);
+    roff.control("PP", []);
+    roff.control("RS", ["4"]);
+    roff.control("nf", []);
+    for line in CONFIG_TEMPLATE.lines() {
+        roff.text([roman(line)]);
+    }
+    roff.control("fi", []);
+    roff.control("RE", []);
+
+    roff.control("SH", ["FILES"]);
+    for (path, what) in [
+        (
+            "$XDG_CONFIG_HOME/borg-tool/config.toml",
+            "The config file (see CONFIGURATION).",
+        ),
+        (
+            "$XDG_CONFIG_HOME/borg-tool/meta.toml",
+            "default_profile = \"NAME\" makes --profile NAME the default.",
+        ),
+        (
+            "$XDG_STATE_HOME/borg-tool/history.jsonl",
+            "One line per backup, prune, check, ... run (borg-tool history).",
+        ),
+        (
+            "$XDG_STATE_HOME/borg-tool/mounts.json",
+            "Archives mounted by borg-tool (borg-tool mounts).",
+        ),
+        (
+            "$XDG_STATE_HOME/borg-tool/probes.json",
+            "Time of the last successful ssh probe per repo (probe_cache_seconds).",
+        ),
+        (
+            "$XDG_STATE_HOME/borg-tool/audit.jsonl",
+            "Every borg command borg-tool ran, with duration and exit code (secrets redacted).",
+        ),
+    ] {
+        roff.control("TP", []);
+        roff.text([italic(path)]);
+        roff.text([roman(what)]);
+    }
+
+    roff.control("SH", ["ENVIRONMENT"]);
+    for (name, what) in [
+        (
+            "BORG_PASSPHRASE, BORG_PASSCOMMAND",
+            "Passed on to borg; no passphrase prompt when one is set.",
+        ),
+        (
+            "BORG_TOOL_REPO",
+            "Without --config, build the config from BORG_TOOL_* variables instead of a file \
+             (see the README for the full list).",
+        ),
+        (
+            "CI",
+            "When true, status lines replace spinners as with --plain.",
+        ),
+    ] {
+        roff.control("TP", []);
+        roff.text([bold(name)]);
+        roff.text([roman(what)]);
+    }
+
+    roff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn main_page_covers_commands_and_config_fields() {
+        let page = String::from_utf8(render_main(crate::cli::Cli::command()).unwrap()).unwrap();
+        assert!(page.starts_with(".ie \\n(.g .ds Aq"));
+        assert!(page.contains(".TH borg-tool 1"));
+        assert!(page.contains(".SH CONFIGURATION"));
+        assert!(page.contains("probe_cache_seconds"));
+        assert!(page.contains("keep_yearly"));
+        assert!(page.contains(".SH FILES"));
+    }
+
+    #[test]
+    fn write_all_skips_hidden_subcommands() {
+        let tmp = tempfile::tempdir().unwrap();
+        let written = write_all(crate::cli::Cli::command(), tmp.path()).unwrap();
+        let names: Vec<String> = written
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names[0], "borg-tool.1");
+        assert!(names.contains(&"borg-tool-config-init.1".to_string()));
+        assert!(!names.iter().any(|n| n.contains("generate-man")));
+    }
+}
@@ -0,0 +1,316 @@
+//! Mounts created by borg-tool (`mounts` subcommand, `umount --all`).
+//!
+//! Kept in `$XDG_STATE_HOME/borg-tool/mounts.json` because `borg mount` daemonizes: the
+//! mount outlives the run that created it, and a later run or interactive session still
+//! needs to know what to unmount. After a crash some of them are dead; [`find_stale`]
+//! spots those and the empty directories left in `mount_root`.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::history::state_dir;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MountRecord {
+    /// Repo name from the config
+    pub repo: String,
+    /// `None` when the whole repository is mounted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<String>,
+    pub mountpoint: PathBuf,
+    /// borg-tool process that created the mount
+    pub pid: u32,
+    /// Local time of the mount, RFC 3339
+    pub mounted_at: String,
+}
+
+impl MountRecord {
+    /// Record for a mount made just now by this process.
+    pub fn new(repo: &str, archive: Option<&str>, mountpoint: &Path) -> Self {
+        Self {
+            repo: repo.to_string(),
+            archive: archive.map(str::to_string),
+            mountpoint: normalize(mountpoint),
+            pid: std::process::id(),
+            mounted_at: chrono::Local::now().to_rfc3339(),
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        self.archive.as_deref().unwrap_or("all archives")
+    }
+}
+
+/// Absolute form of `path` when it exists, so `mnt` and `./mnt/` name the same mount.
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+pub fn default_mounts_path() -> PathBuf {
+    state_dir().join("mounts.json")
+}
+
+/// Recorded mounts, oldest first; a missing file means none.
+pub fn load(path: &Path) -> Result<Vec<MountRecord>> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("Read {}", path.display())),
+    };
+    serde_json::from_str(&raw).with_context(|| format!("Parse {}", path.display()))
+}
+
+/// Replace the file at `path` with `records` (write to a temp file, then rename).
+pub fn save(path: &Path, records: &[MountRecord]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Create {}", dir.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(records)?)
+        .with_context(|| format!("Write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Replace {}", path.display()))
+}
+
+/// Add `record`, dropping an older record for the same mountpoint.
+pub fn add(path: &Path, record: MountRecord) -> Result<()> {
+    let mut records = load(path)?;
+    records.retain(|r| r.mountpoint != record.mountpoint);
+    records.push(record);
+    save(path, &records)
+}
+
+/// Forget the mount at `mountpoint`; unknown mountpoints are ignored.
+pub fn remove(path: &Path, mountpoint: &Path) -> Result<()> {
+    let mountpoint = normalize(mountpoint);
+    let mut records = load(path)?;
+    let before = records.len();
+    records.retain(|r| r.mountpoint != mountpoint);
+    if records.len() == before {
+        return Ok(());
+    }
+    save(path, &records)
+}
+
+/// Mount table of this process (Linux).
+const PROC_MOUNTS: &str = "/proc/self/mounts";
+
+/// errno of a FUSE mount whose daemon is gone ("Transport endpoint is not connected").
+const ENOTCONN: i32 = 107;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaleKind {
+    /// Still in the mount table, but borg behind it has died
+    Disconnected,
+    /// Nothing mounted: an empty directory, or a record whose directory is gone
+    Leftover,
+}
+
+impl StaleKind {
+    pub fn describe(self) -> &'static str {
+        match self {
+            StaleKind::Disconnected => "dead mount (transport endpoint is not connected)",
+            StaleKind::Leftover => "nothing mounted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleMount {
+    pub path: PathBuf,
+    pub kind: StaleKind,
+}
+
+/// Whether something is mounted at `path`; false where `/proc/self/mounts` is missing.
+pub fn is_mounted(path: &Path) -> bool {
+    fs::read_to_string(PROC_MOUNTS).is_ok_and(|table| mounted_paths(&table).contains(path))
+}
+
+/// Dead mounts and leftover directories in `mount_root` and among `records`. Empty on
+/// systems without `/proc/self/mounts`, where live and stale mounts cannot be told apart.
+pub fn find_stale(mount_root: &Path, records: &[MountRecord]) -> Vec<StaleMount> {
+    match fs::read_to_string(PROC_MOUNTS) {
+        Ok(table) => stale_in(&table, mount_root, records),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn stale_in(table: &str, mount_root: &Path, records: &[MountRecord]) -> Vec<StaleMount> {
+    let mounted = mounted_paths(table);
+    let mut candidates: Vec<PathBuf> = records.iter().map(|r| r.mountpoint.clone()).collect();
+    if let Ok(entries) = fs::read_dir(normalize(mount_root)) {
+        candidates.extend(entries.flatten().map(|e| e.path()));
+    }
+    candidates.sort();
+    candidates.dedup();
+    candidates
+        .into_iter()
+        .filter_map(|path| {
+            let kind = classify(&path, mounted.contains(&path))?;
+            Some(StaleMount { path, kind })
+        })
+        .collect()
+}
+
+fn classify(path: &Path, mounted: bool) -> Option<StaleKind> {
+    let listing = fs::read_dir(path);
+    if mounted {
+        return match listing {
+            Err(err) if err.raw_os_error() == Some(ENOTCONN) => Some(StaleKind::Disconnected),
+            _ => None,
+        };
+    }
+    match listing {
+        Ok(mut entries) => entries.next().is_none().then_some(StaleKind::Leftover),
+        Err(err) if err.kind() == ErrorKind::NotFound => Some(StaleKind::Leftover),
+        // files and unreadable directories are not ours to touch
+        Err(_) => None,
+    }
+}
+
+/// Mountpoints in a `/proc/self/mounts` table, with its octal escapes (`\040` for a
+/// space) decoded.
+fn mounted_paths(table: &str) -> HashSet<PathBuf> {
+    table
+        .lines()
+        .filter_map(|line| line.split(' ').nth(1))
+        .map(|field| PathBuf::from(unescape_mount_field(field)))
+        .collect()
+}
+
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let code = rest.get(pos + 1..pos + 4);
+        match code.and_then(|c| u8::from_str_radix(c, 8).ok()) {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Unmount a FUSE mount without borg, which also works when its borg process is dead or
+/// borg is missing: `fusermount3 -u`/`fusermount -u` (`-z` when `lazy`: detach now, finish
+/// once it is no longer busy), or `umount` (`-f` when `lazy`) on macOS.
+pub fn system_unmount(mountpoint: &Path, lazy: bool) -> Result<()> {
+    let commands: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("umount", if lazy { &["-f"] } else { &[] })]
+    } else if lazy {
+        &[
+            ("fusermount3", &["-u", "-z"]),
+            ("fusermount", &["-u", "-z"]),
+        ]
+    } else {
+        &[("fusermount3", &["-u"]), ("fusermount", &["-u"])]
+    };
+    let mut last_err = None;
+    for (program, args) in commands {
+        match Command::new(program).args(*args).arg(mountpoint).output() {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => anyhow::bail!(
+                "{} {} {} failed: {}",
+                program,
+                args.join(" "),
+                mountpoint.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(err) if err.kind() == ErrorKind::NotFound => last_err = Some(err),
+            Err(err) => return Err(err).with_context(|| format!("Run {}", program)),
+        }
+    }
+    let tried: Vec<&str> = commands.iter().map(|(program, _)| *program).collect();
+    Err(last_err.expect("tried at least one program"))
+        .with_context(|| format!("None of {} found", tried.join(", ")))
+}
+
+/// Unmount (if dead) and forget `stale`; its directory is removed only inside
+/// `mount_root`, where borg-tool created it.
+pub fn clean_stale(stale: &StaleMount, mount_root: &Path, records_path: &Path) -> Result<()> {
+    if stale.kind == StaleKind::Disconnected {
+        system_unmount(&stale.path, false)?;
+    }
+    if stale.path.starts_with(normalize(mount_root)) {
+        match fs::remove_dir(&stale.path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("Remove {}", stale.path.display()));
+            }
+        }
+    }
+    remove(records_path, &stale.path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_replaces_same_mountpoint_and_remove_forgets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("state").join("mounts.json");
+        assert!(load(&path).unwrap().is_empty());
+
+        let mnt = tmp.path().join("mnt");
+        add(&path, MountRecord::new("nas", Some("home-1"), &mnt)).unwrap();
+        add(&path, MountRecord::new("nas", None, &mnt)).unwrap();
+        add(
+            &path,
+            MountRecord::new("usb", Some("etc-2"), &tmp.path().join("etc")),
+        )
+        .unwrap();
+
+        let records = load(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].label(), "all archives");
+
+        remove(&path, &mnt).unwrap();
+        remove(&path, Path::new("/not/mounted")).unwrap();
+        let records = load(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].repo, "usb");
+    }
+
+    #[test]
+    fn stale_finds_empty_dirs_and_vanished_records() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        fs::create_dir(root.join("empty")).unwrap();
+        fs::create_dir(root.join("live")).unwrap();
+        fs::create_dir(root.join("data")).unwrap();
+        fs::write(root.join("data").join("keep.txt"), "x").unwrap();
+        fs::write(root.join("notes.txt"), "x").unwrap();
+        let gone = root.join("elsewhere").join("gone");
+        let records = vec![MountRecord::new("nas", None, &gone)];
+        let table = format!(
+            "proc /proc proc rw 0 0\nborgfs {} fuse rw 0 0\n",
+            root.join("live").display()
+        );
+
+        let stale = stale_in(&table, &root, &records);
+        let paths: Vec<&Path> = stale.iter().map(|s| s.path.as_path()).collect();
+        assert_eq!(paths, [root.join("elsewhere/gone"), root.join("empty")]);
+        assert!(stale.iter().all(|s| s.kind == StaleKind::Leftover));
+    }
+
+    #[test]
+    fn mount_table_escapes_are_decoded() {
+        let paths = mounted_paths("borgfs /mnt/my\\040archive fuse rw 0 0\n");
+        assert!(paths.contains(Path::new("/mnt/my archive")));
+    }
+}
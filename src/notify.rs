@@ -0,0 +1,157 @@
+//! ntfy and webhook notifications about finished backups, prunes and checks.
+
+use chrono::Local;
+
+use crate::config::{NotificationConfig, NtfyConfig, WebhookConfig};
+use crate::http;
+use crate::template::{NOTIFY_VARS, Template};
+
+/// One finished operation.
+#[derive(Debug, Clone)]
+pub struct Event<'a> {
+    /// `backup`, `prune` or `check`
+    pub action: &'a str,
+    pub repo: &'a str,
+    /// Preset or archive the operation ran on
+    pub target: &'a str,
+    pub success: bool,
+    /// Summary on success, error chain on failure
+    pub message: String,
+}
+
+impl Event<'_> {
+    fn status(&self) -> &'static str {
+        if self.success { "succeeded" } else { "failed" }
+    }
+
+    fn title(&self) -> String {
+        format!(
+            "borg-tool {} of {} ({}) {}",
+            self.action,
+            self.target,
+            self.repo,
+            self.status()
+        )
+    }
+}
+
+/// Deliver `event` to every configured channel. Delivery problems must not turn a finished
+/// backup into a failed one, so they are only logged.
+pub fn send(cfg: &NotificationConfig, event: &Event) {
+    if cfg.is_empty() || (event.success && !cfg.on_success) {
+        return;
+    }
+    if let Some(ntfy) = &cfg.ntfy
+        && let Err(err) = send_ntfy(ntfy, event)
+    {
+        log::warn!("ntfy notification failed: {:#}", err);
+    }
+    for hook in &cfg.webhooks {
+        if let Err(err) = send_webhook(hook, event) {
+            log::warn!("webhook notification failed: {:#}", err);
+        }
+    }
+}
+
+fn send_ntfy(ntfy: &NtfyConfig, event: &Event) -> anyhow::Result<()> {
+    let url = format!("{}/{}", ntfy.server.trim_end_matches('/'), ntfy.topic);
+    let title = event.title();
+    let (priority, tags) = if event.success {
+        ("default", "white_check_mark")
+    } else {
+        ("high", "rotating_light")
+    };
+    http::post(
+        &url,
+        &[("Title", &title), ("Priority", priority), ("Tags", tags)],
+        event.message.as_bytes(),
+    )
+}
+
+fn send_webhook(hook: &WebhookConfig, event: &Event) -> anyhow::Result<()> {
+    let body = webhook_body(hook.body.as_deref(), event)?;
+    http::post(
+        &hook.url,
+        &[("Content-Type", "application/json")],
+        body.as_bytes(),
+    )
+}
+
+/// Render the webhook body; placeholder values are JSON-escaped so they can sit inside
+/// string literals of the template.
+fn webhook_body(template: Option<&str>, event: &Event) -> anyhow::Result<String> {
+    let host = crate::borg::short_hostname();
+    let Some(template) = template else {
+        return Ok(serde_json::json!({
+            "action": event.action,
+            "status": event.status(),
+            "repo": event.repo,
+            "target": event.target,
+            "hostname": host,
+            "message": event.message,
+        })
+        .to_string());
+    };
+    let escaped: Vec<(&str, String)> = [
+        ("action", event.action),
+        ("status", event.status()),
+        ("repo", event.repo),
+        ("target", event.target),
+        ("hostname", host.as_str()),
+        ("message", event.message.as_str()),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name, json_escape(value)))
+    .collect();
+    let template = Template::parse(template, NOTIFY_VARS)?;
+    Ok(template.render(Local::now(), |name| {
+        escaped
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }))
+}
+
+/// `value` as the inside of a JSON string literal.
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::from(value).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed_backup() -> Event<'static> {
+        Event {
+            action: "backup",
+            repo: "nas",
+            target: "home",
+            success: false,
+            message: "borg create failed: \"lock\" timeout".into(),
+        }
+    }
+
+    #[test]
+    fn webhook_template_escapes_values() {
+        let body = webhook_body(
+            Some(r#"{{"text": "{repo}/{target} {action} {status}: {message}"}}"#),
+            &failed_backup(),
+        )
+        .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            parsed["text"],
+            "nas/home backup failed: borg create failed: \"lock\" timeout"
+        );
+    }
+
+    #[test]
+    fn webhook_default_body_has_all_fields() {
+        let body = webhook_body(None, &failed_backup()).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed["status"], "failed");
+        assert_eq!(parsed["target"], "home");
+        assert!(parsed["hostname"].is_string());
+    }
+}
@@ -0,0 +1,88 @@
+//! Cache of successful SSH probes (`probe_cache_seconds`).
+//!
+//! Kept in `$XDG_STATE_HOME/borg-tool/probes.json` as repo location → time of the last
+//! successful probe, so commands against a repo that answered a minute ago skip the ssh
+//! round trip. Failures are not cached: the next run probes again and notices a fixed key
+//! or a host that came back.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+use crate::history::state_dir;
+
+/// Repo location → UTC time of its last successful probe, RFC 3339
+pub type ProbeCache = HashMap<String, String>;
+
+pub fn default_probes_path() -> PathBuf {
+    state_dir().join("probes.json")
+}
+
+/// The cache at `path`; a missing or unreadable file is an empty cache.
+pub fn load(path: &Path) -> ProbeCache {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Replace the file at `path` with `cache` (write to a temp file, then rename).
+pub fn save(path: &Path, cache: &ProbeCache) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Create {}", dir.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(cache)?)
+        .with_context(|| format!("Write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Replace {}", path.display()))
+}
+
+/// Whether `repo` answered a probe less than `ttl_secs` before `now`.
+pub fn is_fresh(cache: &ProbeCache, repo: &str, ttl_secs: u64, now: DateTime<Utc>) -> bool {
+    cache
+        .get(repo)
+        .and_then(|probed| DateTime::parse_from_rfc3339(probed).ok())
+        .is_some_and(|probed| {
+            let age = now.signed_duration_since(probed).num_seconds();
+            (0..ttl_secs as i64).contains(&age)
+        })
+}
+
+/// Note that `repo` answered a probe at `now`.
+pub fn record(cache: &mut ProbeCache, repo: &str, now: DateTime<Utc>) {
+    cache.insert(repo.to_string(), now.to_rfc3339());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresh_within_ttl_only() {
+        let now = Utc::now();
+        let mut cache = ProbeCache::new();
+        record(
+            &mut cache,
+            "ssh://nas/./repo",
+            now - chrono::Duration::seconds(60),
+        );
+        assert!(is_fresh(&cache, "ssh://nas/./repo", 300, now));
+        assert!(!is_fresh(&cache, "ssh://nas/./repo", 30, now));
+        assert!(!is_fresh(&cache, "ssh://nas/./repo", 0, now));
+        assert!(!is_fresh(&cache, "ssh://other/./repo", 300, now));
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("probes.json");
+        assert!(load(&path).is_empty());
+        let mut cache = ProbeCache::new();
+        record(&mut cache, "ssh://nas/./repo", Utc::now());
+        save(&path, &cache).unwrap();
+        assert_eq!(load(&path), cache);
+    }
+}
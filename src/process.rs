@@ -0,0 +1,598 @@
+//! Waiting for child processes on a worker thread so the user can cancel them.
+
+use std::cell::Cell;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+/// How long a cancelled child gets to exit after SIGTERM (borg releases its lock) before SIGKILL.
+const TERMINATE_GRACE: Duration = Duration::from_secs(5);
+
+/// How long an interrupted child gets after SIGINT (`borg create` writes a checkpoint archive)
+/// before it is terminated.
+const CHECKPOINT_GRACE: Duration = Duration::from_secs(60);
+
+/// Error returned when the user cancels a running child process.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cancelled by user")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<Cancelled>())
+}
+
+/// Error returned for children that stopped because borg-tool received SIGINT or SIGTERM.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+pub fn is_interrupted(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<Interrupted>())
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// PIDs of the children currently waited on, by any thread.
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Whether borg-tool was interrupted; no further children are started then.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Set while [`run_foreground`] waits; Ctrl+C then belongs to that child.
+static FOREGROUND: AtomicBool = AtomicBool::new(false);
+
+/// Run `cmd` attached to the terminal (a subshell, a file manager) and wait for it. Ctrl+C
+/// pressed meanwhile reaches borg-tool too, but is left to the child.
+pub fn run_foreground(cmd: &mut Command) -> std::io::Result<ExitStatus> {
+    FOREGROUND.store(true, Ordering::SeqCst);
+    let status = cmd.status();
+    FOREGROUND.store(false, Ordering::SeqCst);
+    status
+}
+
+/// Whether borg-tool runs as root (`id -u` is 0).
+pub(crate) fn running_as_root() -> bool {
+    Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "0")
+}
+
+#[cfg(feature = "cli")]
+fn running_children() -> Vec<u32> {
+    CHILDREN.lock().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// Keeps a child in [`CHILDREN`] while it is waited on.
+struct Registered(u32);
+
+impl Registered {
+    fn new(pid: u32) -> Self {
+        if let Ok(mut children) = CHILDREN.lock() {
+            children.push(pid);
+        }
+        Registered(pid)
+    }
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        if let Ok(mut children) = CHILDREN.lock() {
+            children.retain(|pid| *pid != self.0);
+        }
+    }
+}
+
+thread_local! {
+    static CANCELLABLE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `action` with Esc-to-cancel enabled for child processes waited on by this thread.
+pub fn cancellable<T>(action: impl FnOnce() -> T) -> T {
+    let previous = CANCELLABLE.replace(true);
+    let result = action();
+    CANCELLABLE.set(previous);
+    result
+}
+
+/// Whether [`wait`] on this thread would react to Esc.
+pub fn cancel_available() -> bool {
+    CANCELLABLE.get() && keys::supported()
+}
+
+/// Spawn `cmd` with output captured and stdin closed, as [`Command::output`] would.
+/// Fails once borg-tool has been [`interrupted`].
+pub fn spawn(cmd: &mut Command) -> std::io::Result<Child> {
+    spawn_with_stdin(cmd, Stdio::null())
+}
+
+/// [`spawn`] reading `stdin`, e.g. the output of a [`Producer`].
+pub fn spawn_with_stdin(cmd: &mut Command, stdin: Stdio) -> std::io::Result<Child> {
+    if interrupted() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Interrupted,
+            Interrupted,
+        ));
+    }
+    cmd.stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+}
+
+/// A child whose stdout feeds another one (`cmd | borg create -`). Its stderr is collected
+/// in the background so a chatty producer cannot block on a full pipe.
+pub struct Producer {
+    child: Child,
+    stderr: JoinHandle<Vec<u8>>,
+}
+
+impl Producer {
+    /// Start `cmd`; the returned [`Stdio`] is its stdout, for [`spawn_with_stdin`].
+    pub fn spawn(cmd: &mut Command) -> std::io::Result<(Self, Stdio)> {
+        let mut child = spawn(cmd)?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = read_in_background(child.stderr.take(), None);
+        Ok((Self { child, stderr }, Stdio::from(stdout)))
+    }
+
+    /// Wait for the producer once its consumer has finished (a consumer that quit early
+    /// leaves it with a broken pipe).
+    pub fn wait(mut self) -> std::io::Result<Output> {
+        let status = self.child.wait()?;
+        let stderr = self.stderr.join().unwrap_or_default();
+        Ok(Output {
+            status,
+            stdout: Vec::new(),
+            stderr,
+        })
+    }
+}
+
+/// Wait for `child` on a worker thread. Inside [`cancellable`], Esc terminates the child and
+/// yields [`Cancelled`].
+pub fn wait(child: Child) -> Result<Output> {
+    wait_with_progress(child, |_| {})
+}
+
+/// [`wait`], calling `on_progress` on this thread with every stderr line as it arrives.
+/// Lines ended by `\r` count too, which is how borg's `--progress` redraws its status.
+///
+/// After an interrupt the child gets [`CHECKPOINT_GRACE`] to stop on its own, and the wait
+/// yields [`Interrupted`].
+pub fn wait_with_progress(child: Child, on_progress: impl FnMut(&str)) -> Result<Output> {
+    wait_inner(child, on_progress, None)
+}
+
+/// [`wait_with_progress`], handing stdout to `on_line` line by line as the child writes it
+/// instead of collecting it; the returned `stdout` stays empty. The reader blocks while
+/// [`STREAM_BACKLOG`] lines are pending, so memory stays flat however much the child prints.
+/// An error from `on_line` terminates the child and is returned.
+pub fn wait_streaming(
+    child: Child,
+    on_progress: impl FnMut(&str),
+    mut on_line: impl FnMut(&str) -> Result<()>,
+) -> Result<Output> {
+    wait_inner(child, on_progress, Some(&mut on_line))
+}
+
+/// Stdout lines [`wait_streaming`] buffers between the reader thread and `on_line`.
+const STREAM_BACKLOG: usize = 16 * 1024;
+
+/// Receiver of streamed stdout lines; an error stops the child.
+pub type LineSink<'a> = &'a mut dyn FnMut(&str) -> Result<()>;
+
+enum StdoutReader {
+    Collect(JoinHandle<Vec<u8>>),
+    Stream(Receiver<String>),
+}
+
+fn wait_inner(
+    mut child: Child,
+    mut on_progress: impl FnMut(&str),
+    mut on_line: Option<LineSink<'_>>,
+) -> Result<Output> {
+    let pid = child.id();
+    let _registered = Registered::new(pid);
+    let stdout = if on_line.is_some() {
+        let (tx, rx) = mpsc::sync_channel(STREAM_BACKLOG);
+        stream_in_background(child.stdout.take(), tx);
+        StdoutReader::Stream(rx)
+    } else {
+        StdoutReader::Collect(read_in_background(child.stdout.take(), None))
+    };
+    let (line_tx, line_rx) = mpsc::channel();
+    let stderr = read_in_background(child.stderr.take(), Some(line_tx));
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(child.wait());
+    });
+
+    let listener = if cancel_available() {
+        Some(keys::Listener::start()?)
+    } else {
+        None
+    };
+    let mut interrupted_at = None;
+    let status = loop {
+        for line in line_rx.try_iter() {
+            on_progress(&line);
+        }
+        if let (StdoutReader::Stream(lines), Some(on_line)) = (&stdout, on_line.as_deref_mut())
+            && let Err(err) = lines.try_iter().try_for_each(|line| on_line(&line))
+        {
+            terminate(pid, &rx);
+            return Err(err);
+        }
+        match rx.recv_timeout(Duration::from_millis(20)) {
+            Ok(status) => break status?,
+            Err(RecvTimeoutError::Disconnected) => anyhow::bail!("Child process waiter exited"),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if interrupted() {
+            let since = *interrupted_at.get_or_insert_with(Instant::now);
+            if since.elapsed() > CHECKPOINT_GRACE {
+                terminate(pid, &rx);
+                return Err(Interrupted.into());
+            }
+        }
+        let Some(listener) = &listener else {
+            continue;
+        };
+        match listener.request(Duration::from_millis(80))? {
+            keys::Request::None => {}
+            keys::Request::Cancel => {
+                terminate(pid, &rx);
+                // readers are left behind: grandchildren (e.g. ssh) may keep the pipes open
+                return Err(Cancelled.into());
+            }
+            // raw mode swallowed the terminal's SIGINT, so deliver it ourselves
+            keys::Request::Interrupt => {
+                if !INTERRUPTED.swap(true, Ordering::SeqCst) {
+                    eprintln!("\r\n{}\r", INTERRUPT_NOTICE);
+                    signal(pid, "INT");
+                }
+            }
+        }
+    };
+    if interrupted() {
+        return Err(Interrupted.into());
+    }
+
+    let stdout = match stdout {
+        StdoutReader::Collect(reader) => reader.join().unwrap_or_default(),
+        StdoutReader::Stream(lines) => {
+            if let Some(on_line) = on_line {
+                lines.iter().try_for_each(|line| on_line(&line))?;
+            }
+            Vec::new()
+        }
+    };
+    let stderr = stderr.join().unwrap_or_default();
+    for line in line_rx.try_iter() {
+        on_progress(&line);
+    }
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Send each line of `pipe` (without the line break) until EOF or until the receiver is gone.
+fn stream_in_background<R: Read + Send + 'static>(pipe: Option<R>, lines: SyncSender<String>) {
+    thread::spawn(move || {
+        let Some(pipe) = pipe else {
+            return;
+        };
+        let mut reader = BufReader::new(pipe);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let line = String::from_utf8_lossy(&buf);
+            if lines
+                .send(line.trim_end_matches(['\n', '\r']).to_string())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+/// Read `pipe` to the end; with `lines`, also send each non-empty line (split on `\n` or `\r`).
+fn read_in_background<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    lines: Option<Sender<String>>,
+) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let Some(mut pipe) = pipe else {
+            return buf;
+        };
+        let mut chunk = [0u8; 4096];
+        let mut line_start = 0;
+        loop {
+            let n = match pipe.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            buf.extend_from_slice(&chunk[..n]);
+            let Some(tx) = &lines else {
+                continue;
+            };
+            while let Some(end) = buf[line_start..]
+                .iter()
+                .position(|b| *b == b'\n' || *b == b'\r')
+            {
+                let line = String::from_utf8_lossy(&buf[line_start..line_start + end]);
+                let line = line.trim();
+                if !line.is_empty() {
+                    let _ = tx.send(line.to_string());
+                }
+                line_start += end + 1;
+            }
+        }
+        buf
+    })
+}
+
+/// SIGTERM (borg releases its lock), SIGKILL if `pid` is still running after [`TERMINATE_GRACE`].
+fn terminate(pid: u32, exited: &mpsc::Receiver<std::io::Result<std::process::ExitStatus>>) {
+    signal(pid, "TERM");
+    if exited.recv_timeout(TERMINATE_GRACE).is_err() {
+        signal(pid, "KILL");
+        let _ = exited.recv();
+    }
+}
+
+const INTERRUPT_NOTICE: &str = "Interrupted: waiting for borg to write a checkpoint and release its lock (Ctrl+C again to abort)";
+
+/// Handle SIGINT and SIGTERM (and SIGHUP with `hangup`, for the interactive menu whose
+/// terminal may be closed) on a background thread: running children are asked to stop
+/// (their waits then yield [`Interrupted`]); with none running, or on a second signal, run
+/// `cleanup` and exit with status 130.
+#[cfg(feature = "cli")]
+pub fn install_interrupt_handler(cleanup: fn(), hangup: bool) -> Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    use std::io::IsTerminal;
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM])?;
+    // only when asked: registering would undo `nohup borg-tool backup …`
+    if hangup {
+        signals.add_signal(SIGHUP)?;
+    }
+    thread::spawn(move || {
+        for sig in signals.forever() {
+            if sig == SIGINT && FOREGROUND.load(Ordering::SeqCst) {
+                continue;
+            }
+            let children = running_children();
+            if children.is_empty() {
+                exit_interrupted(cleanup);
+            }
+            if INTERRUPTED.swap(true, Ordering::SeqCst) {
+                eprintln!("\nInterrupted again: stopping borg");
+                for pid in &children {
+                    signal(*pid, "TERM");
+                }
+                let deadline = Instant::now() + TERMINATE_GRACE;
+                while !running_children().is_empty() && Instant::now() < deadline {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                for pid in running_children() {
+                    signal(pid, "KILL");
+                }
+                exit_interrupted(cleanup);
+            }
+            eprintln!("\n{}", INTERRUPT_NOTICE);
+            // Ctrl+C in a terminal reaches the whole foreground process group, borg included
+            if sig != SIGINT || !std::io::stdin().is_terminal() {
+                for pid in &children {
+                    signal(*pid, "INT");
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Run `cleanup` and exit with the conventional status for SIGINT.
+pub fn exit_interrupted(cleanup: fn()) -> ! {
+    #[cfg(feature = "cli")]
+    let _ = crossterm::terminal::disable_raw_mode();
+    cleanup();
+    eprintln!("Interrupted, exiting");
+    std::process::exit(130);
+}
+
+fn signal(pid: u32, name: &str) {
+    let _ = Command::new("kill")
+        .args([&format!("-{}", name), &pid.to_string()])
+        .status();
+}
+
+#[cfg(feature = "cli")]
+mod keys {
+    use std::io::IsTerminal;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use crossterm::terminal;
+
+    pub fn supported() -> bool {
+        std::io::stdin().is_terminal()
+    }
+
+    pub enum Request {
+        None,
+        Cancel,
+        Interrupt,
+    }
+
+    /// Raw terminal mode for the duration of a wait, so single key presses are visible.
+    pub struct Listener;
+
+    impl Listener {
+        pub fn start() -> Result<Self> {
+            terminal::enable_raw_mode()?;
+            Ok(Listener)
+        }
+
+        /// Esc cancels; raw mode swallows SIGINT, so Ctrl+C arrives as a key press.
+        pub fn request(&self, timeout: Duration) -> Result<Request> {
+            if !event::poll(timeout)? {
+                return Ok(Request::None);
+            }
+            Ok(match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Esc => Request::Cancel,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        Request::Interrupt
+                    }
+                    _ => Request::None,
+                },
+                _ => Request::None,
+            })
+        }
+    }
+
+    impl Drop for Listener {
+        fn drop(&mut self) {
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+#[cfg(not(feature = "cli"))]
+mod keys {
+    use std::time::Duration;
+
+    use anyhow::Result;
+
+    pub fn supported() -> bool {
+        false
+    }
+
+    pub enum Request {
+        None,
+        #[allow(dead_code)]
+        Cancel,
+        #[allow(dead_code)]
+        Interrupt,
+    }
+
+    pub struct Listener;
+
+    impl Listener {
+        pub fn start() -> Result<Self> {
+            Ok(Listener)
+        }
+
+        pub fn request(&self, timeout: Duration) -> Result<Request> {
+            std::thread::sleep(timeout);
+            Ok(Request::None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_collects_output_and_status() {
+        let child =
+            spawn(Command::new("sh").args(["-c", "echo hi; echo err >&2; exit 3"])).unwrap();
+        let out = wait(child).unwrap();
+        assert_eq!(out.stdout, b"hi\n");
+        assert_eq!(out.stderr, b"err\n");
+        assert_eq!(out.status.code(), Some(3));
+    }
+
+    #[test]
+    fn wait_with_progress_reports_stderr_lines() {
+        let child =
+            spawn(Command::new("sh").args(["-c", "printf '10%%\\r20%%\\rdone\\n' >&2; echo out"]))
+                .unwrap();
+        let mut seen = Vec::new();
+        let out = wait_with_progress(child, |line| seen.push(line.to_string())).unwrap();
+        assert_eq!(seen, ["10%", "20%", "done"]);
+        assert_eq!(out.stderr, b"10%\r20%\rdone\n");
+        assert_eq!(out.stdout, b"out\n");
+    }
+
+    #[test]
+    fn wait_streaming_hands_over_stdout_lines() {
+        let child = spawn(Command::new("sh").args(["-c", "seq 1 50000; echo err >&2"])).unwrap();
+        let mut count = 0;
+        let mut last = String::new();
+        let out = wait_streaming(
+            child,
+            |_| {},
+            |line| {
+                count += 1;
+                last = line.to_string();
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!((count, last.as_str()), (50000, "50000"));
+        assert!(out.stdout.is_empty());
+        assert_eq!(out.stderr, b"err\n");
+    }
+
+    #[test]
+    fn wait_streaming_stops_the_child_on_a_line_error() {
+        let child = spawn(Command::new("sh").args(["-c", "echo bad; sleep 30"])).unwrap();
+        let started = Instant::now();
+        let err = wait_streaming(child, |_| {}, |line| anyhow::bail!("cannot parse {}", line))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "cannot parse bad");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn is_interrupted_looks_through_context() {
+        use anyhow::Context;
+        let err = Err::<(), _>(Interrupted).context("backup").unwrap_err();
+        assert!(is_interrupted(&err));
+        assert!(!is_cancelled(&err));
+    }
+
+    #[test]
+    fn is_cancelled_looks_through_context() {
+        use anyhow::Context;
+        let err = Err::<(), _>(Cancelled).context("listing").unwrap_err();
+        assert!(is_cancelled(&err));
+        assert!(!is_cancelled(&anyhow::anyhow!("other")));
+    }
+}
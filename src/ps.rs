@@ -0,0 +1,282 @@
+//! Find borg (and ssh) processes started by borg-tool, including ones left by earlier runs,
+//! and the processes that keep a mount busy.
+//!
+//! Every borg child gets [`SESSION_ENV`] in its environment; processes it spawns (ssh)
+//! inherit it, so `/proc/<pid>/environ` identifies them after the tool itself has exited.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::runlock;
+
+/// `<borg-tool pid>` of the run that started the process.
+pub const SESSION_ENV: &str = "BORG_TOOL_SESSION";
+/// Name of the repo the process works on.
+pub const SESSION_REPO_ENV: &str = "BORG_TOOL_SESSION_REPO";
+/// borg subcommand, e.g. `mount` or `check`.
+pub const SESSION_OP_ENV: &str = "BORG_TOOL_SESSION_OP";
+
+/// Kernel clock ticks per second used in `/proc/<pid>/stat` (USER_HZ, 100 on Linux).
+const CLOCK_TICKS: u64 = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolProcess {
+    pub pid: u32,
+    pub session: u32,
+    /// Whether the borg-tool run that started it is still alive.
+    pub session_alive: bool,
+    pub repo: String,
+    pub operation: String,
+    /// Executable name, e.g. `borg` or `ssh`.
+    pub command: String,
+    pub runtime: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Marker {
+    session: u32,
+    repo: String,
+    operation: String,
+}
+
+/// All processes carrying the borg-tool session marker (Linux only).
+pub fn list() -> Result<Vec<ToolProcess>> {
+    let proc_dir = Path::new("/proc");
+    if !proc_dir.join("self/environ").exists() {
+        anyhow::bail!("Listing processes needs /proc (Linux)");
+    }
+    let uptime = fs::read_to_string(proc_dir.join("uptime"))
+        .ok()
+        .and_then(|raw| raw.split_whitespace().next()?.parse::<f64>().ok());
+    let own_pid = std::process::id();
+
+    let mut found = Vec::new();
+    for entry in fs::read_dir(proc_dir).context("Read /proc")? {
+        let Some(pid) = entry
+            .ok()
+            .and_then(|e| e.file_name().to_str()?.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid == own_pid {
+            continue;
+        }
+        let dir = proc_dir.join(pid.to_string());
+        // other users' processes (or ones that just exited) are unreadable; skip them
+        let Some(marker) = fs::read(dir.join("environ"))
+            .ok()
+            .and_then(|env| parse_marker(&env))
+        else {
+            continue;
+        };
+        let command = fs::read_to_string(dir.join("comm"))
+            .map(|c| c.trim().to_string())
+            .unwrap_or_default();
+        let runtime = fs::read_to_string(dir.join("stat"))
+            .ok()
+            .and_then(|stat| start_ticks(&stat))
+            .zip(uptime)
+            .map(|(ticks, up)| {
+                Duration::from_secs_f64((up - ticks as f64 / CLOCK_TICKS as f64).max(0.0))
+            });
+        found.push(ToolProcess {
+            pid,
+            session: marker.session,
+            session_alive: proc_dir.join(marker.session.to_string()).exists(),
+            repo: marker.repo,
+            operation: marker.operation,
+            command,
+            runtime,
+        });
+    }
+    found.sort_by_key(|p| p.pid);
+    Ok(found)
+}
+
+/// Send SIGTERM to `pid`, but only if it is one of ours.
+pub fn terminate(pid: u32) -> Result<()> {
+    // a backup run itself stops its borg cleanly and undoes its snapshots and stopped containers
+    let backup_run = runlock::running_backups().iter().any(|h| h.pid == pid);
+    if !backup_run && !list()?.iter().any(|p| p.pid == pid) {
+        anyhow::bail!("PID {} is not a process started by borg-tool", pid);
+    }
+    let status = Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()
+        .context("Failed to invoke kill")?;
+    if !status.success() {
+        anyhow::bail!("kill -TERM {} failed with status {}", pid, status);
+    }
+    Ok(())
+}
+
+/// A process with its working directory, root or an open file below some path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathUser {
+    pub pid: u32,
+    pub command: String,
+}
+
+/// Processes using anything below `path`, e.g. the shell that keeps a mount busy. Reads
+/// `/proc` on Linux and asks `lsof` elsewhere; empty if neither works.
+pub fn using_path(path: &Path) -> Vec<PathUser> {
+    let proc_dir = Path::new("/proc");
+    if !proc_dir.join("self/fd").exists() {
+        return lsof_users(path);
+    }
+    let own_pid = std::process::id();
+    let Ok(entries) = fs::read_dir(proc_dir) else {
+        return Vec::new();
+    };
+    let mut users = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid == own_pid {
+            continue;
+        }
+        let dir = entry.path();
+        // readlink only, so a hanging FUSE mount is never touched
+        let mut links = vec![dir.join("cwd"), dir.join("root"), dir.join("exe")];
+        if let Ok(fds) = fs::read_dir(dir.join("fd")) {
+            links.extend(fds.flatten().map(|fd| fd.path()));
+        }
+        if links
+            .iter()
+            .any(|link| fs::read_link(link).is_ok_and(|target| target.starts_with(path)))
+        {
+            let command = fs::read_to_string(dir.join("comm"))
+                .map(|c| c.trim().to_string())
+                .unwrap_or_default();
+            users.push(PathUser { pid, command });
+        }
+    }
+    users.sort_by_key(|u| u.pid);
+    users
+}
+
+fn lsof_users(path: &Path) -> Vec<PathUser> {
+    match Command::new("lsof")
+        .arg("-Fpc")
+        .arg("--")
+        .arg(path)
+        .output()
+    {
+        Ok(output) => parse_lsof(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// `lsof -Fpc` output: a `p<pid>` line per process, followed by `c<command>`.
+fn parse_lsof(output: &str) -> Vec<PathUser> {
+    let mut users: Vec<PathUser> = Vec::new();
+    for line in output.lines() {
+        if let Some(pid) = line.strip_prefix('p').and_then(|p| p.parse().ok()) {
+            users.push(PathUser {
+                pid,
+                command: String::new(),
+            });
+        } else if let (Some(command), Some(user)) = (line.strip_prefix('c'), users.last_mut()) {
+            user.command = command.to_string();
+        }
+    }
+    users
+}
+
+fn parse_marker(environ: &[u8]) -> Option<Marker> {
+    let mut session = None;
+    let mut repo = String::new();
+    let mut operation = String::new();
+    for var in environ.split(|b| *b == 0) {
+        let var = String::from_utf8_lossy(var);
+        let Some((key, value)) = var.split_once('=') else {
+            continue;
+        };
+        match key {
+            SESSION_ENV => session = value.parse().ok(),
+            SESSION_REPO_ENV => repo = value.to_string(),
+            SESSION_OP_ENV => operation = value.to_string(),
+            _ => {}
+        }
+    }
+    Some(Marker {
+        session: session?,
+        repo,
+        operation,
+    })
+}
+
+/// Field 22 (`starttime`) of `/proc/<pid>/stat`; the comm field may contain spaces.
+fn start_ticks(stat: &str) -> Option<u64> {
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    after_comm.split_whitespace().nth(19)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_marker_reads_session_vars() {
+        let env = b"PATH=/bin\0BORG_TOOL_SESSION=4242\0BORG_TOOL_SESSION_REPO=nas\0BORG_TOOL_SESSION_OP=mount\0";
+        assert_eq!(
+            parse_marker(env),
+            Some(Marker {
+                session: 4242,
+                repo: "nas".into(),
+                operation: "mount".into(),
+            })
+        );
+        assert_eq!(parse_marker(b"PATH=/bin\0HOME=/root\0"), None);
+    }
+
+    #[test]
+    fn start_ticks_handles_spaces_in_comm() {
+        let stat = "123 (borg (worker) x) S 1 123 123 0 -1 4194560 100 0 0 0 5 3 0 0 20 0 1 0 98765 1000 200";
+        assert_eq!(start_ticks(stat), Some(98765));
+    }
+
+    #[test]
+    fn parse_lsof_pairs_pids_and_commands() {
+        assert_eq!(
+            parse_lsof("p812\ncbash\nfcwd\np990\ncless\n"),
+            [
+                PathUser {
+                    pid: 812,
+                    command: "bash".into()
+                },
+                PathUser {
+                    pid: 990,
+                    command: "less".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn using_path_finds_process_by_cwd() {
+        if !Path::new("/proc/self/fd").exists() {
+            return;
+        }
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().canonicalize().unwrap();
+        let mut child = Command::new("sleep")
+            .arg("5")
+            .current_dir(&dir)
+            .spawn()
+            .unwrap();
+        let users = using_path(&dir);
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(users.iter().any(|u| u.pid == child.id()));
+    }
+}
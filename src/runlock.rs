@@ -0,0 +1,194 @@
+//! Single-instance lock of backup runs.
+//!
+//! A backup holds an exclusive lock on `<state dir>/locks/backup-<repo>-<preset>.lock` while it
+//! runs, so a timer firing while the previous run of the same preset is still going (or a manual
+//! run next to it) fails right away instead of queueing behind borg's repository lock and
+//! creating a second archive. The lock belongs to the open file and ends with the process,
+//! however it ends; the file content only says who holds it, for errors and `borg-tool ps`.
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+
+use crate::history::state_dir;
+
+pub fn default_locks_dir() -> PathBuf {
+    state_dir().join("locks")
+}
+
+/// Where [`lock_backup`] takes its locks; unset (library use, tests) locks nothing.
+static LOCKS_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Lock the backups of this process in `dir` from now on.
+pub fn enable(dir: PathBuf) {
+    if let Ok(mut slot) = LOCKS_DIR.lock() {
+        *slot = Some(dir);
+    }
+}
+
+/// The run holding a backup lock, as recorded in its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    /// Local time the run took the lock, `YYYY-MM-DD HH:MM:SS`
+    pub started: String,
+    pub repo: String,
+    pub preset: String,
+}
+
+/// A held backup lock; dropping it releases the lock.
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+/// Lock the backup of `preset` in `repo` in the directory passed to [`enable`], failing with
+/// the holder when another run has it. `None` when locking is not enabled.
+pub fn lock_backup(repo: &str, preset: &str) -> Result<Option<RunLock>> {
+    let Some(dir) = LOCKS_DIR.lock().ok().and_then(|dir| dir.clone()) else {
+        return Ok(None);
+    };
+    lock_backup_in(&dir, repo, preset).map(Some)
+}
+
+fn lock_backup_in(dir: &Path, repo: &str, preset: &str) -> Result<RunLock> {
+    fs::create_dir_all(dir).with_context(|| format!("Create {}", dir.display()))?;
+    let path = dir.join(format!(
+        "backup-{}-{}.lock",
+        file_safe(repo),
+        file_safe(preset)
+    ));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Open {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => match read_holder(&mut file) {
+            Some(holder) => anyhow::bail!(
+                "Backup '{}' of repo '{}' is already running (PID {}, started {}); \
+                 `borg-tool ps --kill {}` stops it",
+                preset,
+                repo,
+                holder.pid,
+                holder.started,
+                holder.pid
+            ),
+            None => anyhow::bail!(
+                "Backup '{}' of repo '{}' is already running ({} is locked)",
+                preset,
+                repo,
+                path.display()
+            ),
+        },
+        Err(TryLockError::Error(err)) => {
+            return Err(err).with_context(|| format!("Lock {}", path.display()));
+        }
+    }
+    let holder = format!(
+        "{}\n{}\n{}\n{}\n",
+        std::process::id(),
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        repo,
+        preset
+    );
+    file.set_len(0)
+        .and_then(|()| file.write_all(holder.as_bytes()))
+        .with_context(|| format!("Write {}", path.display()))?;
+    Ok(RunLock { _file: file })
+}
+
+/// Backup runs holding a lock in the directory passed to [`enable`], for `borg-tool ps`.
+pub fn running_backups() -> Vec<Holder> {
+    match LOCKS_DIR.lock().ok().and_then(|dir| dir.clone()) {
+        Some(dir) => running_backups_in(&dir),
+        None => Vec::new(),
+    }
+}
+
+fn running_backups_in(dir: &Path) -> Vec<Holder> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut holders: Vec<Holder> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "lock"))
+        .filter_map(|entry| {
+            let mut file = File::open(entry.path()).ok()?;
+            // getting the lock means nobody holds it; it is released with the file
+            match file.try_lock() {
+                Err(TryLockError::WouldBlock) => read_holder(&mut file),
+                _ => None,
+            }
+        })
+        .collect();
+    holders.sort_by(|a, b| a.started.cmp(&b.started));
+    holders
+}
+
+fn read_holder(file: &mut File) -> Option<Holder> {
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    let mut lines = content.lines();
+    Some(Holder {
+        pid: lines.next()?.parse().ok()?,
+        started: lines.next()?.to_string(),
+        repo: lines.next()?.to_string(),
+        preset: lines.next()?.to_string(),
+    })
+}
+
+/// `name` usable in a file name: anything but ASCII letters, digits, `.`, `_` and `-` becomes `_`.
+fn file_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_run_of_a_preset_fails_until_the_first_ends() {
+        let tmp = tempfile::tempdir().unwrap();
+        let first = lock_backup_in(tmp.path(), "nas box", "home").unwrap();
+
+        let err = lock_backup_in(tmp.path(), "nas box", "home")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("already running"), "{err}");
+        assert!(
+            err.contains(&format!("PID {}", std::process::id())),
+            "{err}"
+        );
+        // other presets are not held up
+        let other = lock_backup_in(tmp.path(), "nas box", "etc").unwrap();
+
+        let running = running_backups_in(tmp.path());
+        assert_eq!(running.len(), 2);
+        assert!(
+            running
+                .iter()
+                .any(|h| h.repo == "nas box" && h.preset == "home")
+        );
+
+        drop(first);
+        drop(other);
+        assert!(running_backups_in(tmp.path()).is_empty());
+        lock_backup_in(tmp.path(), "nas box", "home").unwrap();
+    }
+}
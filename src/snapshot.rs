@@ -0,0 +1,355 @@
+//! File system snapshots of backup presets (`[repos.backups.snapshot]`).
+//!
+//! Before `borg create` a read-only btrfs, LVM or ZFS snapshot of the preset's file system is
+//! taken and mounted below `<temp dir>/borg-tool-snapshots/<preset>` at the path of the
+//! original, e.g. `/home` at `<base>/home`. Includes below the mountpoint are rewritten to
+//! `<base>/./home/...`, which borg (1.2 and later) archives as `home/...`, so the archive
+//! looks the same as one made without a snapshot.
+//!
+//! Snapshots are named after the preset; one left by a run that was killed is removed before
+//! the next is taken.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+
+use crate::config::{BackupConfig, SnapshotConfig, SnapshotKind};
+use crate::mounts;
+
+/// Default `lvcreate --size` of LVM snapshots.
+const DEFAULT_LVM_SIZE: &str = "1G";
+
+/// Check `config` of `preset` without touching the system.
+pub fn validate(config: &SnapshotConfig, preset: &BackupConfig) -> Result<()> {
+    if !config.mountpoint.is_absolute() {
+        anyhow::bail!(
+            "mountpoint {} must be an absolute path",
+            config.mountpoint.display()
+        );
+    }
+    match (config.kind, &config.source) {
+        (SnapshotKind::Lvm, None) => anyhow::bail!("LVM snapshots need a source like \"vg0/home\""),
+        (SnapshotKind::Lvm, Some(source)) if !source.contains('/') => {
+            anyhow::bail!("source '{}' is not of the form \"vg/lv\"", source)
+        }
+        (SnapshotKind::Zfs, None) => {
+            anyhow::bail!("ZFS snapshots need a source like \"tank/home\"")
+        }
+        _ => {}
+    }
+    if preset.stdin_command.is_some() || preset.database.is_some() {
+        anyhow::bail!("snapshots apply to includes, not to stdin_command or database");
+    }
+    if !preset
+        .includes
+        .iter()
+        .any(|include| Path::new(include).starts_with(&config.mountpoint))
+    {
+        anyhow::bail!(
+            "no include is below the mountpoint {}",
+            config.mountpoint.display()
+        );
+    }
+    Ok(())
+}
+
+/// Run `program` with `args`, failing on a non-zero exit.
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to invoke {} (is it installed?)", program))?;
+    log::debug!(
+        "{} {}: exit code {}",
+        program,
+        args.join(" "),
+        output.status.code().map_or_else(
+            || "none (killed by a signal)".to_string(),
+            |c| c.to_string()
+        )
+    );
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "{} failed with status {}: {}",
+            program,
+            output.status,
+            stderr.trim()
+        );
+    }
+    Ok(())
+}
+
+/// A mounted snapshot; [`Snapshot::destroy`] unmounts and removes it.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    kind: SnapshotKind,
+    mountpoint: PathBuf,
+    /// Directory the snapshot is mounted below, at the path of `mountpoint`
+    base: PathBuf,
+    /// LVM volume ("vg/lv"), ZFS snapshot ("pool/fs@name") or btrfs subvolume path
+    created: String,
+    mounted: bool,
+}
+
+/// Snapshots taken and not destroyed yet, for [`destroy_active`] on an interrupt.
+static ACTIVE: Mutex<Vec<Snapshot>> = Mutex::new(Vec::new());
+
+/// Record `snapshot` in [`ACTIVE`], replacing an earlier state of it.
+fn register(snapshot: &Snapshot) {
+    if let Ok(mut active) = ACTIVE.lock() {
+        active.retain(|s| s.created != snapshot.created);
+        active.push(snapshot.clone());
+    }
+}
+
+/// Unmount and remove the snapshots of backups that were interrupted, logging failures;
+/// for the exit path of an interrupted run.
+pub fn destroy_active() {
+    let active = ACTIVE
+        .lock()
+        .map(|mut a| std::mem::take(&mut *a))
+        .unwrap_or_default();
+    for snapshot in active {
+        if let Err(err) = snapshot.destroy() {
+            log::error!("{:#}", err);
+        }
+    }
+}
+
+/// The snapshot `config` of the preset `preset` names, not taken yet; its [`Snapshot::rewrite`]
+/// gives the paths a backup will read.
+pub fn planned(config: &SnapshotConfig, preset: &str) -> Snapshot {
+    let source = config.source.as_deref().unwrap_or_default();
+    let created = match config.kind {
+        SnapshotKind::Btrfs => config
+            .mountpoint
+            .join(format!(".borg-tool-snapshot-{}", preset))
+            .to_string_lossy()
+            .into_owned(),
+        SnapshotKind::Lvm => {
+            let (vg, lv) = source.split_once('/').unwrap_or(("", source));
+            format!("{}/{}-borg-tool-{}", vg, lv, preset)
+        }
+        SnapshotKind::Zfs => format!("{}@borg-tool-{}", source, preset),
+    };
+    Snapshot {
+        kind: config.kind,
+        mountpoint: config.mountpoint.clone(),
+        base: env::temp_dir().join("borg-tool-snapshots").join(preset),
+        created,
+        mounted: false,
+    }
+}
+
+/// Take a snapshot as configured for the preset `preset` and mount it.
+pub fn create(config: &SnapshotConfig, preset: &str) -> Result<Snapshot> {
+    let mut snapshot = planned(config, preset);
+    if snapshot.exists() {
+        log::warn!(
+            "Removing snapshot {} left by an earlier run",
+            snapshot.created
+        );
+        let mut leftover = snapshot.clone();
+        leftover.mounted = mounts::is_mounted(&leftover.target());
+        leftover.destroy()?;
+    }
+    let mountpoint = config.mountpoint.to_string_lossy();
+    match config.kind {
+        SnapshotKind::Btrfs => run(
+            "btrfs",
+            &[
+                "subvolume",
+                "snapshot",
+                "-r",
+                &mountpoint,
+                &snapshot.created,
+            ],
+        )?,
+        SnapshotKind::Lvm => {
+            let name = snapshot.created.rsplit('/').next().unwrap_or_default();
+            run(
+                "lvcreate",
+                &[
+                    "--snapshot",
+                    "--permission",
+                    "r",
+                    "--size",
+                    config.lvm_size.as_deref().unwrap_or(DEFAULT_LVM_SIZE),
+                    "--name",
+                    name,
+                    config.source.as_deref().unwrap_or_default(),
+                ],
+            )?
+        }
+        SnapshotKind::Zfs => run("zfs", &["snapshot", &snapshot.created])?,
+    }
+    register(&snapshot);
+    let mounted = snapshot.mount(config.mount_options.as_deref());
+    register(&snapshot);
+    if let Err(err) = mounted {
+        if let Err(cleanup) = snapshot.destroy() {
+            log::error!("{:#}", cleanup);
+        }
+        return Err(err);
+    }
+    Ok(snapshot)
+}
+
+impl Snapshot {
+    /// Whether the snapshot exists, e.g. left by a run that was killed.
+    fn exists(&self) -> bool {
+        match self.kind {
+            SnapshotKind::Btrfs => Path::new(&self.created).exists(),
+            SnapshotKind::Lvm => run("lvs", &[&self.created]).is_ok(),
+            SnapshotKind::Zfs => run("zfs", &["list", "-t", "snapshot", &self.created]).is_ok(),
+        }
+    }
+
+    /// Where the snapshot shows up: `mountpoint` below `base`.
+    fn target(&self) -> PathBuf {
+        let relative = self
+            .mountpoint
+            .strip_prefix("/")
+            .unwrap_or(&self.mountpoint);
+        self.base.join(relative)
+    }
+
+    fn mount(&mut self, options: Option<&str>) -> Result<()> {
+        let target = self.target();
+        fs::create_dir_all(&target).with_context(|| format!("Create {}", target.display()))?;
+        let target = target.to_string_lossy();
+        let mut opts = match self.kind {
+            // the btrfs snapshot is read-only already
+            SnapshotKind::Btrfs => "bind".to_string(),
+            SnapshotKind::Lvm | SnapshotKind::Zfs => "ro".to_string(),
+        };
+        if let Some(extra) = options {
+            opts = format!("{},{}", opts, extra);
+        }
+        match self.kind {
+            SnapshotKind::Btrfs => run("mount", &["-o", &opts, &self.created, &target])?,
+            SnapshotKind::Lvm => {
+                let device = format!("/dev/{}", self.created);
+                run("mount", &["-o", &opts, &device, &target])?
+            }
+            SnapshotKind::Zfs => run("mount", &["-t", "zfs", "-o", &opts, &self.created, &target])?,
+        }
+        self.mounted = true;
+        Ok(())
+    }
+
+    /// `include` as read from the snapshot; paths outside the mountpoint stay as they are.
+    pub fn rewrite(&self, include: &str) -> String {
+        if Path::new(include).starts_with(&self.mountpoint) {
+            // borg strips everything up to "/./" from the archived paths
+            format!("{}/.{}", self.base.display(), include)
+        } else {
+            include.to_string()
+        }
+    }
+
+    /// Unmount and remove the snapshot.
+    pub fn destroy(self) -> Result<()> {
+        if let Ok(mut active) = ACTIVE.lock() {
+            active.retain(|s| s.created != self.created);
+        }
+        let target = self.target();
+        if self.mounted {
+            run("umount", &[&target.to_string_lossy()])
+                .with_context(|| format!("Snapshot {} is still mounted", self.created))?;
+        }
+        match self.kind {
+            SnapshotKind::Btrfs => run("btrfs", &["subvolume", "delete", &self.created]),
+            SnapshotKind::Lvm => run("lvremove", &["--force", &self.created]),
+            SnapshotKind::Zfs => run("zfs", &["destroy", &self.created]),
+        }
+        .with_context(|| format!("Snapshot {} was not removed", self.created))?;
+        // only empty directories go, in case anything is still mounted there
+        for dir in target
+            .ancestors()
+            .take_while(|dir| dir.starts_with(&self.base))
+        {
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_below_the_mountpoint_read_from_the_snapshot() {
+        let snapshot = Snapshot {
+            kind: SnapshotKind::Lvm,
+            mountpoint: PathBuf::from("/home"),
+            base: PathBuf::from("/tmp/borg-tool-snapshots/home"),
+            created: "vg0/home-borg-tool-home".into(),
+            mounted: false,
+        };
+        assert_eq!(
+            snapshot.target(),
+            Path::new("/tmp/borg-tool-snapshots/home/home")
+        );
+        assert_eq!(
+            snapshot.rewrite("/home/alice"),
+            "/tmp/borg-tool-snapshots/home/./home/alice"
+        );
+        assert_eq!(snapshot.rewrite("/homework"), "/homework");
+        assert_eq!(snapshot.rewrite("/etc"), "/etc");
+    }
+
+    #[test]
+    fn snapshots_are_named_after_the_preset() {
+        let mut config = SnapshotConfig {
+            kind: SnapshotKind::Lvm,
+            mountpoint: PathBuf::from("/home"),
+            source: Some("vg0/home".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            planned(&config, "daily").created,
+            "vg0/home-borg-tool-daily"
+        );
+        config.kind = SnapshotKind::Zfs;
+        config.source = Some("tank/home".into());
+        assert_eq!(
+            planned(&config, "daily").created,
+            "tank/home@borg-tool-daily"
+        );
+        config.kind = SnapshotKind::Btrfs;
+        assert_eq!(
+            planned(&config, "daily").created,
+            "/home/.borg-tool-snapshot-daily"
+        );
+    }
+
+    #[test]
+    fn validate_requires_a_source_and_a_covered_include() {
+        let preset = BackupConfig {
+            name: "home".into(),
+            includes: vec!["/home".into(), "/etc".into()],
+            ..Default::default()
+        };
+        let mut config = SnapshotConfig {
+            kind: SnapshotKind::Zfs,
+            mountpoint: PathBuf::from("/home"),
+            ..Default::default()
+        };
+        assert!(validate(&config, &preset).is_err());
+        config.source = Some("tank/home".into());
+        validate(&config, &preset).unwrap();
+        config.mountpoint = PathBuf::from("/srv");
+        let err = validate(&config, &preset).unwrap_err().to_string();
+        assert!(err.contains("no include"), "{err}");
+    }
+}
@@ -0,0 +1,349 @@
+//! Backup freshness per repo and preset (`status` subcommand).
+
+use chrono::{Duration, Local, NaiveDateTime};
+use serde::Serialize;
+
+use crate::borg::{BorgArchive, archive_name_prefix};
+use crate::config::{BackupConfig, RepoCtx};
+use crate::filter::{parse_age, parse_archive_time};
+use crate::history::HistoryEntry;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PresetStatus {
+    pub repo: String,
+    pub preset: String,
+    /// Newest archive created by the preset
+    pub newest: Option<String>,
+    pub newest_time: Option<String>,
+    /// Age of the newest archive in seconds
+    pub age_secs: Option<i64>,
+    pub max_age: Option<String>,
+    /// Older than `max_age`, or no archive at all while `max_age` is set
+    pub stale: bool,
+    /// Why the repo or preset could not be checked
+    pub error: Option<String>,
+}
+
+impl PresetStatus {
+    /// Needs attention: stale or not checkable.
+    pub fn is_problem(&self) -> bool {
+        self.stale || self.error.is_some()
+    }
+}
+
+/// Statuses of all presets of `repo` given its archive list, relative to `now`.
+pub fn preset_statuses(
+    repo: &RepoCtx,
+    archives: &[BorgArchive],
+    now: NaiveDateTime,
+) -> Vec<PresetStatus> {
+    repo.backups
+        .iter()
+        .map(|preset| preset_status(repo, preset, archives, now))
+        .collect()
+}
+
+/// Every preset of `repo` marked with `error`, e.g. when the repo could not be listed.
+pub fn unavailable(repo: &RepoCtx, error: &str) -> Vec<PresetStatus> {
+    repo.backups
+        .iter()
+        .map(|preset| PresetStatus {
+            error: Some(error.to_string()),
+            ..empty(repo, preset)
+        })
+        .collect()
+}
+
+fn empty(repo: &RepoCtx, preset: &BackupConfig) -> PresetStatus {
+    PresetStatus {
+        repo: repo.name.clone(),
+        preset: preset.name.clone(),
+        newest: None,
+        newest_time: None,
+        age_secs: None,
+        max_age: preset.max_age.clone().or_else(|| repo.max_age.clone()),
+        stale: false,
+        error: None,
+    }
+}
+
+fn preset_status(
+    repo: &RepoCtx,
+    preset: &BackupConfig,
+    archives: &[BorgArchive],
+    now: NaiveDateTime,
+) -> PresetStatus {
+    let mut status = empty(repo, preset);
+    let prefix = match archive_name_prefix(preset, &repo.name) {
+        Ok(prefix) => prefix,
+        Err(err) => {
+            status.error = Some(format!("{:#}", err));
+            return status;
+        }
+    };
+    let newest = archives
+        .iter()
+        .filter(|a| a.name.starts_with(&prefix))
+        .filter_map(|a| Some((a, a.time_utc.as_deref().and_then(parse_archive_time)?)))
+        .max_by_key(|(_, time)| *time);
+    let age = newest.map(|(_, time)| now - time);
+    if let Some((archive, time)) = newest {
+        status.newest = Some(archive.name.clone());
+        status.newest_time = Some(time.format("%Y-%m-%d %H:%M").to_string());
+    }
+    status.age_secs = age.map(|a| a.num_seconds());
+
+    if let Some(raw) = &status.max_age {
+        match parse_age(raw) {
+            Ok(max_age) => status.stale = age.is_none_or(|age| age > max_age),
+            Err(err) => status.error = Some(format!("{:#}", err)),
+        }
+    }
+    status
+}
+
+/// `preset_statuses` relative to the current local time.
+pub fn preset_statuses_now(repo: &RepoCtx, archives: &[BorgArchive]) -> Vec<PresetStatus> {
+    preset_statuses(repo, archives, Local::now().naive_local())
+}
+
+/// Nagios/Icinga plugin states, ordered by severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Health {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl Health {
+    /// Exit status of the plugin for this state.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Health::Ok => 0,
+            Health::Warning => 1,
+            Health::Critical => 2,
+            Health::Unknown => 3,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Health::Ok => "OK",
+            Health::Warning => "WARNING",
+            Health::Critical => "CRITICAL",
+            Health::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// Age thresholds for `check-health`; unset ones fall back to each preset's `max_age`
+/// (warning) and twice the warning age (critical).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HealthThresholds {
+    pub warning: Option<Duration>,
+    pub critical: Option<Duration>,
+}
+
+/// Overall state plus the one-line plugin output (with age perfdata) for `statuses`.
+/// `history` supplies the outcome of the last recorded backup of each preset.
+pub fn evaluate_health(
+    statuses: &[PresetStatus],
+    history: &[HistoryEntry],
+    thresholds: HealthThresholds,
+) -> (Health, String) {
+    if statuses.is_empty() {
+        return (
+            Health::Unknown,
+            "BORG UNKNOWN - no backup presets configured".to_string(),
+        );
+    }
+    let mut worst = Health::Ok;
+    let mut problems = Vec::new();
+    let mut perfdata = Vec::new();
+    for status in statuses {
+        let name = format!("{}/{}", status.repo, status.preset);
+        let warning = thresholds.warning.or_else(|| {
+            status
+                .max_age
+                .as_deref()
+                .and_then(|raw| parse_age(raw).ok())
+        });
+        let critical = thresholds.critical.or(warning.map(|w| w * 2));
+        let age = status.age_secs.map(Duration::seconds);
+
+        let (health, problem) = if let Some(err) = &status.error {
+            let first = err.lines().next().unwrap_or("");
+            (Health::Critical, format!("{}: {}", name, first))
+        } else if age.is_none() {
+            (Health::Critical, format!("{}: no archive", name))
+        } else if let Some(age) = age.filter(|a| critical.is_some_and(|c| *a > c)) {
+            (
+                Health::Critical,
+                format!("{}: {} old", name, short_age(age)),
+            )
+        } else if let Some(age) = age.filter(|a| warning.is_some_and(|w| *a > w)) {
+            (Health::Warning, format!("{}: {} old", name, short_age(age)))
+        } else if last_backup_failed(history, status) {
+            (Health::Warning, format!("{}: last backup failed", name))
+        } else {
+            (Health::Ok, String::new())
+        };
+        worst = worst.max(health);
+        if health != Health::Ok {
+            problems.push(problem);
+        }
+        if let Some(age) = age {
+            perfdata.push(format!(
+                "'{}'={}s;{};{}",
+                name,
+                age.num_seconds(),
+                warning
+                    .map(|w| w.num_seconds().to_string())
+                    .unwrap_or_default(),
+                critical
+                    .map(|c| c.num_seconds().to_string())
+                    .unwrap_or_default()
+            ));
+        }
+    }
+
+    let summary = if problems.is_empty() {
+        format!("{} preset(s) fresh", statuses.len())
+    } else {
+        problems.join(", ")
+    };
+    let mut line = format!("BORG {} - {}", worst.label(), summary);
+    if !perfdata.is_empty() {
+        line.push_str(" | ");
+        line.push_str(&perfdata.join(" "));
+    }
+    (worst, line)
+}
+
+fn last_backup_failed(history: &[HistoryEntry], status: &PresetStatus) -> bool {
+    history
+        .iter()
+        .rev()
+        .find(|e| e.action == "backup" && e.repo == status.repo && e.target == status.preset)
+        .is_some_and(|e| !e.success)
+}
+
+/// `3d`, `30h` or `12m` (days from two days on).
+fn short_age(age: Duration) -> String {
+    match age.num_seconds() {
+        s if s >= 2 * 86400 => format!("{}d", s / 86400),
+        s if s >= 3600 => format!("{}h", s / 3600),
+        s => format!("{}m", s / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(name: &str, time: &str) -> BorgArchive {
+        BorgArchive {
+            name: name.into(),
+            time_utc: Some(time.into()),
+            ..Default::default()
+        }
+    }
+
+    fn at(raw: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn newest_matching_archive_decides_staleness() {
+        let repo = RepoCtx {
+            name: "nas".into(),
+            max_age: Some("36h".into()),
+            backups: vec![
+                BackupConfig {
+                    name: "home".into(),
+                    ..Default::default()
+                },
+                BackupConfig {
+                    name: "etc".into(),
+                    max_age: Some("1w".into()),
+                    ..Default::default()
+                },
+                BackupConfig {
+                    name: "db".into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let archives = [
+            archive("nas-home-2025-03-01_03-00-00", "2025-03-01T03:00:00.000000"),
+            archive("nas-home-2025-03-08_03-00-00", "2025-03-08T03:00:00.000000"),
+            archive("nas-etc-2025-03-05_03-00-00", "2025-03-05T03:00:00.000000"),
+            archive("other-2025-03-10_03-00-00", "2025-03-10T03:00:00.000000"),
+        ];
+
+        let statuses = preset_statuses(&repo, &archives, at("2025-03-10T12:00:00"));
+        let home = &statuses[0];
+        assert_eq!(home.newest.as_deref(), Some("nas-home-2025-03-08_03-00-00"));
+        assert_eq!(home.age_secs, Some(57 * 3600));
+        assert!(home.stale);
+
+        let etc = &statuses[1];
+        assert_eq!(etc.max_age.as_deref(), Some("1w"));
+        assert!(!etc.stale);
+
+        // never backed up at all
+        let db = &statuses[2];
+        assert_eq!((db.newest.clone(), db.stale), (None, true));
+    }
+
+    fn fresh(preset: &str, age_secs: i64) -> PresetStatus {
+        PresetStatus {
+            repo: "nas".into(),
+            preset: preset.into(),
+            newest: Some(format!("nas-{}", preset)),
+            newest_time: None,
+            age_secs: Some(age_secs),
+            max_age: Some("1d".into()),
+            stale: false,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn health_uses_max_age_and_history() {
+        let none = HealthThresholds::default();
+        let (health, line) = evaluate_health(&[fresh("home", 3600)], &[], none);
+        assert_eq!(health, Health::Ok);
+        assert_eq!(
+            line,
+            "BORG OK - 1 preset(s) fresh | 'nas/home'=3600s;86400;172800"
+        );
+
+        // warning at max_age, critical at twice that
+        let statuses = [fresh("home", 90_000), fresh("etc", 200_000)];
+        let (health, line) = evaluate_health(&statuses, &[], none);
+        assert_eq!(health, Health::Critical);
+        assert!(line.starts_with("BORG CRITICAL - nas/home: 25h old, nas/etc: 2d old |"));
+
+        let failed = HistoryEntry {
+            started: String::new(),
+            action: "backup".into(),
+            repo: "nas".into(),
+            target: "home".into(),
+            archive: None,
+            duration_secs: 0.0,
+            success: false,
+            message: String::new(),
+            stats: None,
+        };
+        let strict = HealthThresholds {
+            warning: Some(Duration::hours(2)),
+            critical: None,
+        };
+        let (health, line) = evaluate_health(&[fresh("home", 60)], &[failed], strict);
+        assert_eq!(health, Health::Warning);
+        assert!(line.contains("nas/home: last backup failed"));
+    }
+}
@@ -0,0 +1,185 @@
+//! systemd service and timer units running a backup preset (`generate systemd`).
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::config::RepoCtx;
+
+/// A `.service`/`.timer` pair for one repo/preset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Units {
+    /// Without the suffix, e.g. `borg-tool-nas-home`
+    pub name: String,
+    pub service: String,
+    pub timer: String,
+}
+
+/// Units running `<exe> --config <config> --non-interactive --plain --repo <repo> backup
+/// <preset>` on `on_calendar` (a systemd calendar expression such as `daily`).
+pub fn render_units(
+    exe: &Path,
+    config: &Path,
+    repo: &RepoCtx,
+    preset: &str,
+    on_calendar: &str,
+) -> Units {
+    let name = format!("borg-tool-{}-{}", unit_safe(&repo.name), unit_safe(preset));
+    let exec = [
+        exe.display().to_string(),
+        "--config".to_string(),
+        config.display().to_string(),
+        "--non-interactive".to_string(),
+        "--plain".to_string(),
+        "--repo".to_string(),
+        repo.name.clone(),
+        "backup".to_string(),
+        preset.to_string(),
+    ]
+    .iter()
+    .map(|arg| exec_quote(arg))
+    .collect::<Vec<_>>()
+    .join(" ");
+
+    let credentials = if repo.passcommand.is_some() || repo.passphrase_file.is_some() {
+        "# The passphrase comes from passcommand/passphrase_file in the config.\n".to_string()
+    } else {
+        format!(
+            "# borg-tool never prompts here: set passcommand or passphrase_file for repo '{name}'\n\
+             # in the config, or hand the passphrase over with systemd credentials:\n\
+             #LoadCredential=borg-passphrase:/etc/borg-tool/{name}.pass\n\
+             #Environment=BORG_PASSCOMMAND=\"cat %d/borg-passphrase\"\n",
+            name = repo.name
+        )
+    };
+
+    let service = format!(
+        "[Unit]\n\
+         Description=borg-tool backup '{preset}' to {repo}\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={exec}\n\
+         {credentials}\
+         Nice=10\n\
+         IOSchedulingClass=idle\n",
+        preset = preset,
+        repo = repo.name,
+        exec = exec,
+        credentials = credentials,
+    );
+    let timer = format!(
+        "[Unit]\n\
+         Description=Run borg-tool backup '{preset}' to {repo} ({calendar})\n\
+         \n\
+         [Timer]\n\
+         OnCalendar={calendar}\n\
+         RandomizedDelaySec=15m\n\
+         Persistent=true\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        preset = preset,
+        repo = repo.name,
+        calendar = on_calendar,
+    );
+    Units {
+        name,
+        service,
+        timer,
+    }
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user` (or `~/.config/systemd/user`).
+pub fn user_unit_dir() -> Result<PathBuf> {
+    let config_home = match env::var("XDG_CONFIG_HOME") {
+        Ok(xdg) => PathBuf::from(xdg),
+        Err(_) => PathBuf::from(env::var("HOME").context("HOME is not set")?).join(".config"),
+    };
+    Ok(config_home.join("systemd").join("user"))
+}
+
+/// Write both units into `dir`; returns the paths written.
+pub fn install(units: &Units, dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir).with_context(|| format!("Create {}", dir.display()))?;
+    let mut written = Vec::new();
+    for (suffix, content) in [("service", &units.service), ("timer", &units.timer)] {
+        let path = dir.join(format!("{}.{}", units.name, suffix));
+        fs::write(&path, content).with_context(|| format!("Write {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Keep unit names to characters systemd accepts without escaping.
+fn unit_safe(raw: &str) -> String {
+    raw.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Quote an ExecStart argument: `%` is a specifier and whitespace splits arguments.
+fn exec_quote(raw: &str) -> String {
+    let escaped = raw.replace('%', "%%");
+    if escaped.is_empty() || escaped.contains([' ', '\t', '"', '\'', '\\', ';']) {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units_run_preset_without_prompts() {
+        let repo = RepoCtx {
+            name: "nas box".into(),
+            ..Default::default()
+        };
+        let units = render_units(
+            Path::new("/usr/local/bin/borg-tool"),
+            Path::new("/home/u/.config/borg-tool/config.toml"),
+            &repo,
+            "home",
+            "*-*-* 02:30",
+        );
+        assert_eq!(units.name, "borg-tool-nas_box-home");
+        assert!(units.service.contains(
+            "ExecStart=/usr/local/bin/borg-tool --config /home/u/.config/borg-tool/config.toml --non-interactive --plain --repo \"nas box\" backup home\n"
+        ));
+        assert!(units.service.contains("#LoadCredential=borg-passphrase:"));
+        assert!(units.timer.contains("OnCalendar=*-*-* 02:30\n"));
+        assert!(units.timer.contains("WantedBy=timers.target"));
+
+        let repo = RepoCtx {
+            passcommand: Some("pass show borg".into()),
+            ..repo
+        };
+        let units = render_units(
+            Path::new("borg-tool"),
+            Path::new("c.toml"),
+            &repo,
+            "home",
+            "daily",
+        );
+        assert!(!units.service.contains("LoadCredential"));
+    }
+
+    #[test]
+    fn exec_quote_escapes_specifiers() {
+        assert_eq!(exec_quote("50%"), "50%%");
+        assert_eq!(exec_quote("a \"b\""), "\"a \\\"b\\\"\"");
+    }
+}
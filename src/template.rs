@@ -0,0 +1,232 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, format::StrftimeItems};
+
+/// Placeholders accepted in archive name templates (besides `{now:<fmt>}`).
+pub const ARCHIVE_NAME_VARS: &[&str] = &["hostname", "user", "preset", "repo"];
+
+/// Placeholders accepted in notification webhook bodies.
+pub const NOTIFY_VARS: &[&str] = &["action", "status", "repo", "target", "hostname", "message"];
+
+/// Placeholders accepted in `list --format`.
+pub const LIST_FORMAT_VARS: &[&str] = &["name", "time", "comment"];
+
+/// Placeholders accepted in `files --format`; `size` is in bytes, `hsize` human-readable.
+pub const FILES_FORMAT_VARS: &[&str] = &[
+    "path", "type", "mode", "user", "group", "size", "hsize", "mtime", "checksum",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Var(String),
+    Now(String),
+}
+
+/// Parsed `{placeholder}` template, e.g. `{hostname}-{preset}-{now:%Y-%m-%d}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Parse `raw`, rejecting placeholders not listed in `vars` and invalid strftime formats.
+    /// `{{` and `}}` produce literal braces.
+    pub fn parse(raw: &str, vars: &[&str]) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = raw.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => anyhow::bail!("Unmatched '}}' in template '{}'", raw),
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) => inner.push(ch),
+                            None => anyhow::bail!("Unclosed '{{' in template '{}'", raw),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_placeholder(&inner, vars, raw)?);
+                }
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Expand the template; `lookup` resolves named placeholders.
+    pub fn render<'a, F>(&self, now: DateTime<Local>, lookup: F) -> String
+    where
+        F: Fn(&str) -> Option<&'a str>,
+    {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Var(name) => out.push_str(lookup(name).unwrap_or_default()),
+                Segment::Now(fmt) => out.push_str(&now.format(fmt).to_string()),
+            }
+        }
+        out
+    }
+
+    /// Expansion of everything before the first time placeholder: the part shared by all
+    /// names the template produces.
+    pub fn render_prefix<'a, F>(&self, lookup: F) -> String
+    where
+        F: Fn(&str) -> Option<&'a str>,
+    {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Var(name) => out.push_str(lookup(name).unwrap_or_default()),
+                Segment::Now(_) => break,
+            }
+        }
+        out
+    }
+}
+
+fn parse_placeholder(inner: &str, vars: &[&str], raw: &str) -> Result<Segment> {
+    if let Some(fmt) = inner.strip_prefix("now:") {
+        if fmt.is_empty() {
+            anyhow::bail!("Empty time format in '{{now:}}' in template '{}'", raw);
+        }
+        if StrftimeItems::new(fmt).parse().is_err() {
+            anyhow::bail!("Invalid time format '{}' in template '{}'", fmt, raw);
+        }
+        return Ok(Segment::Now(fmt.to_string()));
+    }
+    if inner == "now" {
+        return Ok(Segment::Now("%Y-%m-%d_%H-%M-%S".to_string()));
+    }
+    if vars.contains(&inner) {
+        return Ok(Segment::Var(inner.to_string()));
+    }
+    anyhow::bail!(
+        "Unknown placeholder '{{{}}}' in template '{}'. Supported: {}, {{now:<format>}}",
+        inner,
+        raw,
+        vars.iter()
+            .map(|v| format!("{{{}}}", v))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Parse an output line template given on the command line. Quoted shell arguments keep
+/// backslashes, so `\t`, `\n` and `\\` are turned into tab, newline and backslash here.
+pub fn parse_line_format(raw: &str, vars: &[&str]) -> Result<Template> {
+    let mut unescaped = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    Template::parse(&unescaped, vars)
+}
+
+/// Validate an archive name template: known placeholders and no path separators.
+pub fn validate_archive_name_template(raw: &str) -> Result<Template> {
+    let template = Template::parse(raw, ARCHIVE_NAME_VARS)?;
+    let sample = template.render(Local::now(), |_| Some("x"));
+    if sample.trim().is_empty() {
+        anyhow::bail!("Archive name template '{}' expands to an empty name", raw);
+    }
+    if sample.contains('/') {
+        anyhow::bail!("Archive name template '{}' must not produce '/'", raw);
+    }
+    Ok(template)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn fixed_now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 3, 4, 5, 6, 7).unwrap()
+    }
+
+    #[test]
+    fn renders_vars_and_time() {
+        let tpl = Template::parse("{hostname}-{preset}-{now:%Y-%m-%d}", ARCHIVE_NAME_VARS).unwrap();
+        let out = tpl.render(fixed_now(), |name| match name {
+            "hostname" => Some("box"),
+            "preset" => Some("home"),
+            _ => None,
+        });
+        assert_eq!(out, "box-home-2025-03-04");
+    }
+
+    #[test]
+    fn render_prefix_stops_at_time() {
+        let tpl = Template::parse("{hostname}-{preset}-{now}-x", ARCHIVE_NAME_VARS).unwrap();
+        assert_eq!(tpl.render_prefix(|_| Some("h")), "h-h-");
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        let tpl = Template::parse("{{x}}-{repo}", ARCHIVE_NAME_VARS).unwrap();
+        assert_eq!(tpl.render(fixed_now(), |_| Some("r")), "{x}-r");
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for raw in ["{host}", "{preset", "preset}", "{now:%Q}", "{now:}"] {
+            assert!(
+                Template::parse(raw, ARCHIVE_NAME_VARS).is_err(),
+                "expected error for {raw}"
+            );
+        }
+    }
+
+    #[test]
+    fn line_format_unescapes_tabs() {
+        let tpl = parse_line_format(r"{path}\t{size}\\n", FILES_FORMAT_VARS).unwrap();
+        let out = tpl.render(fixed_now(), |name| match name {
+            "path" => Some("etc/hosts"),
+            "size" => Some("12"),
+            _ => None,
+        });
+        assert_eq!(out, "etc/hosts\t12\\n");
+        assert!(parse_line_format("{size}", LIST_FORMAT_VARS).is_err());
+    }
+
+    #[test]
+    fn archive_template_rejects_slashes() {
+        assert!(validate_archive_name_template("{now:%Y/%m}").is_err());
+        assert!(validate_archive_name_template("a/{preset}").is_err());
+        assert!(validate_archive_name_template("{hostname}-{now}").is_ok());
+    }
+}
@@ -0,0 +1,140 @@
+//! Directory tree built from a flat archive listing.
+
+use std::collections::BTreeMap;
+
+use crate::borg::BorgItem;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeNode {
+    pub name: String,
+    /// borg item type (`d`, `-`, `l`, ...); `None` for directories only implied by their children.
+    pub item_type: Option<String>,
+    /// Size of this item plus everything below it.
+    pub size: u64,
+    /// Number of listed items below this node.
+    pub items: usize,
+    pub children: BTreeMap<String, TreeNode>,
+}
+
+impl TreeNode {
+    pub fn is_dir(&self) -> bool {
+        !self.children.is_empty() || matches!(self.item_type.as_deref(), None | Some("d"))
+    }
+
+    /// The node at `path` (slash separated, relative to this node).
+    pub fn find(&self, path: &str) -> Option<&TreeNode> {
+        path.split('/')
+            .filter(|p| !p.is_empty())
+            .try_fold(self, |node, part| node.children.get(part))
+    }
+
+    /// The children as listing entries below `dir`, the way `borg list` would report them.
+    /// Implied directories get type `d`; directory sizes are left out.
+    pub fn child_items(&self, dir: &str) -> Vec<BorgItem> {
+        self.children
+            .values()
+            .map(|child| {
+                let is_dir = child.is_dir();
+                BorgItem {
+                    path: join_path(dir, &child.name),
+                    item_type: Some(child.item_type.clone().unwrap_or_else(|| "d".into())),
+                    size: (!is_dir).then_some(child.size),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+/// `dir/name`, or just `name` at the archive root.
+pub fn join_path(dir: &str, name: &str) -> String {
+    let dir = dir.trim_end_matches('/');
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+/// Arrange `items` into a tree rooted at an unnamed node. Parent directories missing from
+/// the listing (e.g. when it was filtered) are created on the way.
+pub fn build(items: &[BorgItem]) -> TreeNode {
+    let mut root = TreeNode::default();
+    for item in items {
+        let size = item.size.unwrap_or(0);
+        let mut node = &mut root;
+        for part in item.path.split('/').filter(|p| !p.is_empty()) {
+            node.size += size;
+            node.items += 1;
+            node = node
+                .children
+                .entry(part.to_string())
+                .or_insert_with(|| TreeNode {
+                    name: part.to_string(),
+                    ..Default::default()
+                });
+        }
+        node.item_type = item.item_type.clone();
+        node.size += size;
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, kind: &str, size: u64) -> BorgItem {
+        BorgItem {
+            path: path.into(),
+            item_type: Some(kind.into()),
+            size: Some(size),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn build_aggregates_counts_and_sizes() {
+        let root = build(&[
+            item("etc", "d", 0),
+            item("etc/hosts", "-", 100),
+            item("etc/ssh/sshd_config", "-", 50),
+            item("home/u/notes", "-", 7),
+        ]);
+        assert_eq!(root.items, 4);
+        assert_eq!(root.size, 157);
+
+        let etc = &root.children["etc"];
+        assert_eq!((etc.items, etc.size), (2, 150));
+        assert_eq!(etc.item_type.as_deref(), Some("d"));
+
+        // implied directory without its own listing entry
+        let ssh = &etc.children["ssh"];
+        assert_eq!((ssh.items, ssh.size, ssh.item_type.clone()), (1, 50, None));
+        assert!(ssh.is_dir());
+        assert!(!etc.children["hosts"].is_dir());
+    }
+
+    #[test]
+    fn find_and_child_items_fill_in_implied_directories() {
+        let root = build(&[
+            item("home/u/docs/a.txt", "-", 10),
+            item("home/u/docs/b", "d", 0),
+        ]);
+        assert!(root.find("home/x").is_none());
+
+        let u = root.find("home/u/").unwrap();
+        assert_eq!(
+            u.child_items("home/u"),
+            [BorgItem {
+                path: "home/u/docs".into(),
+                item_type: Some("d".into()),
+                ..Default::default()
+            }]
+        );
+        let docs = root.find("home/u/docs").unwrap().child_items("home/u/docs");
+        assert_eq!(docs[0].path, "home/u/docs/a.txt");
+        assert_eq!(docs[0].size, Some(10));
+        assert_eq!(docs[1].size, None);
+    }
+}
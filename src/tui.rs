@@ -0,0 +1,708 @@
+//! `borg-tool tui`: a full-screen alternative to the step-by-step menus. Repositories and
+//! archives on the left, the file tree of the selected archive in the middle, details on the
+//! right, and a status bar with mounts and running operations.
+//!
+//! borg runs on worker threads that report back over a channel, so the panes keep redrawing
+//! while a remote listing takes its time.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::borg::{self, BorgArchive, BorgItem, InfoReport};
+use crate::config::{RepoCtx, status_label};
+use crate::logging;
+use crate::mounts::{self, MountRecord};
+use crate::process;
+use crate::ui::{breadcrumbs, human_duration, human_size, is_dir_item, item_mode, load_directory};
+
+const KEYS_HELP: &str =
+    "↑↓ move  Enter open  ⌫ back  Tab switch pane  i stats  m mount  u unmount  r reload  q quit";
+
+/// Run the TUI over `repos` until the user quits.
+pub fn run(repos: Vec<RepoCtx>) -> Result<()> {
+    if repos.is_empty() {
+        anyhow::bail!("No repositories configured");
+    }
+    borg::ensure_can_prompt("The TUI needs a terminal")?;
+    let quiet = borg::quiet();
+    borg::set_quiet(true);
+    logging::set_stderr_muted(true);
+    let mut terminal = ratatui::init();
+    let result = App::new(repos).run(&mut terminal);
+    ratatui::restore();
+    logging::set_stderr_muted(false);
+    borg::set_quiet(quiet);
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Repos,
+    Archives,
+    Files,
+}
+
+/// Result of a worker thread.
+enum Outcome {
+    Archives(Result<Vec<BorgArchive>>),
+    /// Listings of `dir` and of any level below it that came along
+    Listing {
+        archive: String,
+        dir: String,
+        result: Result<HashMap<String, Vec<BorgItem>>>,
+    },
+    Info {
+        archive: String,
+        result: Result<InfoReport>,
+    },
+    Mount(Result<String>),
+}
+
+struct Finished {
+    job: u64,
+    /// Repo the job ran against; results for a repo closed meanwhile are dropped
+    repo: usize,
+    outcome: Outcome,
+}
+
+struct App {
+    repos: Vec<RepoCtx>,
+    repo_list: ListState,
+    /// Index of the repo whose archives are shown
+    open_repo: Option<usize>,
+    passphrases: HashMap<usize, Option<String>>,
+    archives: Vec<BorgArchive>,
+    archive_list: ListState,
+    /// Archive shown in the file pane and the directory inside it
+    browsing: Option<String>,
+    dir: String,
+    /// archive -> directory -> entries, directories first
+    listings: HashMap<String, HashMap<String, Vec<BorgItem>>>,
+    file_list: ListState,
+    info: HashMap<String, InfoReport>,
+    mounts: Vec<MountRecord>,
+    /// Labels of the borg operations running on worker threads
+    running: Vec<(u64, String)>,
+    next_job: u64,
+    /// Last error or notice, shown in the status bar
+    message: String,
+    pane: Pane,
+    tx: Sender<Finished>,
+    rx: Receiver<Finished>,
+}
+
+impl App {
+    fn new(repos: Vec<RepoCtx>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            repos,
+            repo_list: ListState::default().with_selected(Some(0)),
+            open_repo: None,
+            passphrases: HashMap::new(),
+            archives: Vec::new(),
+            archive_list: ListState::default(),
+            browsing: None,
+            dir: String::new(),
+            listings: HashMap::new(),
+            file_list: ListState::default(),
+            info: HashMap::new(),
+            mounts: mounts::load(&mounts::default_mounts_path()).unwrap_or_default(),
+            running: Vec::new(),
+            next_job: 0,
+            message: String::new(),
+            pane: Pane::Repos,
+            tx,
+            rx,
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            while let Ok(finished) = self.rx.try_recv() {
+                self.finish(finished);
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+            if process::interrupted() {
+                return Ok(());
+            }
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && !self.handle_key(key, terminal)?
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns `false` to quit.
+    fn handle_key(&mut self, key: KeyEvent, terminal: &mut DefaultTerminal) -> Result<bool> {
+        match key.code {
+            KeyCode::Char('q') => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false);
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.open(terminal)?,
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') | KeyCode::Esc => self.back(),
+            KeyCode::Tab => {
+                self.pane = match self.pane {
+                    Pane::Repos if self.open_repo.is_some() => Pane::Archives,
+                    Pane::Archives if self.browsing.is_some() => Pane::Files,
+                    Pane::Files => Pane::Archives,
+                    other => other,
+                }
+            }
+            KeyCode::Char('i') => self.load_info(),
+            KeyCode::Char('m') => self.mount(),
+            KeyCode::Char('u') => self.unmount(),
+            KeyCode::Char('r') => {
+                if let Some(repo) = self.open_repo {
+                    self.load_archives(repo);
+                }
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (state, len) = match self.pane {
+            Pane::Repos => (&mut self.repo_list, self.repos.len()),
+            Pane::Archives => (&mut self.archive_list, self.archives.len()),
+            Pane::Files => {
+                let len = self.current_listing().map_or(0, Vec::len);
+                (&mut self.file_list, len)
+            }
+        };
+        if len == 0 {
+            return;
+        }
+        let current = state.selected().unwrap_or(0) as isize;
+        state.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+    }
+
+    fn open(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        match self.pane {
+            Pane::Repos => {
+                let Some(idx) = self.repo_list.selected() else {
+                    return Ok(());
+                };
+                if !self.passphrases.contains_key(&idx) {
+                    let pass = match borg::passphrase_without_prompt(&self.repos[idx], &None) {
+                        Some(pass) => pass,
+                        None => {
+                            // rpassword needs the normal screen
+                            ratatui::restore();
+                            let pass = borg::ensure_passphrase(&self.repos[idx]);
+                            *terminal = ratatui::init();
+                            match pass {
+                                Ok(pass) => pass,
+                                Err(err) => {
+                                    self.message = format!("{:#}", err);
+                                    return Ok(());
+                                }
+                            }
+                        }
+                    };
+                    self.passphrases.insert(idx, pass);
+                }
+                if self.open_repo != Some(idx) {
+                    self.open_repo = Some(idx);
+                    self.archives.clear();
+                    self.archive_list.select(None);
+                    self.browsing = None;
+                    self.listings.clear();
+                    self.info.clear();
+                    self.load_archives(idx);
+                }
+                self.pane = Pane::Archives;
+            }
+            Pane::Archives => {
+                let Some(archive) = self.selected_archive().map(|a| a.name.clone()) else {
+                    return Ok(());
+                };
+                if self.browsing.as_deref() != Some(archive.as_str()) {
+                    self.browsing = Some(archive);
+                    self.enter_dir(String::new());
+                }
+                self.pane = Pane::Files;
+            }
+            Pane::Files => {
+                if let Some(item) = self.selected_item().filter(|i| is_dir_item(i)) {
+                    let dir = item.path.clone();
+                    self.enter_dir(dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn back(&mut self) {
+        match self.pane {
+            Pane::Files if !self.dir.is_empty() => {
+                let left = self.dir.clone();
+                self.enter_dir(parent_dir(&left).to_string());
+                // keep the cursor on the directory we came from
+                let position = self
+                    .current_listing()
+                    .and_then(|entries| entries.iter().position(|e| e.path == left));
+                self.file_list.select(position.or(Some(0)));
+            }
+            Pane::Files => self.pane = Pane::Archives,
+            Pane::Archives => self.pane = Pane::Repos,
+            Pane::Repos => {}
+        }
+    }
+
+    fn enter_dir(&mut self, dir: String) {
+        self.dir = dir;
+        self.file_list.select(Some(0));
+        if self.current_listing().is_none() {
+            self.load_listing();
+        }
+    }
+
+    fn current_listing(&self) -> Option<&Vec<BorgItem>> {
+        self.listings.get(self.browsing.as_ref()?)?.get(&self.dir)
+    }
+
+    fn selected_archive(&self) -> Option<&BorgArchive> {
+        self.archives.get(self.archive_list.selected()?)
+    }
+
+    fn selected_item(&self) -> Option<&BorgItem> {
+        self.current_listing()?.get(self.file_list.selected()?)
+    }
+
+    /// Archive the `i`/`m`/`u` keys act on: the one browsed in the file pane, or the one
+    /// selected in the archive list.
+    fn target_archive(&self) -> Option<String> {
+        match self.pane {
+            Pane::Files => self.browsing.clone(),
+            Pane::Archives => self.selected_archive().map(|a| a.name.clone()),
+            Pane::Repos => None,
+        }
+    }
+
+    /// Run `work` on a worker thread against the open repo, listed as `label` meanwhile.
+    fn spawn<F>(&mut self, label: String, work: F)
+    where
+        F: FnOnce(&RepoCtx, Option<&str>) -> Outcome + Send + 'static,
+    {
+        let Some(repo) = self.open_repo else {
+            return;
+        };
+        let ctx = self.repos[repo].clone();
+        let pass = self.passphrases.get(&repo).cloned().flatten();
+        let job = self.next_job;
+        self.next_job += 1;
+        self.running.push((job, label));
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            let outcome = work(&ctx, pass.as_deref());
+            let _ = tx.send(Finished { job, repo, outcome });
+        });
+    }
+
+    fn load_archives(&mut self, repo: usize) {
+        let label = format!("Listing archives of {}", self.repos[repo].name);
+        self.spawn(label, |ctx, pass| {
+            Outcome::Archives(borg::list_archives(ctx, pass))
+        });
+    }
+
+    fn load_listing(&mut self) {
+        let Some(archive) = self.browsing.clone() else {
+            return;
+        };
+        let dir = self.dir.clone();
+        let label = format!("Listing /{} in {}", dir, archive);
+        self.spawn(label, move |ctx, pass| {
+            let mut listings = HashMap::new();
+            let result =
+                load_directory(ctx, &archive, &dir, pass, &mut listings).map(|()| listings);
+            Outcome::Listing {
+                archive,
+                dir,
+                result,
+            }
+        });
+    }
+
+    fn load_info(&mut self) {
+        let Some(archive) = self.target_archive() else {
+            return;
+        };
+        let label = format!("Reading statistics of {}", archive);
+        self.spawn(label, move |ctx, pass| {
+            let result = borg::info(ctx, Some(&archive), pass);
+            Outcome::Info { archive, result }
+        });
+    }
+
+    fn mount(&mut self) {
+        let Some(archive) = self.target_archive() else {
+            return;
+        };
+        if self.mount_of(&archive).is_some() {
+            self.message = format!("{} is mounted already", archive);
+            return;
+        }
+        let label = format!("Mounting {}", archive);
+        self.spawn(label, move |ctx, pass| {
+            let mountpoint = borg::default_mountpoint(ctx, Some(&archive));
+            let result = borg::mount_archive(ctx, Some(&archive), &mountpoint, pass)
+                .map(|()| format!("Mounted {} at {}", archive, mountpoint.display()));
+            Outcome::Mount(result)
+        });
+    }
+
+    fn unmount(&mut self) {
+        let Some(archive) = self.target_archive() else {
+            return;
+        };
+        let Some(mountpoint) = self.mount_of(&archive) else {
+            self.message = format!("{} is not mounted", archive);
+            return;
+        };
+        let label = format!("Unmounting {}", mountpoint.display());
+        self.spawn(label, move |ctx, pass| {
+            let result = borg::umount_archive(ctx, &mountpoint, pass, false)
+                .map(|()| format!("Unmounted {}", mountpoint.display()));
+            Outcome::Mount(result)
+        });
+    }
+
+    fn mount_of(&self, archive: &str) -> Option<PathBuf> {
+        let repo = &self.repos[self.open_repo?];
+        self.mounts
+            .iter()
+            .find(|m| m.repo == repo.name && m.archive.as_deref() == Some(archive))
+            .map(|m| m.mountpoint.clone())
+    }
+
+    fn finish(&mut self, finished: Finished) {
+        self.running.retain(|(job, _)| *job != finished.job);
+        match finished.outcome {
+            Outcome::Mount(result) => {
+                // mounts are recorded on disk, whatever repo is open now
+                self.message = result.unwrap_or_else(|err| format!("{:#}", err));
+                self.mounts = mounts::load(&mounts::default_mounts_path()).unwrap_or_default();
+            }
+            // results for a repo that was closed meanwhile
+            _ if self.open_repo != Some(finished.repo) => {}
+            Outcome::Archives(Ok(mut archives)) => {
+                archives.reverse(); // newest first
+                self.archive_list
+                    .select((!archives.is_empty()).then_some(0));
+                self.message = format!("{} archive(s)", archives.len());
+                self.archives = archives;
+            }
+            Outcome::Listing {
+                archive,
+                dir,
+                result: Ok(listings),
+            } => {
+                let cached = self.listings.entry(archive).or_default();
+                cached.extend(listings);
+                cached.entry(dir).or_default();
+            }
+            Outcome::Info {
+                archive,
+                result: Ok(report),
+            } => {
+                self.info.insert(archive, report);
+            }
+            Outcome::Archives(Err(err))
+            | Outcome::Listing {
+                result: Err(err), ..
+            }
+            | Outcome::Info {
+                result: Err(err), ..
+            } => self.message = format!("{:#}", err),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status, help] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [left, middle, right] = Layout::horizontal([
+            Constraint::Percentage(25),
+            Constraint::Percentage(45),
+            Constraint::Percentage(30),
+        ])
+        .areas(main);
+
+        self.draw_left(frame, left);
+        self.draw_files(frame, middle);
+        frame.render_widget(
+            Paragraph::new(self.details())
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title(" Details ")),
+            right,
+        );
+        frame.render_widget(Paragraph::new(self.status_line()), status);
+        frame.render_widget(
+            Paragraph::new(KEYS_HELP).style(Style::new().add_modifier(Modifier::DIM)),
+            help,
+        );
+    }
+
+    fn draw_left(&mut self, frame: &mut Frame, area: Rect) {
+        let focused = self.pane != Pane::Files;
+        if self.pane == Pane::Repos || self.open_repo.is_none() {
+            let items: Vec<ListItem> = self
+                .repos
+                .iter()
+                .map(|r| ListItem::new(format!("{}  [{}]", r.name, status_label(r.status))))
+                .collect();
+            let list = pane_list(items, " Repositories ", focused);
+            frame.render_stateful_widget(list, area, &mut self.repo_list);
+            return;
+        }
+        let repo = &self.repos[self.open_repo.unwrap_or_default()];
+        let title = format!(" {} ({}) ", repo.name, self.archives.len());
+        let items: Vec<ListItem> =
+            self.archives
+                .iter()
+                .map(|a| {
+                    let mounted = if self.mounts.iter().any(|m| {
+                        m.repo == repo.name && m.archive.as_deref() == Some(a.name.as_str())
+                    }) {
+                        " [mounted]"
+                    } else {
+                        ""
+                    };
+                    ListItem::new(format!("{}{}", a.name, mounted))
+                })
+                .collect();
+        let list = pane_list(items, &title, focused);
+        frame.render_stateful_widget(list, area, &mut self.archive_list);
+    }
+
+    fn draw_files(&mut self, frame: &mut Frame, area: Rect) {
+        let focused = self.pane == Pane::Files;
+        let Some(archive) = self.browsing.clone() else {
+            let hint = Paragraph::new("Open an archive to browse its files")
+                .block(Block::bordered().title(" Files "));
+            frame.render_widget(hint, area);
+            return;
+        };
+        let title = format!(" {}: {} ", archive, breadcrumbs(&self.dir));
+        let Some(entries) = self.current_listing() else {
+            let loading = Paragraph::new("Loading…").block(Block::bordered().title(title));
+            frame.render_widget(loading, area);
+            return;
+        };
+        let items: Vec<ListItem> = entries.iter().map(|e| ListItem::new(file_row(e))).collect();
+        let list = pane_list(items, &title, focused);
+        frame.render_stateful_widget(list, area, &mut self.file_list);
+    }
+
+    fn details(&self) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        let mut field = |name: &str, value: String| {
+            lines.push(Line::from(format!("{:<11} {}", name, value)));
+        };
+        match self.pane {
+            Pane::Repos => {
+                let Some(repo) = self.repo_list.selected().and_then(|i| self.repos.get(i)) else {
+                    return lines;
+                };
+                field("Name", repo.name.clone());
+                field("Location", repo.repo.clone());
+                field("Status", status_label(repo.status).to_string());
+                field("Presets", repo.backups.len().to_string());
+                field("Mount root", repo.mount_root.display().to_string());
+            }
+            Pane::Archives => {
+                let Some(archive) = self.selected_archive() else {
+                    return lines;
+                };
+                field("Archive", archive.name.clone());
+                field(
+                    "Time",
+                    archive.time_utc.clone().unwrap_or_else(|| "-".to_string()),
+                );
+                if !archive.comment.is_empty() {
+                    field("Comment", archive.comment.clone());
+                }
+                if let Some(mountpoint) = self.mount_of(&archive.name) {
+                    field("Mounted at", mountpoint.display().to_string());
+                }
+                match self
+                    .info
+                    .get(&archive.name)
+                    .and_then(|r| r.archive.as_ref())
+                {
+                    Some(info) => {
+                        let stats = &info.stats;
+                        field("Files", stats.nfiles.to_string());
+                        field("Original", human_size(stats.original_size));
+                        field("Compressed", human_size(stats.compressed_size));
+                        field("Dedup", human_size(stats.deduplicated_size));
+                        if let Some(secs) = info.duration {
+                            field("Duration", human_duration(secs as u64));
+                        }
+                        if let Some(host) = &info.hostname {
+                            field("Host", host.clone());
+                        }
+                    }
+                    None => lines.push(Line::from("Press i for statistics")),
+                }
+            }
+            Pane::Files => {
+                let Some(item) = self.selected_item() else {
+                    return lines;
+                };
+                field("Path", item.path.clone());
+                field("Mode", item_mode(item).to_string());
+                if item.user.is_some() || item.group.is_some() {
+                    field(
+                        "Owner",
+                        format!(
+                            "{}:{}",
+                            item.user.as_deref().unwrap_or("-"),
+                            item.group.as_deref().unwrap_or("-")
+                        ),
+                    );
+                }
+                if let Some(size) = item.size {
+                    field("Size", format!("{} ({} bytes)", human_size(size), size));
+                }
+                if let Some(mtime) = &item.mtime {
+                    field("Modified", mtime.clone());
+                }
+            }
+        }
+        lines
+    }
+
+    fn status_line(&self) -> String {
+        let mounts = if self.mounts.is_empty() {
+            "none".to_string()
+        } else {
+            self.mounts
+                .iter()
+                .map(|m| format!("{} @ {}", m.label(), m.mountpoint.display()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut line = format!("Mounts: {}", mounts);
+        if !self.running.is_empty() {
+            let labels: Vec<&str> = self.running.iter().map(|(_, l)| l.as_str()).collect();
+            line.push_str(&format!(" | Running: {}…", labels.join(", ")));
+        }
+        if !self.message.is_empty() {
+            line.push_str(&format!(" | {}", self.message));
+        }
+        line
+    }
+}
+
+fn pane_list<'a>(items: Vec<ListItem<'a>>, title: &str, focused: bool) -> List<'a> {
+    let block = Block::bordered().title(title.to_string());
+    let block = if focused {
+        block.border_style(Style::new().add_modifier(Modifier::BOLD))
+    } else {
+        block.border_style(Style::new().add_modifier(Modifier::DIM))
+    };
+    List::new(items)
+        .block(block)
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
+
+fn file_row(item: &BorgItem) -> String {
+    let name = item.path.rsplit('/').next().unwrap_or(&item.path);
+    let size = match item.size {
+        Some(size) if !is_dir_item(item) => human_size(size),
+        _ => String::new(),
+    };
+    let suffix = if is_dir_item(item) { "/" } else { "" };
+    format!("{:<10} {:>10}  {}{}", item_mode(item), size, name, suffix)
+}
+
+fn parent_dir(dir: &str) -> &str {
+    dir.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn parent_dir_walks_up_to_the_root() {
+        assert_eq!(parent_dir("etc/ssh/sshd_config.d"), "etc/ssh");
+        assert_eq!(parent_dir("etc"), "");
+    }
+
+    #[test]
+    fn draw_shows_panes_and_status() {
+        let repo = RepoCtx {
+            name: "nas".into(),
+            repo: "/srv/borg".into(),
+            ..Default::default()
+        };
+        let mut app = App::new(vec![repo]);
+        app.open_repo = Some(0);
+        app.pane = Pane::Files;
+        app.archives = vec![BorgArchive {
+            name: "home-2025-01-01".into(),
+            time_utc: None,
+            ..Default::default()
+        }];
+        app.browsing = Some("home-2025-01-01".into());
+        app.listings
+            .entry("home-2025-01-01".into())
+            .or_default()
+            .insert(
+                String::new(),
+                vec![BorgItem {
+                    path: "notes.md".into(),
+                    item_type: Some("-".into()),
+                    size: Some(2048),
+                    ..Default::default()
+                }],
+            );
+        app.file_list.select(Some(0));
+        app.mounts.clear();
+        app.running.push((0, "Listing archives of nas".into()));
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 12)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("home-2025-01-01"));
+        assert!(screen.contains("notes.md"));
+        assert!(screen.contains("2.0 KiB"));
+        assert!(screen.contains("Running: Listing archives of nas"));
+    }
+}
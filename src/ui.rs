@@ -1,24 +1,115 @@
+use std::collections::HashMap;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use dialoguer::{Confirm, Input, Select, console::Term, theme::ColorfulTheme};
+use chrono::NaiveDateTime;
+use dialoguer::{Confirm, FuzzySelect, Input, Select, console::Term, theme::ColorfulTheme};
 use rpassword::prompt_password;
+use serde::Serialize;
 
 use crate::borg::{
-    BorgArchive, BorgItem, default_mountpoint, ensure_mount_available, ensure_passphrase_cached,
-    init_repo, list_archives, list_items, mount_archive, repo_status, run_backup, umount_archive,
+    BackupOutcome, BackupPreview, BackupRun, BorgArchive, BorgItem, BorgTiming, DiffEntry,
+    DiffKind, InfoReport, PruneReport, RepoLocked, break_lock, default_mountpoint, delete_archives,
+    diff_archives, ensure_can_prompt, ensure_mount_available, ensure_passphrase_cached,
+    extract_ssh_host, extract_streaming, extract_to_stdout, fetch_archive_list, init_repo,
+    list_archives, list_directory, list_items, list_subtree, mount_archive, non_interactive,
+    passphrase_without_prompt, plain_output, quiet, repo_locked, repo_status, restore_archive,
+    run_backup, short_hostname, umount_archive, with_spinner,
 };
 use crate::config::{
-    BackupConfig, Config, RepoConfig, RepoCtx, RepoStatus, default_borg_bin, default_mount_root,
-    save_config, status_label,
+    BackupConfig, BorgBin, Config, RepoConfig, RepoCtx, RepoStatus, SshOptions, default_borg_bin,
+    default_mount_root, save_config, status_label,
 };
+use crate::filter::{
+    ARCHIVE_SORT_KEYS, ArchiveFilter, DateRange, parse_archive_time, parse_since, parse_until,
+};
+use crate::find::FindReport;
+use crate::grep::{GrepReport, MAX_GREP_FILE_BYTES, looks_binary};
+use crate::history::{HistoryEntry, default_history_path};
+use crate::mounts::{self, MountRecord};
+use crate::probes;
+use crate::process::{is_cancelled, run_foreground, running_as_root};
+use crate::ps::ToolProcess;
+use crate::runlock;
+use crate::status::PresetStatus;
+use crate::template::Template;
+use crate::tree::TreeNode;
+
+type ArchivePrefetch = thread::JoinHandle<Result<Vec<BorgArchive>>>;
+
+/// How long the interactive session reuses an archive list before listing again.
+const ARCHIVE_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Archive list of the interactive session's repo, reused when "Archives" is opened again
+/// and refreshed in the background once it is older than [`ARCHIVE_CACHE_TTL`].
+#[derive(Default)]
+struct ArchiveCache {
+    listed: Option<(Instant, Vec<BorgArchive>)>,
+    refresh: Option<ArchivePrefetch>,
+}
+
+impl ArchiveCache {
+    fn is_fresh(&self) -> bool {
+        self.listed
+            .as_ref()
+            .is_some_and(|(at, _)| at.elapsed() < ARCHIVE_CACHE_TTL)
+    }
+
+    fn archives(&self) -> &[BorgArchive] {
+        self.listed.as_ref().map_or(&[], |(_, archives)| archives)
+    }
+
+    fn store(&mut self, archives: Vec<BorgArchive>) {
+        self.listed = Some((Instant::now(), archives));
+    }
+
+    /// Start listing in the background unless the list is fresh or a listing is running.
+    fn refresh_if_stale(&mut self, repo: &RepoCtx, pass: Option<String>) {
+        if self.is_fresh() || self.refresh.is_some() {
+            return;
+        }
+        let ctx = repo.clone();
+        self.refresh = Some(thread::spawn(move || {
+            fetch_archive_list(&ctx, &ArchiveFilter::default(), pass.as_deref())
+        }));
+    }
+
+    /// Wait for a background listing. A failed one leaves the cache stale, so the next
+    /// foreground listing reports the error.
+    fn settle(&mut self) -> Result<()> {
+        let Some(handle) = self.refresh.take() else {
+            return Ok(());
+        };
+        let listed = with_spinner("Listing archives", |_pb| {
+            Ok(handle.join().ok().and_then(Result::ok))
+        })?;
+        if let Some(archives) = listed {
+            self.store(archives);
+        }
+        Ok(())
+    }
+
+    fn forget(&mut self, name: &str) {
+        if let Some((_, archives)) = &mut self.listed {
+            archives.retain(|a| a.name != name);
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.listed = None;
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub enum ArchiveAction {
     Browse,
+    Compare,
     Mount,
     UnmountCurrent,
+    Delete,
     Back,
 }
 
@@ -26,32 +117,30 @@ pub enum ArchiveAction {
 pub enum MainAction {
     Archives,
     Backups,
+    MountRepo,
+    Mounts,
     BackRepo,
     Quit,
 }
 
-fn short_hostname() -> String {
-    if let Ok(env) = std::env::var("HOSTNAME")
-        && !env.trim().is_empty()
-    {
-        return env;
-    }
-    match Command::new("hostname").arg("-s").output() {
-        Ok(out) if out.status.success() => {
-            let raw = String::from_utf8_lossy(&out.stdout).trim().to_string();
-            if raw.is_empty() {
-                "unknown".to_string()
-            } else {
-                raw
-            }
-        }
-        _ => "unknown".to_string(),
+/// Clear the screen between steps, unless output goes to a log (`--plain`).
+fn clear_screen(term: &Term) -> Result<()> {
+    if plain_output() {
+        term.write_line("")?;
+    } else {
+        term.clear_screen()?;
     }
+    Ok(())
+}
+
+/// `--plain` default: on when stderr is not a terminal or `CI=true`.
+pub fn plain_by_default() -> bool {
+    !std::io::stderr().is_terminal() || std::env::var("CI").is_ok_and(|v| v == "true")
 }
 
 fn show_step(title: &str, lines: &[String]) -> Result<()> {
     let term = Term::stdout();
-    term.clear_screen()?;
+    clear_screen(&term)?;
     term.write_line(title)?;
     if !lines.is_empty() {
         for line in lines {
@@ -71,7 +160,7 @@ fn show_error_and_wait(message: &str) {
 
 fn show_repo_select_header(host: &str) -> Result<()> {
     let term = Term::stdout();
-    term.clear_screen()?;
+    clear_screen(&term)?;
     term.write_line(&format!("Host: {} | Repo: (choose) | Mount: n/a", host))?;
     term.write_line("")?;
     term.write_line("Choose repository")?;
@@ -88,10 +177,10 @@ fn show_step_with_ctx(
     mount: Option<&MountInfo>,
 ) -> Result<()> {
     let term = Term::stdout();
-    term.clear_screen()?;
+    clear_screen(&term)?;
     let mount_line = if mount_available {
         match mount {
-            Some(m) => format!("Mount: {} @ {}", m.archive, m.mountpoint.display()),
+            Some(m) => format!("Mount: {} @ {}", m.label(), m.mountpoint.display()),
             None => "Mount: none".to_string(),
         }
     } else {
@@ -120,14 +209,18 @@ pub fn select_archive_action(
     theme: &ColorfulTheme,
     has_mount: bool,
     mount_available: bool,
+    can_delete: bool,
 ) -> Result<ArchiveAction> {
-    let mut options = vec!["Browse files"];
+    let mut options = vec!["Browse files", "Compare with…"];
     if mount_available {
         options.push("Mount");
     }
     if has_mount {
         options.push("Unmount current");
     }
+    if can_delete {
+        options.push("Delete…");
+    }
     options.push("Back");
 
     let choice = Select::with_theme(theme)
@@ -141,8 +234,10 @@ pub fn select_archive_action(
             let label = options[idx];
             match label {
                 "Browse files" => ArchiveAction::Browse,
+                "Compare with…" => ArchiveAction::Compare,
                 "Mount" => ArchiveAction::Mount,
                 "Unmount current" => ArchiveAction::UnmountCurrent,
+                "Delete…" => ArchiveAction::Delete,
                 _ => ArchiveAction::Back,
             }
         }
@@ -151,22 +246,33 @@ pub fn select_archive_action(
     Ok(action)
 }
 
-pub fn select_main_action(theme: &ColorfulTheme) -> Result<MainAction> {
-    let options = ["Archives", "Backups", "Change repository", "Quit"];
+/// `recorded_mounts`: mounts of this repo left by earlier runs, offered as "Mounts (N)".
+pub fn select_main_action(
+    theme: &ColorfulTheme,
+    mount_available: bool,
+    recorded_mounts: usize,
+) -> Result<MainAction> {
+    let mut options = vec![
+        ("Archives".to_string(), MainAction::Archives),
+        ("Backups".to_string(), MainAction::Backups),
+    ];
+    if mount_available {
+        options.push(("Mount repository".to_string(), MainAction::MountRepo));
+    }
+    if recorded_mounts > 0 {
+        options.push((format!("Mounts ({})", recorded_mounts), MainAction::Mounts));
+    }
+    options.push(("Change repository".to_string(), MainAction::BackRepo));
+    options.push(("Quit".to_string(), MainAction::Quit));
+    let labels: Vec<&str> = options.iter().map(|(label, _)| label.as_str()).collect();
     let choice = Select::with_theme(theme)
         .with_prompt("What do you want to do?")
-        .items(options)
+        .items(&labels)
         .default(0)
         .interact_opt()?;
 
-    let action = match choice {
-        Some(0) => MainAction::Archives,
-        Some(1) => MainAction::Backups,
-        Some(2) => MainAction::BackRepo,
-        None => MainAction::BackRepo, // Esc should go back to repo selection
-        _ => MainAction::Quit,
-    };
-    Ok(action)
+    // Esc should go back to repo selection
+    Ok(choice.map_or(MainAction::BackRepo, |idx| options[idx].1))
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -177,7 +283,7 @@ pub enum InteractiveOutcome {
 
 #[derive(Debug, Clone)]
 pub enum BackupChoice {
-    Preset(BackupConfig),
+    Preset(Box<BackupConfig>),
     CreateNew,
     Back,
 }
@@ -193,6 +299,9 @@ pub fn select_repo_ctx(
     if repos.is_empty() {
         match cmd {
             None | Some(crate::cli::Commands::Interactive) => {
+                ensure_can_prompt(
+                    "No repositories configured; add one to the config file or pass --repo-url",
+                )?;
                 show_step(
                     "No repositories configured",
                     &["Let's set up a repository to get started.".to_string()],
@@ -233,6 +342,14 @@ pub fn select_repo_ctx(
         None
         | Some(crate::cli::Commands::Interactive)
         | Some(crate::cli::Commands::Backup { .. }) => loop {
+            ensure_can_prompt(&format!(
+                "Multiple repos configured; choose one with --repo <name> ({})",
+                repos
+                    .iter()
+                    .map(|r| r.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))?;
             show_repo_select_header(&host)?;
             let mut labels: Vec<String> = repos
                 .iter()
@@ -277,50 +394,243 @@ pub fn select_archive(
     archives: &[BorgArchive],
     theme: &ColorfulTheme,
 ) -> Result<Option<BorgArchive>> {
-    let mut items: Vec<String> = archives
-        .iter()
-        .map(|a| {
-            let time = a.time_utc.as_deref().unwrap_or("-");
-            format!("{}  [{}]", a.name, time)
-        })
-        .collect();
-    items.push("Back".to_string());
+    Ok(match pick_archive(archives, theme, false)? {
+        ArchivePick::Archive(archive) => Some(archive),
+        ArchivePick::Refresh | ArchivePick::Back => None,
+    })
+}
 
-    let selection = Select::with_theme(theme)
-        .with_prompt("Choose archive (Back to return)")
-        .items(&items)
-        .default(0)
-        .interact_opt()?;
+enum ArchivePick {
+    Archive(BorgArchive),
+    /// List the archives again instead of using the cached list
+    Refresh,
+    Back,
+}
 
-    Ok(match selection {
-        Some(idx) if idx < archives.len() => Some(archives[idx].clone()),
-        _ => None,
-    })
+fn pick_archive(
+    archives: &[BorgArchive],
+    theme: &ColorfulTheme,
+    refreshable: bool,
+) -> Result<ArchivePick> {
+    ensure_can_prompt("No archive given; pass the archive name")?;
+    let mut range = DateRange::default();
+    let mut filter = ArchiveFilter::default();
+    loop {
+        let shown = range.apply(&filter.apply(archives));
+        let mut items: Vec<String> = shown
+            .iter()
+            .map(|a| {
+                let time = a.time_utc.as_deref().unwrap_or("-");
+                if a.comment.is_empty() {
+                    format!("{}  [{}]", a.name, time)
+                } else {
+                    format!("{}  [{}]  {}", a.name, time, a.comment)
+                }
+            })
+            .collect();
+        let mut actions = vec![
+            ("Filter by date…", ArchiveListAction::Dates),
+            ("Filter by name…", ArchiveListAction::Name),
+            ("Only the last N…", ArchiveListAction::Last),
+            ("Sort by…", ArchiveListAction::Sort),
+        ];
+        if !range.is_empty() || !filter.is_empty() {
+            actions.push(("Clear filters", ArchiveListAction::Clear));
+        }
+        if refreshable {
+            actions.push(("Refresh list", ArchiveListAction::Refresh));
+        }
+        items.extend(actions.iter().map(|(label, _)| label.to_string()));
+        items.push("Back".to_string());
+
+        let prompt = if shown.len() == archives.len() {
+            "Choose archive (type to filter, Back to return)".to_string()
+        } else {
+            format!(
+                "Choose archive ({} of {} shown, type to filter, Back to return)",
+                shown.len(),
+                archives.len()
+            )
+        };
+        let selection = FuzzySelect::with_theme(theme)
+            .with_prompt(prompt)
+            .items(&items)
+            .default(0)
+            .interact_opt()?;
+
+        let Some(idx) = selection else {
+            return Ok(ArchivePick::Back);
+        };
+        if idx < shown.len() {
+            return Ok(ArchivePick::Archive(shown[idx].clone()));
+        }
+        match actions.get(idx - shown.len()).map(|(_, action)| action) {
+            Some(ArchiveListAction::Dates) => range = prompt_date_range(theme)?,
+            Some(ArchiveListAction::Name) => {
+                let raw: String = Input::with_theme(theme)
+                    .with_prompt("Name prefix, or a pattern with * ? [ ] (empty = any)")
+                    .allow_empty(true)
+                    .interact_text()?;
+                let raw = raw.trim();
+                (filter.prefix, filter.glob) = if raw.is_empty() {
+                    (None, None)
+                } else if raw.contains(['*', '?', '[']) {
+                    (None, Some(raw.to_string()))
+                } else {
+                    (Some(raw.to_string()), None)
+                };
+            }
+            Some(ArchiveListAction::Last) => {
+                let n: usize = Input::with_theme(theme)
+                    .with_prompt("Show only the last N archives (0 = all)")
+                    .default(filter.last.unwrap_or(0))
+                    .interact_text()?;
+                filter.last = (n > 0).then_some(n);
+            }
+            Some(ArchiveListAction::Sort) => {
+                let current = filter.sort_by.as_deref().unwrap_or("timestamp");
+                let key = Select::with_theme(theme)
+                    .with_prompt("Sort archives by")
+                    .items(ARCHIVE_SORT_KEYS)
+                    .default(
+                        ARCHIVE_SORT_KEYS
+                            .iter()
+                            .position(|k| *k == current)
+                            .unwrap_or(0),
+                    )
+                    .interact_opt()?;
+                if let Some(key) = key {
+                    filter.sort_by = (key > 0).then(|| ARCHIVE_SORT_KEYS[key].to_string());
+                }
+            }
+            Some(ArchiveListAction::Clear) => {
+                range = DateRange::default();
+                filter = ArchiveFilter::default();
+            }
+            Some(ArchiveListAction::Refresh) => return Ok(ArchivePick::Refresh),
+            None => return Ok(ArchivePick::Back),
+        }
+    }
 }
 
-pub fn select_item(items: &[BorgItem], theme: &ColorfulTheme) -> Result<Option<BorgItem>> {
-    let mut display: Vec<String> = items
-        .iter()
-        .map(|i| format!("{:<6} {}", i.item_type.as_deref().unwrap_or(""), i.path))
-        .collect();
+enum ArchiveListAction {
+    Dates,
+    Name,
+    Last,
+    Sort,
+    Clear,
+    Refresh,
+}
+
+fn prompt_date_range(theme: &ColorfulTheme) -> Result<DateRange> {
+    let prompt_bound = |label: &str, parse: fn(&str) -> Result<NaiveDateTime>| {
+        Input::<String>::with_theme(theme)
+            .with_prompt(label)
+            .allow_empty(true)
+            .validate_with(|raw: &String| -> Result<(), String> {
+                if raw.trim().is_empty() {
+                    return Ok(());
+                }
+                parse(raw).map(|_| ()).map_err(|e| e.to_string())
+            })
+            .interact_text()
+            .map(|raw| {
+                let raw = raw.trim();
+                (!raw.is_empty()).then(|| parse(raw)).transpose()
+            })
+    };
+    let since = prompt_bound(
+        "Since (YYYY-MM-DD or age like 7d, 2w; empty = any)",
+        parse_since,
+    )??;
+    let until = prompt_bound("Until (YYYY-MM-DD or age; empty = any)", parse_until)??;
+    Ok(DateRange { since, until })
+}
+
+enum BrowseChoice {
+    Up,
+    Enter(String),
+    File(BorgItem),
+    ExtractDir,
+    Back,
+}
+
+fn select_browse_entry(
+    dir: &str,
+    entries: &[BorgItem],
+    theme: &ColorfulTheme,
+) -> Result<BrowseChoice> {
+    let at_root = dir.is_empty();
+    let mut display: Vec<String> = Vec::new();
+    if !at_root {
+        display.push("..".to_string());
+    }
+    for item in entries {
+        let name = item.path.rsplit('/').next().unwrap_or(&item.path);
+        let (name, size) = if is_dir_item(item) {
+            (format!("{}/", name), String::new())
+        } else {
+            (
+                name.to_string(),
+                item.size.map(human_size).unwrap_or_default(),
+            )
+        };
+        display.push(format!(
+            "{:<10} {:>10} {:<16} {}",
+            item_mode(item),
+            size,
+            item.mtime.as_deref().map(short_mtime).unwrap_or_default(),
+            name
+        ));
+    }
+    if !at_root {
+        display.push("Extract this directory".to_string());
+    }
     display.push("Back".to_string());
 
-    let selection = Select::with_theme(theme)
-        .with_prompt("Choose file (Back to return)")
+    let selection = FuzzySelect::with_theme(theme)
+        .with_prompt("Choose file or directory (type to filter, Back to return)")
         .items(&display)
         .default(0)
         .interact_opt()?;
-
-    Ok(match selection {
-        Some(idx) if idx < items.len() => Some(items[idx].clone()),
-        _ => None,
+    let Some(mut idx) = selection else {
+        return Ok(BrowseChoice::Back);
+    };
+    if !at_root {
+        if idx == 0 {
+            return Ok(BrowseChoice::Up);
+        }
+        idx -= 1;
+    }
+    Ok(match entries.get(idx) {
+        Some(item) if is_dir_item(item) => BrowseChoice::Enter(item.path.clone()),
+        Some(item) => BrowseChoice::File(item.clone()),
+        None if !at_root && idx == entries.len() => BrowseChoice::ExtractDir,
+        None => BrowseChoice::Back,
     })
 }
 
+pub(crate) fn is_dir_item(item: &BorgItem) -> bool {
+    item.item_type.as_deref() == Some("d")
+}
+
+/// "/ > etc > ssh" for `etc/ssh`.
+pub(crate) fn breadcrumbs(dir: &str) -> String {
+    std::iter::once("/")
+        .chain(dir.split('/').filter(|p| !p.is_empty()))
+        .collect::<Vec<_>>()
+        .join(" > ")
+}
+
 pub fn select_backup(backups: &[BackupConfig], theme: &ColorfulTheme) -> Result<BackupChoice> {
+    ensure_can_prompt("No backup preset given; pass the preset name")?;
     let mut labels: Vec<String> = backups
         .iter()
-        .map(|b| format!("{}  ({} includes)", b.name, b.includes.len()))
+        .map(|b| match (&b.database, &b.stdin_command) {
+            (Some(db), _) => format!("{}  ({:?} dump)", b.name, db.engine),
+            (None, Some(command)) => format!("{}  (output of `{}`)", b.name, command),
+            (None, None) => format!("{}  ({} includes)", b.name, b.includes.len()),
+        })
         .collect();
     labels.push("Create new backup preset".to_string());
     labels.push("Back".to_string());
@@ -332,7 +642,7 @@ pub fn select_backup(backups: &[BackupConfig], theme: &ColorfulTheme) -> Result<
         .interact_opt()?;
 
     Ok(match selection {
-        Some(idx) if idx < backups.len() => BackupChoice::Preset(backups[idx].clone()),
+        Some(idx) if idx < backups.len() => BackupChoice::Preset(Box::new(backups[idx].clone())),
         Some(idx) if idx == backups.len() => BackupChoice::CreateNew,
         _ => BackupChoice::Back,
     })
@@ -352,6 +662,35 @@ fn build_repo_list(cfg: &Config) -> Vec<RepoCtx> {
                     .unwrap_or_else(|| cfg.mount_root.clone()),
                 backups: r.backups.clone(),
                 status: RepoStatus::Unknown,
+                base_dir: r.base_dir.clone(),
+                cache_dir: r.cache_dir.clone(),
+                protected: r.protected,
+                compact_after_prune: r.compact_after_prune,
+                passcommand: r.passcommand.clone(),
+                passphrase_file: r.passphrase_file.clone(),
+                healthcheck_url: r.healthcheck_url.clone(),
+                max_age: r.max_age.clone(),
+                global_args: cfg.global_borg_args.clone(),
+                excludes: cfg
+                    .global_excludes
+                    .iter()
+                    .chain(&r.excludes)
+                    .cloned()
+                    .collect(),
+                env: cfg
+                    .env
+                    .iter()
+                    .chain(&r.env)
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+                ssh: SshOptions::from_repo(cfg, r),
+                remote_path: r.remote_borg_path.clone(),
+                upload_ratelimit: r.upload_ratelimit_kib,
+                lock_wait: r.lock_wait_seconds.or(cfg.lock_wait_seconds),
+                retries: r.retries.unwrap_or(cfg.retries),
+                notifications: cfg.notifications.clone(),
+                history_file: Some(default_history_path()),
+                ..Default::default()
             })
             .collect()
     } else if let Some(repo) = &cfg.repo {
@@ -362,6 +701,14 @@ fn build_repo_list(cfg: &Config) -> Vec<RepoCtx> {
             mount_root: cfg.mount_root.clone(),
             backups: Vec::new(),
             status: RepoStatus::Unknown,
+            global_args: cfg.global_borg_args.clone(),
+            excludes: cfg.global_excludes.clone(),
+            env: cfg.env.clone(),
+            lock_wait: cfg.lock_wait_seconds,
+            retries: cfg.retries,
+            notifications: cfg.notifications.clone(),
+            history_file: Some(default_history_path()),
+            ..Default::default()
         }]
     } else {
         Vec::new()
@@ -371,35 +718,114 @@ fn build_repo_list(cfg: &Config) -> Vec<RepoCtx> {
         return repos;
     }
 
-    let total = repos.len();
-
-    for (idx, repo) in repos.iter_mut().enumerate() {
-        let pb = indicatif::ProgressBar::new_spinner();
-        pb.set_style(
-            indicatif::ProgressStyle::with_template("{spinner:.green} {msg}").expect("template"),
-        );
-        pb.set_message(format!(
-            "({}/{}) Probing {} ({})",
-            idx + 1,
-            total,
-            repo.name,
-            repo.repo
-        ));
-        pb.enable_steady_tick(std::time::Duration::from_millis(120));
+    let probe: Vec<bool> = if cfg.repos.is_empty() {
+        vec![cfg.probe_ssh]
+    } else {
+        cfg.repos
+            .iter()
+            .map(|r| r.probe.unwrap_or(cfg.probe_ssh))
+            .collect()
+    };
+    let cache_path = probes::default_probes_path();
+    let mut cache = if cfg.probe_cache_seconds > 0 {
+        probes::load(&cache_path)
+    } else {
+        probes::ProbeCache::new()
+    };
+    let now = chrono::Utc::now();
 
-        repo.status = repo_status(&repo.repo, cfg.probe_ssh);
+    // remotes may each take the full SSH timeout, so probe them all at once
+    let progress = if quiet() {
+        indicatif::MultiProgress::with_draw_target(indicatif::ProgressDrawTarget::hidden())
+    } else {
+        indicatif::MultiProgress::new()
+    };
+    let style =
+        indicatif::ProgressStyle::with_template("{spinner:.green} {msg}").expect("template");
+    let cached: Vec<bool> = thread::scope(|scope| {
+        let workers: Vec<_> = repos
+            .iter_mut()
+            .zip(probe)
+            .map(|(repo, probe)| {
+                let cached =
+                    probe && probes::is_fresh(&cache, &repo.repo, cfg.probe_cache_seconds, now);
+                let pb = progress.add(indicatif::ProgressBar::new_spinner());
+                pb.set_style(style.clone());
+                pb.set_message(format!("Probing {} ({})", repo.name, repo.repo));
+                pb.enable_steady_tick(Duration::from_millis(120));
+                scope.spawn(move || {
+                    repo.status = if cached {
+                        RepoStatus::RemoteOk
+                    } else {
+                        repo_status(&repo.repo, &repo.ssh, probe)
+                    };
+                    pb.finish_with_message(format!(
+                        "[{}{}] {} ({})",
+                        status_label(repo.status),
+                        if cached { ", cached" } else { "" },
+                        repo.name,
+                        repo.repo
+                    ));
+                    cached
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or(false))
+            .collect()
+    });
 
-        pb.finish_with_message(format!(
-            "[{}] {} ({})",
-            status_label(repo.status),
-            repo.name,
-            repo.repo
-        ));
+    if cfg.probe_cache_seconds > 0 {
+        let mut changed = false;
+        for (repo, _) in repos.iter().zip(&cached).filter(|(_, cached)| !**cached) {
+            if repo.status == RepoStatus::RemoteOk {
+                probes::record(&mut cache, &repo.repo, now);
+                changed = true;
+            } else {
+                changed |= cache.remove(&repo.repo).is_some();
+            }
+        }
+        // only saves a round trip next time; not worth failing over
+        if changed {
+            let _ = probes::save(&cache_path, &cache);
+        }
     }
 
     repos
 }
 
+/// Temporary repo context for `--repo-url`, using the global defaults from `cfg`.
+pub fn adhoc_repo_ctx(cfg: &Config, repo: &str) -> RepoCtx {
+    RepoCtx {
+        name: "ad-hoc".to_string(),
+        repo: repo.to_string(),
+        borg_bin: cfg.borg_bin.clone(),
+        mount_root: cfg.mount_root.clone(),
+        backups: Vec::new(),
+        status: repo_status(repo, &SshOptions::default(), false),
+        global_args: cfg.global_borg_args.clone(),
+        lock_wait: cfg.lock_wait_seconds,
+        retries: cfg.retries,
+        notifications: cfg.notifications.clone(),
+        history_file: Some(default_history_path()),
+        ..Default::default()
+    }
+}
+
+/// All configured repos for `backup --all`, or only `only` when given.
+pub fn configured_repos(cfg: &Config, only: Option<&str>) -> Result<Vec<RepoCtx>> {
+    let mut repos = build_repo_list(cfg);
+    if let Some(name) = only {
+        let names = repos.iter().map(|r| r.name.clone()).collect::<Vec<_>>();
+        repos.retain(|r| r.name == name);
+        if repos.is_empty() {
+            anyhow::bail!("Repo '{}' not found. Available: {}", name, names.join(", "));
+        }
+    }
+    Ok(repos)
+}
+
 fn ensure_repo_available(repo: RepoCtx, cmd: Option<&crate::cli::Commands>) -> Result<RepoCtx> {
     match repo.status {
         RepoStatus::MissingLocal => match cmd {
@@ -413,6 +839,27 @@ fn ensure_repo_available(repo: RepoCtx, cmd: Option<&crate::cli::Commands>) -> R
                 anyhow::bail!("Repo '{}' path '{}' not found.", repo.name, repo.repo);
             }
         },
+        RepoStatus::HostKeyChanged => {
+            let host = extract_ssh_host(&repo.repo).unwrap_or_default();
+            let message = format!(
+                "The SSH host key of '{}' (repo '{}') differs from the one in ~/.ssh/known_hosts. \
+                 This can mean someone is intercepting the connection. Verify the new key with the \
+                 server's admin, then remove the old one with `ssh-keygen -R {}`.",
+                host, repo.name, host
+            );
+            match cmd {
+                None | Some(crate::cli::Commands::Interactive) => println!("Warning: {}", message),
+                _ => anyhow::bail!(message),
+            }
+        }
+        RepoStatus::HostKeyUnknown => {
+            let host = extract_ssh_host(&repo.repo).unwrap_or_default();
+            println!(
+                "Warning: '{}' is not in ~/.ssh/known_hosts and ssh_host_key_checking is strict; \
+                 connect once with `ssh {}` and check the fingerprint.",
+                host, host
+            );
+        }
         RepoStatus::RemoteAuthNeeded => match cmd {
             None | Some(crate::cli::Commands::Interactive) => {
                 println!(
@@ -438,16 +885,30 @@ pub fn run_interactive(
     mut repo: RepoCtx,
     passphrase_cache: &mut Option<String>,
 ) -> Result<InteractiveOutcome> {
+    ensure_can_prompt("The interactive menu needs a terminal; pass a subcommand")?;
     let theme = dialog_theme();
-    let mut mount_state: Option<MountInfo> = None;
+    offer_stale_cleanup(&repo, &theme)?;
+    // pick up the newest mount an earlier run left for this repo
+    let mut mount_state: Option<MountInfo> = recorded_mounts(&repo).pop().map(|record| MountInfo {
+        archive: record.archive,
+        mountpoint: record.mountpoint,
+    });
     let mount_available = ensure_mount_available(&repo).unwrap_or(false);
     let host = short_hostname();
+    let mut cache = ArchiveCache::default();
+
+    'menu: loop {
+        // warm up "Archives" while the user reads the menu; never prompts for a passphrase
+        if !repo.print_command
+            && let Some(pass) = passphrase_without_prompt(&repo, passphrase_cache)
+        {
+            cache.refresh_if_stale(&repo, pass);
+        }
 
-    loop {
         let mut main_info = vec![format!("Repo: {} ({})", repo.name, repo.repo)];
         main_info.push(if mount_available {
             match &mount_state {
-                Some(m) => format!("Mounted: {} @ {}", m.archive, m.mountpoint.display()),
+                Some(m) => format!("Mounted: {} @ {}", m.label(), m.mountpoint.display()),
                 None => "Mount available (none mounted)".to_string(),
             }
         } else {
@@ -462,40 +923,72 @@ pub fn run_interactive(
             mount_state.as_ref(),
         )?;
 
-        match select_main_action(&theme)? {
+        let action = select_main_action(&theme, mount_available, recorded_mounts(&repo).len())?;
+        // other actions may need an exclusive repo lock, so always let the refresh finish
+        cache.settle()?;
+        match action {
             MainAction::Archives => {
                 let pass = ensure_passphrase_cached(passphrase_cache, &repo)?;
-                let archives = list_archives(&repo, pass.as_deref())?;
-                if archives.is_empty() {
-                    println!("No archives found");
-                    continue;
-                }
-
-                let mut archive_info = vec![
-                    format!("Repo: {} ({})", repo.name, repo.repo),
-                    format!("Archives found: {}", archives.len()),
-                ];
-                archive_info.push(if mount_available {
-                    match &mount_state {
-                        Some(m) => format!("Mounted: {} @ {}", m.archive, m.mountpoint.display()),
-                        None => "Mount available (none mounted)".to_string(),
+                let archive = loop {
+                    // a failed refresh is retried in the foreground so its error is reported
+                    if !cache.is_fresh() {
+                        match list_archives(&repo, pass.as_deref()) {
+                            Ok(archives) => cache.store(archives),
+                            Err(err) if is_cancelled(&err) => continue 'menu,
+                            Err(err) => match repo_locked(&err) {
+                                Some(locked) => {
+                                    log::error!("{}", err);
+                                    offer_break_lock(&repo, locked, &theme)?;
+                                    continue 'menu;
+                                }
+                                None => return Err(err),
+                            },
+                        }
                     }
-                } else {
-                    "Mount unavailable (no FUSE support detected)".to_string()
-                });
-                show_step_with_ctx(
-                    "Archives",
-                    &archive_info,
-                    &host,
-                    &repo,
-                    mount_available,
-                    mount_state.as_ref(),
-                )?;
+                    let archives = cache.archives();
+                    if archives.is_empty() {
+                        println!("No archives found");
+                        continue 'menu;
+                    }
+
+                    let listed_ago = cache
+                        .listed
+                        .as_ref()
+                        .map_or(0, |(at, _)| at.elapsed().as_secs());
+                    let mut archive_info = vec![
+                        format!("Repo: {} ({})", repo.name, repo.repo),
+                        format!(
+                            "Archives found: {} (listed {} ago)",
+                            archives.len(),
+                            human_duration(listed_ago)
+                        ),
+                    ];
+                    archive_info.push(if mount_available {
+                        match &mount_state {
+                            Some(m) => {
+                                format!("Mounted: {} @ {}", m.label(), m.mountpoint.display())
+                            }
+                            None => "Mount available (none mounted)".to_string(),
+                        }
+                    } else {
+                        "Mount unavailable (no FUSE support detected)".to_string()
+                    });
+                    show_step_with_ctx(
+                        "Archives",
+                        &archive_info,
+                        &host,
+                        &repo,
+                        mount_available,
+                        mount_state.as_ref(),
+                    )?;
 
-                let archive = match select_archive(&archives, &theme)? {
-                    Some(a) => a,
-                    None => continue,
+                    match pick_archive(archives, &theme, true)? {
+                        ArchivePick::Archive(archive) => break archive,
+                        ArchivePick::Refresh => cache.invalidate(),
+                        ArchivePick::Back => continue 'menu,
+                    }
                 };
+                let archives = cache.archives();
 
                 let mut action_info = vec![
                     format!("Repo: {} ({})", repo.name, repo.repo),
@@ -508,7 +1001,7 @@ pub fn run_interactive(
                 if let Some(m) = &mount_state {
                     action_info.push(format!(
                         "Mounted: {} @ {}",
-                        m.archive,
+                        m.label(),
                         m.mountpoint.display()
                     ));
                 }
@@ -521,7 +1014,12 @@ pub fn run_interactive(
                     mount_state.as_ref(),
                 )?;
 
-                match select_archive_action(&theme, mount_state.is_some(), mount_available)? {
+                match select_archive_action(
+                    &theme,
+                    mount_state.is_some(),
+                    mount_available,
+                    !repo.protected,
+                )? {
                     ArchiveAction::Browse => {
                         browse_files(
                             &host,
@@ -533,40 +1031,64 @@ pub fn run_interactive(
                             mount_state.as_ref(),
                         )?;
                     }
+                    ArchiveAction::Compare => {
+                        let others: Vec<BorgArchive> = archives
+                            .iter()
+                            .filter(|a| a.name != archive.name)
+                            .cloned()
+                            .collect();
+                        if others.is_empty() {
+                            show_error_and_wait("No other archive to compare with.");
+                            continue;
+                        }
+                        show_step_with_ctx(
+                            "Compare with…",
+                            &action_info,
+                            &host,
+                            &repo,
+                            mount_available,
+                            mount_state.as_ref(),
+                        )?;
+                        if let Some(other) = select_archive(&others, &theme)? {
+                            compare_archives(
+                                &host,
+                                &repo,
+                                (&archive, &other),
+                                pass.as_deref(),
+                                &theme,
+                                mount_available,
+                                mount_state.as_ref(),
+                            )?;
+                        }
+                    }
                     ArchiveAction::Mount => {
-                        if let Some(active) = &mount_state {
-                            if Confirm::with_theme(&theme)
-                                .with_prompt(format!(
-                                    "Unmount current ({}) before mounting new one?",
-                                    active.mountpoint.display()
-                                ))
-                                .default(true)
-                                .interact()?
-                            {
-                                umount_archive(&repo, &active.mountpoint, pass.as_deref())?;
-                                println!("Unmounted {}", active.mountpoint.display());
-                            } else {
-                                continue;
-                            }
+                        mount_with_prompt(
+                            &repo,
+                            Some(&archive.name),
+                            &mut mount_state,
+                            pass.as_deref(),
+                            &theme,
+                        )?;
+                    }
+                    ArchiveAction::Delete => {
+                        if mount_state
+                            .as_ref()
+                            .is_some_and(|m| m.archive.as_deref() == Some(archive.name.as_str()))
+                        {
+                            show_error_and_wait("This archive is mounted; unmount it first.");
+                            continue;
+                        }
+                        let names = [archive.name.clone()];
+                        if confirm_delete_archives(&names, &theme)? {
+                            delete_archives(&repo, &names, pass.as_deref())?;
+                            cache.forget(&archive.name);
+                            println!("Deleted {}", archive.name);
                         }
-
-                        let default_mp = default_mountpoint(&repo, &archive.name);
-                        let target: String = Input::with_theme(&theme)
-                            .with_prompt("Mountpoint")
-                            .default(default_mp.display().to_string())
-                            .interact_text()?;
-                        let target_path = PathBuf::from(target);
-                        mount_archive(&repo, &archive.name, &target_path, pass.as_deref())?;
-                        println!("Mounted {} at {}", archive.name, target_path.display());
-                        mount_state = Some(crate::ui::MountInfo {
-                            archive: archive.name.clone(),
-                            mountpoint: target_path,
-                        });
                     }
                     ArchiveAction::Back => {}
                     ArchiveAction::UnmountCurrent => {
                         if let Some(active) = mount_state.take() {
-                            umount_archive(&repo, &active.mountpoint, pass.as_deref())?;
+                            umount_archive(&repo, &active.mountpoint, pass.as_deref(), false)?;
                             println!("Unmounted {}", active.mountpoint.display());
                         }
                     }
@@ -605,7 +1127,7 @@ pub fn run_interactive(
                     mount_state.as_ref(),
                 )?;
                 let preset = match select_backup(&repo.backups, &theme)? {
-                    BackupChoice::Preset(p) => p,
+                    BackupChoice::Preset(p) => *p,
                     BackupChoice::CreateNew => {
                         if let Some(new_preset) = setup_backup_preset_wizard(&repo, &theme)? {
                             add_preset_to_config(cfg, &repo.name, new_preset.clone());
@@ -618,56 +1140,318 @@ pub fn run_interactive(
                     }
                     BackupChoice::Back => continue,
                 };
-                if let Err(err) = run_backup(&repo, &preset, pass.as_deref()) {
-                    show_error_and_wait(&format!("Backup failed: {err}"));
+                match run_backup(&repo, &preset, pass.as_deref()) {
+                    Ok(outcome) => {
+                        cache.invalidate();
+                        print_backup_outcome(&outcome);
+                    }
+                    Err(err) => match repo_locked(&err) {
+                        Some(locked) => {
+                            log::error!("Backup failed: {err}");
+                            offer_break_lock(&repo, locked, &theme)?;
+                        }
+                        None => show_error_and_wait(&format!("Backup failed: {err}")),
+                    },
                 }
             }
+            MainAction::MountRepo => {
+                let pass = ensure_passphrase_cached(passphrase_cache, &repo)?;
+                mount_with_prompt(&repo, None, &mut mount_state, pass.as_deref(), &theme)?;
+            }
+            MainAction::Mounts => manage_mounts(&repo, &mut mount_state, &theme)?,
             MainAction::BackRepo => return Ok(InteractiveOutcome::ChangeRepo),
             MainAction::Quit => return Ok(InteractiveOutcome::Quit),
         }
     }
 }
 
-pub fn browse_files(
-    host: &str,
+/// List dead mounts and leftover directories (see [`mounts::find_stale`]) and offer to
+/// unmount and remove them. Without a terminal they are only reported.
+pub fn offer_stale_cleanup(repo: &RepoCtx, theme: &ColorfulTheme) -> Result<()> {
+    let records_path = mounts::default_mounts_path();
+    let stale = mounts::find_stale(
+        &repo.mount_root,
+        &mounts::load(&records_path).unwrap_or_default(),
+    );
+    if stale.is_empty() {
+        return Ok(());
+    }
+    eprintln!("Stale mountpoints:");
+    for entry in &stale {
+        eprintln!("  {}  {}", entry.path.display(), entry.kind.describe());
+    }
+    if non_interactive() || !std::io::stdin().is_terminal() {
+        eprintln!(
+            "Unmount dead mounts with `fusermount -u <path>`, then remove the empty directories."
+        );
+        return Ok(());
+    }
+    if !Confirm::with_theme(theme)
+        .with_prompt("Unmount and remove them?")
+        .default(true)
+        .interact()?
+    {
+        return Ok(());
+    }
+    for entry in &stale {
+        match mounts::clean_stale(entry, &repo.mount_root, &records_path) {
+            Ok(()) => println!("Cleaned up {}", entry.path.display()),
+            Err(err) => log::warn!("could not clean up {}: {:#}", entry.path.display(), err),
+        }
+    }
+    Ok(())
+}
+
+/// Mounts of `repo` recorded by this or earlier runs, oldest first.
+fn recorded_mounts(repo: &RepoCtx) -> Vec<MountRecord> {
+    let mut records = mounts::load(&mounts::default_mounts_path()).unwrap_or_default();
+    records.retain(|r| r.repo == repo.name);
+    records
+}
+
+/// List the recorded mounts of `repo` and unmount the chosen one (or all of them).
+fn manage_mounts(
     repo: &RepoCtx,
-    archive: &BorgArchive,
-    passphrase: Option<&str>,
+    mount_state: &mut Option<MountInfo>,
     theme: &ColorfulTheme,
-    mount_available: bool,
-    mount: Option<&MountInfo>,
 ) -> Result<()> {
-    loop {
-        show_step_with_ctx(
-            "Browse files",
-            &[
-                format!("Repo: {} ({})", repo.name, repo.repo),
-                format!(
-                    "Archive: {} [{}]",
-                    archive.name,
-                    archive.time_utc.as_deref().unwrap_or("-")
-                ),
-            ],
-            host,
-            repo,
-            mount_available,
-            mount,
-        )?;
-        let items = list_items(repo, &archive.name, passphrase)?;
-        if items.is_empty() {
-            println!("No files in archive {}", archive.name);
-            return Ok(());
+    let records = recorded_mounts(repo);
+    let mut labels: Vec<String> = records
+        .iter()
+        .map(|r| format!("Unmount {} @ {}", r.label(), r.mountpoint.display()))
+        .collect();
+    labels.push("Unmount all".to_string());
+    labels.push("Back".to_string());
+    let choice = Select::with_theme(theme)
+        .with_prompt("Mounts")
+        .items(&labels)
+        .default(0)
+        .interact_opt()?;
+    let chosen: Vec<&MountRecord> = match choice {
+        Some(idx) if idx < records.len() => vec![&records[idx]],
+        Some(idx) if idx == records.len() => records.iter().collect(),
+        _ => return Ok(()),
+    };
+    for record in chosen {
+        // borg umount only talks to FUSE, no passphrase needed
+        match umount_archive(repo, &record.mountpoint, None, false) {
+            Ok(()) => {
+                println!("Unmounted {}", record.mountpoint.display());
+                if mount_state
+                    .as_ref()
+                    .is_some_and(|m| m.mountpoint == record.mountpoint)
+                {
+                    *mount_state = None;
+                }
+            }
+            Err(err) => show_error_and_wait(&format!(
+                "Could not unmount {}: {:#}",
+                record.mountpoint.display(),
+                err
+            )),
         }
+    }
+    Ok(())
+}
 
-        let item = match select_item(&items, theme)? {
-            Some(i) => i,
-            None => return Ok(()), // back to archive list
+/// Ask for a mountpoint and mount `archive` (`None`: the whole repository), offering to
+/// unmount whatever is mounted already. Returns without mounting if the user keeps it.
+fn mount_with_prompt(
+    repo: &RepoCtx,
+    archive: Option<&str>,
+    mount_state: &mut Option<MountInfo>,
+    pass: Option<&str>,
+    theme: &ColorfulTheme,
+) -> Result<()> {
+    if let Some(active) = mount_state.as_ref() {
+        if !Confirm::with_theme(theme)
+            .with_prompt(format!(
+                "Unmount current ({}) before mounting new one?",
+                active.mountpoint.display()
+            ))
+            .default(true)
+            .interact()?
+        {
+            return Ok(());
+        }
+        umount_archive(repo, &active.mountpoint, pass, false)?;
+        println!("Unmounted {}", active.mountpoint.display());
+        *mount_state = None;
+    }
+
+    offer_stale_cleanup(repo, theme)?;
+    let default_mp = default_mountpoint(repo, archive);
+    let target: String = Input::with_theme(theme)
+        .with_prompt("Mountpoint")
+        .default(default_mp.display().to_string())
+        .interact_text()?;
+    let target_path = PathBuf::from(target);
+    mount_archive(repo, archive, &target_path, pass)?;
+    let info = MountInfo {
+        archive: archive.map(str::to_string),
+        // the form the mount records use, so "Mounts" can match it
+        mountpoint: std::fs::canonicalize(&target_path).unwrap_or(target_path),
+    };
+    println!("Mounted {} at {}", info.label(), info.mountpoint.display());
+    let follow_up = select_mount_follow_up(theme)?;
+    if follow_up_mount(repo, &info.mountpoint, follow_up, pass, theme)? {
+        *mount_state = Some(info);
+    }
+    Ok(())
+}
+
+/// What to do with a mount right after creating it (`mount --open/--shell`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MountFollowUp {
+    FileManager,
+    Shell,
+    Nothing,
+}
+
+fn select_mount_follow_up(theme: &ColorfulTheme) -> Result<MountFollowUp> {
+    let options = ["Open in file manager", "Open a shell there", "Continue"];
+    let choice = Select::with_theme(theme)
+        .with_prompt("Mounted. Now?")
+        .items(options)
+        .default(2)
+        .interact_opt()?;
+    Ok(match choice {
+        Some(0) => MountFollowUp::FileManager,
+        Some(1) => MountFollowUp::Shell,
+        _ => MountFollowUp::Nothing,
+    })
+}
+
+/// Run `follow_up` on the fresh mount at `mountpoint`. After a subshell exits the user is
+/// offered to unmount; returns whether it is still mounted.
+pub fn follow_up_mount(
+    repo: &RepoCtx,
+    mountpoint: &Path,
+    follow_up: MountFollowUp,
+    pass: Option<&str>,
+    theme: &ColorfulTheme,
+) -> Result<bool> {
+    match follow_up {
+        MountFollowUp::Nothing => {}
+        MountFollowUp::FileManager => {
+            let opener = if cfg!(target_os = "macos") {
+                "open"
+            } else {
+                "xdg-open"
+            };
+            let status = std::process::Command::new(opener)
+                .arg(mountpoint)
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .status()
+                .with_context(|| format!("Cannot start {}", opener))?;
+            if !status.success() {
+                anyhow::bail!("{} {} failed: {}", opener, mountpoint.display(), status);
+            }
+        }
+        MountFollowUp::Shell => {
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+            println!(
+                "Starting {} in {}; exit the shell to return to borg-tool.",
+                shell,
+                mountpoint.display()
+            );
+            run_foreground(std::process::Command::new(&shell).current_dir(mountpoint))
+                .with_context(|| format!("Cannot start shell {}", shell))?;
+            if !non_interactive()
+                && Confirm::with_theme(theme)
+                    .with_prompt(format!("Unmount {}?", mountpoint.display()))
+                    .default(true)
+                    .interact()?
+            {
+                umount_archive(repo, mountpoint, pass, false)?;
+                println!("Unmounted {}", mountpoint.display());
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Directory-by-directory browser: each level is listed on first visit and kept for the
+/// rest of the session, so even archives with millions of items open quickly.
+pub fn browse_files(
+    host: &str,
+    repo: &RepoCtx,
+    archive: &BorgArchive,
+    passphrase: Option<&str>,
+    theme: &ColorfulTheme,
+    mount_available: bool,
+    mount: Option<&MountInfo>,
+) -> Result<()> {
+    let mut listings: HashMap<String, Vec<BorgItem>> = HashMap::new();
+    let mut dir = String::new();
+    loop {
+        if !listings.contains_key(&dir) {
+            match load_directory(repo, &archive.name, &dir, passphrase, &mut listings) {
+                Ok(()) => {}
+                Err(err) if is_cancelled(&err) => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+        let entries = &listings[&dir];
+        if dir.is_empty() && entries.is_empty() {
+            println!("No files in archive {}", archive.name);
+            return Ok(());
+        }
+        show_step_with_ctx(
+            "Browse files",
+            &[
+                format!("Repo: {} ({})", repo.name, repo.repo),
+                format!(
+                    "Archive: {} [{}]",
+                    archive.name,
+                    archive.time_utc.as_deref().unwrap_or("-")
+                ),
+                format!("Path: {}", breadcrumbs(&dir)),
+            ],
+            host,
+            repo,
+            mount_available,
+            mount,
+        )?;
+
+        let target = match select_browse_entry(&dir, entries, theme)? {
+            BrowseChoice::Up => {
+                dir = dir
+                    .rsplit_once('/')
+                    .map(|(p, _)| p.to_string())
+                    .unwrap_or_default();
+                continue;
+            }
+            BrowseChoice::Enter(path) => {
+                dir = path;
+                continue;
+            }
+            BrowseChoice::File(item) if item.item_type.as_deref() == Some("-") => {
+                let action = Select::with_theme(theme)
+                    .with_prompt(format!("{} (Back to return)", item.path))
+                    .items(["View", "Extract…", "Back"])
+                    .default(0)
+                    .interact_opt()?;
+                match action {
+                    Some(0) => {
+                        view_file(repo, &archive.name, &item, passphrase)?;
+                        continue;
+                    }
+                    Some(1) => item.path,
+                    _ => continue,
+                }
+            }
+            BrowseChoice::File(item) => item.path,
+            BrowseChoice::ExtractDir => dir.clone(),
+            BrowseChoice::Back => return Ok(()), // back to archive list
         };
 
         if Confirm::with_theme(theme)
             .with_prompt(format!(
                 "Extract '{}' from '{}' to current directory?",
-                item.path, archive.name
+                target, archive.name
             ))
             .default(false)
             .interact()?
@@ -676,12 +1460,367 @@ pub fn browse_files(
                 .with_prompt("Destination directory")
                 .default(".".to_string())
                 .interact_text()?;
-            crate::borg::extract_file(repo, &archive.name, &item.path, &dest, passphrase)?;
+            crate::borg::extract_file(repo, &archive.name, &target, &dest, passphrase)?;
             println!("Extracted to {}", dest);
         }
     }
 }
 
+/// Files up to this size are read in one go and shown inline when they fit the screen.
+const INLINE_VIEW_BYTES: u64 = 64 * 1024;
+
+/// Show a file's content via `borg extract --stdout`: small text files inline, anything
+/// else streamed into `$PAGER` (default `less`).
+fn view_file(
+    repo: &RepoCtx,
+    archive: &str,
+    item: &BorgItem,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let term = Term::stdout();
+    if item.size.is_some_and(|size| size <= INLINE_VIEW_BYTES) {
+        let content = with_spinner(&format!("Reading {}", item.path), |_pb| {
+            extract_to_stdout(repo, archive, &item.path, passphrase)
+        })?;
+        if looks_binary(&content) {
+            term.write_line(&format!(
+                "{} looks like a binary file ({}); extract it instead.",
+                item.path,
+                human_size(content.len() as u64)
+            ))?;
+            return wait_for_key(&term);
+        }
+        let text = String::from_utf8_lossy(&content);
+        let (rows, _) = term.size();
+        if text.lines().count() + 2 < rows as usize {
+            clear_screen(&term)?;
+            term.write_line(&format!("── {} ({})", item.path, archive))?;
+            term.write_str(&text)?;
+            if !text.ends_with('\n') {
+                term.write_line("")?;
+            }
+            return wait_for_key(&term);
+        }
+    }
+
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let mut child = match std::process::Command::new(words.next().unwrap_or("less"))
+        .args(words)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            term.write_line(&format!(
+                "Cannot start pager '{}': {}; set $PAGER or extract the file instead.",
+                pager, err
+            ))?;
+            return wait_for_key(&term);
+        }
+    };
+    let mut stdin = child.stdin.take().context("Pager stdin")?;
+    let mut pager_closed = false;
+    let streamed = extract_streaming(repo, archive, &item.path, passphrase, |line| {
+        use std::io::Write;
+        if writeln!(stdin, "{}", line).is_err() {
+            // the pager quit before the end of the file
+            pager_closed = true;
+            anyhow::bail!("pager closed");
+        }
+        Ok(())
+    });
+    drop(stdin);
+    child.wait().context("Wait for pager")?;
+    match streamed {
+        Err(_) if pager_closed => Ok(()),
+        other => other,
+    }
+}
+
+fn wait_for_key(term: &Term) -> Result<()> {
+    term.write_line("(press any key to return)")?;
+    term.read_key()?;
+    Ok(())
+}
+
+/// Fill `listings[dir]`, directories first. An empty level may just consist of directories
+/// that were never archived themselves (e.g. `home` for an include of `/home/u/docs`); then
+/// the whole subtree is listed once and every level below `dir` is cached from it.
+pub(crate) fn load_directory(
+    repo: &RepoCtx,
+    archive: &str,
+    dir: &str,
+    passphrase: Option<&str>,
+    listings: &mut HashMap<String, Vec<BorgItem>>,
+) -> Result<()> {
+    let mut entries = list_directory(repo, archive, dir, passphrase)?;
+    if entries.is_empty() {
+        let items = list_subtree(repo, archive, dir, passphrase)?;
+        let root = crate::tree::build(&items);
+        if let Some(node) = root.find(dir) {
+            cache_tree_listings(node, dir, listings);
+        }
+        entries = listings.remove(dir).unwrap_or_default();
+    }
+    entries.sort_by(|a, b| {
+        is_dir_item(b)
+            .cmp(&is_dir_item(a))
+            .then(a.path.cmp(&b.path))
+    });
+    listings.insert(dir.to_string(), entries);
+    Ok(())
+}
+
+fn cache_tree_listings(node: &TreeNode, dir: &str, listings: &mut HashMap<String, Vec<BorgItem>>) {
+    for child in node.children.values().filter(|c| c.is_dir()) {
+        cache_tree_listings(child, &crate::tree::join_path(dir, &child.name), listings);
+    }
+    let mut entries = node.child_items(dir);
+    entries.sort_by(|a, b| {
+        is_dir_item(b)
+            .cmp(&is_dir_item(a))
+            .then(a.path.cmp(&b.path))
+    });
+    listings.insert(dir.to_string(), entries);
+}
+
+fn compare_archives(
+    host: &str,
+    repo: &RepoCtx,
+    (first, second): (&BorgArchive, &BorgArchive),
+    passphrase: Option<&str>,
+    theme: &ColorfulTheme,
+    mount_available: bool,
+    mount: Option<&MountInfo>,
+) -> Result<()> {
+    let time = |a: &BorgArchive| a.time_utc.as_deref().and_then(parse_archive_time);
+    let (older, newer) = if time(second) < time(first) {
+        (second, first)
+    } else {
+        (first, second)
+    };
+    let entries = match diff_archives(repo, &older.name, &newer.name, passphrase) {
+        Ok(entries) => entries,
+        Err(err) if is_cancelled(&err) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let groups = [
+        ("Added", DiffKind::Added),
+        ("Removed", DiffKind::Removed),
+        ("Changed", DiffKind::Changed),
+    ]
+    .map(|(label, kind)| {
+        let paths: Vec<String> = entries
+            .iter()
+            .filter(|e| e.kind == kind)
+            .map(describe_diff_entry)
+            .collect();
+        (label, paths)
+    });
+
+    loop {
+        show_step_with_ctx(
+            "Compare archives",
+            &[
+                format!(
+                    "Older: {} [{}]",
+                    older.name,
+                    older.time_utc.as_deref().unwrap_or("-")
+                ),
+                format!(
+                    "Newer: {} [{}]",
+                    newer.name,
+                    newer.time_utc.as_deref().unwrap_or("-")
+                ),
+                groups
+                    .iter()
+                    .map(|(label, paths)| format!("{}: {}", label, paths.len()))
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+            ],
+            host,
+            repo,
+            mount_available,
+            mount,
+        )?;
+        let mut options: Vec<String> = groups
+            .iter()
+            .map(|(label, paths)| format!("{} ({})", label, paths.len()))
+            .collect();
+        options.push("Back".to_string());
+        let choice = Select::with_theme(theme)
+            .with_prompt("Show which changes? (Back to return)")
+            .items(&options)
+            .default(0)
+            .interact_opt()?;
+        let (label, paths) = match choice {
+            Some(idx) if idx < groups.len() => &groups[idx],
+            _ => return Ok(()),
+        };
+        if paths.is_empty() {
+            continue;
+        }
+        Select::with_theme(theme)
+            .with_prompt(format!("{} paths (Enter/Esc to return)", label))
+            .items(paths)
+            .max_length(20)
+            .default(0)
+            .interact_opt()?;
+    }
+}
+
+/// Path plus borg's change types (for changed paths) and the size delta.
+fn describe_diff_entry(entry: &DiffEntry) -> String {
+    let mut line = entry.path.clone();
+    if entry.kind == DiffKind::Changed {
+        line.push_str(&format!("  [{}]", entry.changes.join(", ")));
+    }
+    if entry.size_delta != 0 {
+        line.push_str(&format!("  ({})", human_size_delta(entry.size_delta)));
+    }
+    line
+}
+
+fn human_size_delta(delta: i64) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!("{}{}", sign, human_size(delta.unsigned_abs()))
+}
+
+pub fn print_diff(entries: &[DiffEntry]) {
+    if entries.is_empty() {
+        println!("No differences");
+        return;
+    }
+    for entry in entries {
+        let marker = match entry.kind {
+            DiffKind::Added => '+',
+            DiffKind::Removed => '-',
+            DiffKind::Changed => '~',
+        };
+        println!("{} {}", marker, describe_diff_entry(entry));
+    }
+    let count = |kind: DiffKind| entries.iter().filter(|e| e.kind == kind).count();
+    println!(
+        "{} added, {} removed, {} changed (net {})",
+        count(DiffKind::Added),
+        count(DiffKind::Removed),
+        count(DiffKind::Changed),
+        human_size_delta(entries.iter().map(|e| e.size_delta).sum())
+    );
+}
+
+/// Disaster-recovery flow: archive → target root → review → extract → next steps.
+pub fn restore_system_wizard(
+    repo: &RepoCtx,
+    passphrase: Option<&str>,
+    theme: &ColorfulTheme,
+) -> Result<()> {
+    ensure_can_prompt("restore-system is an interactive wizard")?;
+    let repo_line = format!("Repo: {} ({})", repo.name, repo.repo);
+    show_step(
+        "Restore system (1/3): choose archive",
+        std::slice::from_ref(&repo_line),
+    )?;
+    let archives = list_archives(repo, passphrase)?;
+    if archives.is_empty() {
+        println!("No archives in {}", repo.name);
+        return Ok(());
+    }
+    let Some(archive) = select_archive(&archives, theme)? else {
+        return Ok(());
+    };
+
+    show_step(
+        "Restore system (2/3): choose target root",
+        &[
+            repo_line.clone(),
+            format!("Archive: {}", archive.name),
+            "Mount the new root file system first (e.g. mount /dev/sdX2 /mnt).".to_string(),
+        ],
+    )?;
+    let target_raw: String = Input::with_theme(theme)
+        .with_prompt("Target root directory")
+        .default("/mnt".to_string())
+        .validate_with(|input: &String| -> Result<(), &str> {
+            let path = Path::new(input.trim());
+            if !path.is_absolute() {
+                return Err("Use an absolute path");
+            }
+            if !path.is_dir() {
+                return Err("Directory does not exist");
+            }
+            Ok(())
+        })
+        .interact_text()?;
+    let target = PathBuf::from(target_raw.trim());
+
+    let items = list_items(repo, &archive.name, passphrase)?;
+    let total: u64 = items.iter().filter_map(|i| i.size).sum();
+    let target_empty = std::fs::read_dir(&target)
+        .with_context(|| format!("Read {}", target.display()))?
+        .filter_map(|e| e.ok())
+        .all(|e| e.file_name() == "lost+found");
+    let mut review = vec![
+        repo_line,
+        format!(
+            "Archive: {} [{}]",
+            archive.name,
+            archive.time_utc.as_deref().unwrap_or("-")
+        ),
+        format!("Target:  {}", target.display()),
+        format!("Content: {} entries, {}", items.len(), human_size(total)),
+        "borg extract --numeric-ids --sparse (owners restored by numeric UID/GID)".to_string(),
+    ];
+    if !target_empty {
+        review.push("WARNING: target is not empty; existing files will be overwritten.".into());
+    }
+    if target == Path::new("/") {
+        review.push("WARNING: target is the running system's root.".into());
+    }
+    if !running_as_root() {
+        review.push(
+            "WARNING: not running as root; ownership and special files cannot be restored. Re-run with sudo."
+                .into(),
+        );
+    }
+    show_step("Restore system (3/3): review", &review)?;
+
+    let typed: String = Input::with_theme(theme)
+        .with_prompt("Type the archive name to start the restore (empty to cancel)")
+        .allow_empty(true)
+        .interact_text()?;
+    if typed.trim() != archive.name {
+        println!("Restore cancelled.");
+        return Ok(());
+    }
+
+    restore_archive(repo, &archive.name, &target, passphrase)?;
+
+    let root = target.display();
+    println!("Restored {} into {}.", archive.name, root);
+    println!();
+    println!("Next steps:");
+    for step in [
+        format!(
+            "Recreate excluded mount points if missing: mkdir -p {root}/{{proc,sys,dev,run,tmp}}"
+        ),
+        format!("Check {root}/etc/fstab (and crypttab) against the new disks' UUIDs from `blkid`"),
+        format!(
+            "Reinstall the boot loader from a chroot: for d in dev proc sys run; do mount --rbind /$d {root}/$d; done; chroot {root}"
+        ),
+        "  then e.g. grub-install /dev/sdX && update-grub && update-initramfs -u -k all"
+            .to_string(),
+        "Unmount everything, reboot, and verify services and logs".to_string(),
+    ] {
+        println!("  - {}", step);
+    }
+    Ok(())
+}
+
 fn migrate_legacy_repo(cfg: &mut Config) {
     if cfg.repos.is_empty()
         && let Some(legacy) = cfg.repo.take()
@@ -689,9 +1828,7 @@ fn migrate_legacy_repo(cfg: &mut Config) {
         cfg.repos.push(RepoConfig {
             name: "default".to_string(),
             repo: legacy,
-            borg_bin: None,
-            mount_root: None,
-            backups: Vec::new(),
+            ..Default::default()
         });
     }
 }
@@ -739,7 +1876,7 @@ fn setup_new_repo_wizard(
 
     let borg_bin_input: String = Input::with_theme(theme)
         .with_prompt("borg binary")
-        .default(cfg.borg_bin.clone())
+        .default(cfg.borg_bin.to_string())
         .interact_text()?;
 
     let mount_root_input: String = Input::with_theme(theme)
@@ -747,6 +1884,7 @@ fn setup_new_repo_wizard(
         .default(cfg.mount_root.display().to_string())
         .interact_text()?;
     let mount_root = PathBuf::from(mount_root_input);
+    let borg_bin = BorgBin::from_input(&borg_bin_input);
 
     let enc_options = ["repokey (encrypted, recommended)", "none (no encryption)"];
     let enc_choice = Select::with_theme(theme)
@@ -785,10 +1923,16 @@ fn setup_new_repo_wizard(
     let ctx = RepoCtx {
         name: name.clone(),
         repo: repo_path.clone(),
-        borg_bin: borg_bin_input.clone(),
+        borg_bin: borg_bin.clone(),
         mount_root: mount_root.clone(),
         backups: Vec::new(),
         status: RepoStatus::Unknown,
+        global_args: cfg.global_borg_args.clone(),
+        lock_wait: cfg.lock_wait_seconds,
+        retries: cfg.retries,
+        notifications: cfg.notifications.clone(),
+        history_file: Some(default_history_path()),
+        ..Default::default()
     };
 
     if let Err(err) = init_repo(&ctx, &encryption, passphrase.as_deref()) {
@@ -803,24 +1947,21 @@ fn setup_new_repo_wizard(
     cfg.repos.push(RepoConfig {
         name: name.clone(),
         repo: repo_path,
-        borg_bin: if borg_bin_input == cfg.borg_bin || borg_bin_input == default_borg_bin() {
+        borg_bin: if borg_bin == cfg.borg_bin || borg_bin == default_borg_bin() {
             None
         } else {
-            Some(borg_bin_input)
+            Some(borg_bin)
         },
         mount_root: if mount_root == cfg.mount_root || mount_root == default_mount_root() {
             None
         } else {
             Some(mount_root)
         },
-        backups: Vec::new(),
+        ..Default::default()
     });
 
     if Confirm::with_theme(theme)
-        .with_prompt(format!(
-            "Save updated config to {}? (comments will be lost)",
-            config_path.display()
-        ))
+        .with_prompt(format!("Save updated config to {}?", config_path.display()))
         .default(true)
         .interact()?
     {
@@ -960,6 +2101,7 @@ fn setup_backup_preset_wizard(
         one_file_system,
         exclude_caches,
         archive_prefix,
+        ..Default::default()
     }))
 }
 
@@ -971,10 +2113,7 @@ fn add_preset_to_config(cfg: &mut Config, repo_name: &str, preset: BackupConfig)
 
 fn maybe_save_config(cfg: &Config, config_path: &Path, theme: &ColorfulTheme) -> Result<()> {
     if Confirm::with_theme(theme)
-        .with_prompt(format!(
-            "Save updated config to {}? (comments will be lost)",
-            config_path.display()
-        ))
+        .with_prompt(format!("Save updated config to {}?", config_path.display()))
         .default(true)
         .interact()?
     {
@@ -997,6 +2136,110 @@ mod tests {
         assert_eq!(res, vec!["/home", "/var/log", "tmp"]);
     }
 
+    #[test]
+    fn item_line_shows_ls_style_columns() {
+        let item = BorgItem {
+            path: "etc/hosts".into(),
+            item_type: Some("-".into()),
+            size: Some(2048),
+            mtime: Some("2025-01-02T03:04:05.000000".into()),
+            mode: Some("-rw-r--r--".into()),
+            user: Some("root".into()),
+            group: Some("root".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            item_line(&item, false),
+            "-rw-r--r-- root:root            2.0 KiB 2025-01-02 03:04 etc/hosts"
+        );
+
+        let bare = BorgItem {
+            path: "etc".into(),
+            item_type: Some("d".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            item_line(&bare, false),
+            "d          -:-                                           etc"
+        );
+    }
+
+    #[test]
+    fn format_item_fills_placeholders() {
+        let template = crate::template::parse_line_format(
+            r"{path}\t{size}\t{hsize}\t{user}",
+            crate::template::FILES_FORMAT_VARS,
+        )
+        .unwrap();
+        let item = BorgItem {
+            path: "var/db.sql".into(),
+            size: Some(4096),
+            ..Default::default()
+        };
+        assert_eq!(format_item(&template, &item), "var/db.sql\t4096\t4.0 KiB\t");
+    }
+
+    #[test]
+    fn breadcrumbs_start_at_archive_root() {
+        assert_eq!(breadcrumbs(""), "/");
+        assert_eq!(breadcrumbs("etc/ssh"), "/ > etc > ssh");
+    }
+
+    #[test]
+    fn human_size_uses_binary_units() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[test]
+    fn describe_diff_entry_shows_changes_and_delta() {
+        let entry = DiffEntry {
+            path: "etc/hosts".into(),
+            kind: DiffKind::Changed,
+            changes: vec!["modified".into(), "mode".into()],
+            size_delta: -2048,
+        };
+        assert_eq!(
+            describe_diff_entry(&entry),
+            "etc/hosts  [modified, mode]  (-2.0 KiB)"
+        );
+        assert_eq!(human_size_delta(12), "+12 B");
+    }
+
+    #[test]
+    fn human_duration_picks_largest_units() {
+        assert_eq!(human_duration(42), "42s");
+        assert_eq!(human_duration(307), "5m07s");
+        assert_eq!(human_duration(3 * 3600 + 125), "3h02m");
+    }
+
+    #[test]
+    fn archive_cache_reuses_until_invalidated() {
+        let mut cache = ArchiveCache::default();
+        assert!(!cache.is_fresh());
+        cache.store(vec![
+            BorgArchive {
+                name: "home-1".into(),
+                time_utc: None,
+                ..Default::default()
+            },
+            BorgArchive {
+                name: "home-2".into(),
+                time_utc: None,
+                ..Default::default()
+            },
+        ]);
+        assert!(cache.is_fresh());
+        cache.forget("home-1");
+        assert_eq!(cache.archives().len(), 1);
+        assert_eq!(cache.archives()[0].name, "home-2");
+        cache.invalidate();
+        assert!(!cache.is_fresh());
+        assert!(cache.archives().is_empty());
+    }
+
     #[test]
     fn parse_list_handles_empty() {
         let res = parse_list("   ");
@@ -1004,6 +2247,143 @@ mod tests {
     }
 }
 
+pub fn confirm_delete(count: usize, keep: usize, theme: &ColorfulTheme) -> Result<bool> {
+    ensure_can_prompt("Deleting needs confirmation; pass --yes")?;
+    Ok(Confirm::with_theme(theme)
+        .with_prompt(format!(
+            "Delete {} archive(s), keeping the newest {}?",
+            count, keep
+        ))
+        .default(false)
+        .interact()?)
+}
+
+/// Ask the user to type the archive name (or the number of archives) before deleting.
+pub fn confirm_delete_archives(names: &[String], theme: &ColorfulTheme) -> Result<bool> {
+    ensure_can_prompt("Deleting needs confirmation; pass --yes")?;
+    let expected = match names {
+        [single] => single.clone(),
+        _ => names.len().to_string(),
+    };
+    let prompt = match names {
+        [single] => format!("Type '{}' to delete it (empty to cancel)", single),
+        _ => format!(
+            "Type {} to delete these {} archives (empty to cancel)",
+            expected, expected
+        ),
+    };
+    let answer: String = Input::with_theme(theme)
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .interact_text()?;
+    Ok(answer.trim() == expected)
+}
+
+/// Ask for the archive paths `restore` should put back.
+pub fn prompt_restore_paths(theme: &ColorfulTheme) -> Result<Vec<String>> {
+    ensure_can_prompt("No paths given; pass the paths to restore")?;
+    let raw: String = Input::with_theme(theme)
+        .with_prompt("Paths to restore (space separated, e.g. etc/nginx home/u/.bashrc)")
+        .allow_empty(true)
+        .interact_text()?;
+    Ok(raw.split_whitespace().map(str::to_string).collect())
+}
+
+/// What `restore` is about to do: item count, owner handling and the files it replaces.
+pub fn print_restore_plan(
+    archive: &str,
+    items: &[BorgItem],
+    overwritten: &[PathBuf],
+    numeric_ids: bool,
+) {
+    const SHOWN: usize = 20;
+    let total: u64 = items.iter().filter_map(|i| i.size).sum();
+    println!(
+        "Restoring {} item(s), {} from {} to their original locations",
+        items.len(),
+        human_size(total),
+        archive
+    );
+    println!(
+        "Owners: {}",
+        match (running_as_root(), numeric_ids) {
+            (false, _) => "not restored (not running as root; files will belong to you)",
+            (true, false) => "restored by user/group name",
+            (true, true) => "restored by numeric UID/GID",
+        }
+    );
+    if overwritten.is_empty() {
+        println!("No existing files are replaced.");
+        return;
+    }
+    println!(
+        "Existing files that will be replaced ({}):",
+        overwritten.len()
+    );
+    for path in overwritten.iter().take(SHOWN) {
+        println!("  {}", path.display());
+    }
+    if overwritten.len() > SHOWN {
+        println!("  ... and {} more", overwritten.len() - SHOWN);
+    }
+}
+
+pub fn confirm_restore(count: usize, overwritten: usize, theme: &ColorfulTheme) -> Result<bool> {
+    ensure_can_prompt("Restoring needs confirmation; pass --yes")?;
+    Ok(Confirm::with_theme(theme)
+        .with_prompt(format!(
+            "Restore {} item(s), replacing {} existing file(s)?",
+            count, overwritten
+        ))
+        .default(false)
+        .interact()?)
+}
+
+/// Show who holds the repository lock and offer `borg break-lock`.
+fn offer_break_lock(repo: &RepoCtx, locked: &RepoLocked, theme: &ColorfulTheme) -> Result<()> {
+    ensure_can_prompt("The repository is locked")?;
+    if locked.holders.is_empty() {
+        eprintln!("Lock holder unknown (the lock roster is on the repository host).");
+    }
+    let mut stale = !locked.holders.is_empty();
+    for holder in &locked.holders {
+        let state = match holder.running_here() {
+            Some(true) => "still running",
+            Some(false) => "no longer running",
+            None => "on another host",
+        };
+        stale &= holder.running_here() == Some(false);
+        eprintln!("Lock held by {}: {}", holder, state);
+    }
+    let prompt = if stale {
+        "The lock is stale. Break it?"
+    } else {
+        "Break the lock anyway? Only do this if no borg process uses the repository"
+    };
+    if !Confirm::with_theme(theme)
+        .with_prompt(prompt)
+        .default(stale)
+        .interact()?
+    {
+        return Ok(());
+    }
+    break_lock(repo)?;
+    println!("Removed the lock of {}", repo.name);
+    Ok(())
+}
+
+pub fn confirm_repair(repo: &str, theme: &ColorfulTheme) -> Result<bool> {
+    ensure_can_prompt("check --repair needs confirmation; pass --yes")?;
+    eprintln!(
+        "borg check --repair may remove damaged data from '{}'. Make sure you have a copy of the repository first.",
+        repo
+    );
+    Ok(Confirm::with_theme(theme)
+        .with_prompt("Run the repair?")
+        .default(false)
+        .interact()?)
+}
+
 pub fn print_archives(archives: &[BorgArchive]) {
     if archives.is_empty() {
         println!("No archives found");
@@ -1011,24 +2391,549 @@ pub fn print_archives(archives: &[BorgArchive]) {
     }
     for arch in archives {
         let time = arch.time_utc.as_deref().unwrap_or("-");
-        println!("{:<40} {}", arch.name, time);
+        if arch.comment.is_empty() {
+            println!("{:<40} {}", arch.name, time);
+        } else {
+            println!("{:<40} {:<19}  {}", arch.name, time, arch.comment);
+        }
     }
 }
 
-pub fn print_items(items: &[BorgItem]) {
-    if items.is_empty() {
+/// `find` hits grouped by archive, newest archive first.
+pub fn print_find_report(report: &FindReport) {
+    let mut archives = 0;
+    let mut last_archive = None;
+    for hit in &report.hits {
+        if last_archive != Some(&hit.archive) {
+            archives += 1;
+            last_archive = Some(&hit.archive);
+            println!("{}  [{}]", hit.archive, hit.time.as_deref().unwrap_or("-"));
+        }
+        println!(
+            "  {:>10}  {:<19}  {}",
+            hit.size.map(human_size).unwrap_or_default(),
+            hit.mtime.as_deref().unwrap_or("-"),
+            hit.path
+        );
+    }
+    if archives == 0 {
+        println!("No matches in {} archive(s)", report.searched);
+    } else {
+        println!(
+            "{} match(es) in {} of {} archive(s)",
+            report.hits.len(),
+            archives,
+            report.searched
+        );
+    }
+}
+
+/// `list --format` line for `archive`.
+pub fn format_archive(template: &Template, archive: &BorgArchive) -> String {
+    template.render(chrono::Local::now(), |name| match name {
+        "name" => Some(archive.name.as_str()),
+        "time" => archive.time_utc.as_deref(),
+        "comment" => Some(archive.comment.as_str()),
+        _ => None,
+    })
+}
+
+/// `files --format` line for `item`; missing values expand to nothing.
+pub fn format_item(template: &Template, item: &BorgItem) -> String {
+    let size = item.size.map(|s| s.to_string());
+    let hsize = item.size.map(human_size);
+    template.render(chrono::Local::now(), |name| match name {
+        "path" => Some(item.path.as_str()),
+        "type" => item.item_type.as_deref(),
+        "mode" => item.mode.as_deref(),
+        "user" => item.user.as_deref(),
+        "group" => item.group.as_deref(),
+        "size" => size.as_deref(),
+        "hsize" => hsize.as_deref(),
+        "mtime" => item.mtime.as_deref(),
+        "checksum" => item.checksum(),
+        _ => None,
+    })
+}
+
+/// One line of the `files` listing; `with_checksum` adds the checksum column.
+pub fn print_item(item: &BorgItem, with_checksum: bool) {
+    println!("{}", item_line(item, with_checksum));
+}
+
+/// `ls -l` like columns: mode, owner, size, mtime, then the path.
+fn item_line(item: &BorgItem, with_checksum: bool) -> String {
+    let owner = format!(
+        "{}:{}",
+        item.user.as_deref().unwrap_or("-"),
+        item.group.as_deref().unwrap_or("-")
+    );
+    let mut line = format!(
+        "{:<10} {:<17} {:>10} {:<16} ",
+        item_mode(item),
+        owner,
+        item.size.map(human_size).unwrap_or_default(),
+        item.mtime.as_deref().map(short_mtime).unwrap_or_default()
+    );
+    if with_checksum {
+        line.push_str(&format!("{:<64} ", item.checksum().unwrap_or("-")));
+    }
+    line.push_str(&item.path);
+    line
+}
+
+/// The mode string, or just the item type when borg did not report one.
+pub(crate) fn item_mode(item: &BorgItem) -> &str {
+    item.mode
+        .as_deref()
+        .or(item.item_type.as_deref())
+        .unwrap_or("")
+}
+
+/// borg's `2025-01-01T10:00:00.000000` as `2025-01-01 10:00`.
+pub(crate) fn short_mtime(raw: &str) -> String {
+    parse_archive_time(raw)
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| raw.to_string())
+}
+
+pub fn print_tree(root: &TreeNode) {
+    if root.children.is_empty() {
         println!("No files in archive");
         return;
     }
+    println!("{} item(s), {}", root.items, human_size(root.size));
+    print_tree_children(root, "");
+}
+
+fn print_tree_children(node: &TreeNode, indent: &str) {
+    let count = node.children.len();
+    for (idx, child) in node.children.values().enumerate() {
+        let last = idx + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+        if child.is_dir() {
+            println!(
+                "{}{}{}/  ({} item(s), {})",
+                indent,
+                branch,
+                child.name,
+                child.items,
+                human_size(child.size)
+            );
+            let next = format!("{}{}", indent, if last { "    " } else { "│   " });
+            print_tree_children(child, &next);
+        } else {
+            println!(
+                "{}{}{}  ({})",
+                indent,
+                branch,
+                child.name,
+                human_size(child.size)
+            );
+        }
+    }
+}
+
+/// Pretty-printed JSON on stdout, for `--json`.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+pub fn print_backup_outcome(outcome: &BackupOutcome) {
+    print_backup_warnings(outcome);
+    if let Some(stats) = &outcome.stats {
+        let archive = &stats.archive.stats;
+        println!("  Files:              {}", archive.nfiles);
+        println!(
+            "  Original size:      {}",
+            human_size(archive.original_size)
+        );
+        println!(
+            "  Compressed size:    {}",
+            human_size(archive.compressed_size)
+        );
+        println!(
+            "  Deduplicated size:  {}",
+            human_size(archive.deduplicated_size)
+        );
+        println!(
+            "  Repository size:    {}",
+            human_size(stats.repo.unique_csize)
+        );
+        if let Some(duration) = stats.archive.duration {
+            println!(
+                "  Duration:           {}",
+                human_duration(duration.round() as u64)
+            );
+        }
+    }
+    if let Some(verification) = &outcome.verification {
+        let what = if verification.verify_data {
+            "metadata and data"
+        } else {
+            "metadata"
+        };
+        match &verification.error {
+            None => println!("Verified {} of '{}': ok", what, outcome.archive),
+            Some(err) => println!("Verification of '{}' FAILED: {}", outcome.archive, err),
+        }
+    }
+}
+
+/// `backup --dry-run`: the command, then borg's file list in its own `status path` format.
+pub fn print_backup_preview(preview: &BackupPreview) {
+    println!("{}", preview.command);
+    if let Some(files) = &preview.files {
+        for file in files {
+            println!("{} {}", file.status, file.path);
+        }
+        println!("{} path(s) would be archived", files.len());
+    }
+}
+
+fn print_backup_warnings(outcome: &BackupOutcome) {
+    if outcome.warnings.is_empty() {
+        println!("Backup '{}' completed", outcome.archive);
+        return;
+    }
+    println!(
+        "Backup '{}' completed with {} warning(s):",
+        outcome.archive,
+        outcome.warnings.len()
+    );
+    for (category, count) in outcome.warning_counts() {
+        println!("  {:>5} × {}", count, category);
+    }
+    const SHOWN: usize = 10;
+    for warning in outcome.warnings.iter().take(SHOWN) {
+        println!("  - {}", warning.message);
+    }
+    if outcome.warnings.len() > SHOWN {
+        println!("  … and {} more", outcome.warnings.len() - SHOWN);
+    }
+}
 
-    for item in items {
-        let kind = item.item_type.as_deref().unwrap_or("");
-        println!("{:<8} {}", kind, item.path);
+/// Summary table of `backup --all`, followed by the errors of failed presets.
+pub fn print_backup_runs(runs: &[BackupRun]) {
+    println!();
+    println!(
+        "{:<16} {:<16} {:<8} {:>8}  ARCHIVE",
+        "REPO", "PRESET", "STATUS", "TIME"
+    );
+    for run in runs {
+        let status = match (&run.error, run.warnings) {
+            (Some(_), _) => "failed",
+            (None, 0) => "ok",
+            (None, _) => "warnings",
+        };
+        println!(
+            "{:<16} {:<16} {:<8} {:>8}  {}",
+            run.repo,
+            run.preset,
+            status,
+            human_duration(run.duration_secs as u64),
+            run.archive.as_deref().unwrap_or("-")
+        );
+    }
+    for run in runs {
+        if let Some(err) = &run.error {
+            println!();
+            println!("{}/{} failed: {}", run.repo, run.preset, err);
+        }
+    }
+}
+
+pub fn print_status(statuses: &[PresetStatus]) {
+    if statuses.is_empty() {
+        println!("No backup presets configured");
+        return;
+    }
+    for status in statuses {
+        let name = format!("{}/{}", status.repo, status.preset);
+        let newest = match (&status.newest_time, status.age_secs) {
+            (Some(time), Some(age)) => format!("{} ({} ago)", time, human_age(age.max(0) as u64)),
+            _ => "no archive yet".to_string(),
+        };
+        let verdict = match (&status.error, status.stale, &status.max_age) {
+            (Some(err), _, _) => format!("ERROR: {}", err.lines().next().unwrap_or("")),
+            (None, true, Some(max_age)) => format!("STALE (max_age {})", max_age),
+            (None, _, Some(_)) => "ok".to_string(),
+            (None, _, None) => "no max_age".to_string(),
+        };
+        println!("{:<28} {:<36} {}", name, newest, verdict);
+    }
+}
+
+/// Like [`human_duration`], but in whole days from two days on.
+fn human_age(secs: u64) -> String {
+    if secs < 2 * 86400 {
+        human_duration(secs)
+    } else {
+        format!("{} days", secs / 86400)
+    }
+}
+
+pub fn print_history(entries: &[HistoryEntry]) {
+    if entries.is_empty() {
+        println!("No recorded runs");
+        return;
+    }
+    for entry in entries {
+        let started = chrono::DateTime::parse_from_rfc3339(&entry.started)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| entry.started.clone());
+        let status = if entry.success { "ok" } else { "failed" };
+        let detail = match &entry.archive {
+            Some(archive) if entry.success => archive.as_str(),
+            _ => entry.message.lines().next().unwrap_or(""),
+        };
+        println!(
+            "{}  {:<7} {:<12} {:<16} {:<7} {:>7}  {}",
+            started,
+            entry.action,
+            entry.repo,
+            entry.target,
+            status,
+            human_duration(entry.duration_secs as u64),
+            detail
+        );
+    }
+}
+
+pub fn print_info(report: &InfoReport) {
+    if let Some(archive) = &report.archive {
+        let stats = &archive.stats;
+        println!("Archive:            {}", archive.name);
+        if let Some(start) = &archive.start {
+            println!("Created:            {}", start);
+        }
+        if let Some(duration) = archive.duration {
+            println!(
+                "Duration:           {}",
+                human_duration(duration.max(0.0).round() as u64)
+            );
+        }
+        if let (Some(user), Some(host)) = (&archive.username, &archive.hostname) {
+            println!("Created by:         {}@{}", user, host);
+        }
+        println!("Files:              {}", stats.nfiles);
+        println!("Original size:      {}", human_size(stats.original_size));
+        println!(
+            "Compressed:         {}{}",
+            human_size(stats.compressed_size),
+            ratio(stats.compressed_size, stats.original_size)
+        );
+        println!(
+            "Deduplicated:       {} (only in this archive)",
+            human_size(stats.deduplicated_size)
+        );
+        println!();
+    }
+
+    let repo = &report.repo;
+    println!("Repository:         {}", report.location);
+    if let Some(mode) = &report.encryption {
+        println!("Encryption:         {}", mode);
+    }
+    println!("Original size:      {}", human_size(repo.total_size));
+    println!(
+        "Compressed:         {}{}",
+        human_size(repo.total_csize),
+        ratio(repo.total_csize, repo.total_size)
+    );
+    println!(
+        "Deduplicated:       {}{}",
+        human_size(repo.unique_csize),
+        ratio(repo.unique_csize, repo.total_size)
+    );
+    println!(
+        "Chunks:             {} unique, {} total",
+        repo.total_unique_chunks, repo.total_chunks
+    );
+}
+
+/// ` (42.0% of original)`, or nothing when the original size is unknown.
+fn ratio(part: u64, whole: u64) -> String {
+    if whole == 0 {
+        return String::new();
+    }
+    format!(" ({:.1}% of original)", part as f64 * 100.0 / whole as f64)
+}
+
+pub fn print_prune_report(preset: &str, report: &PruneReport, dry_run: bool) {
+    println!("Backup '{}':", preset);
+    for entry in &report.kept {
+        println!("  keep   {}", entry);
+    }
+    for entry in &report.pruned {
+        println!("  prune  {}", entry);
+    }
+    println!(
+        "{} archive(s) {}, {} kept",
+        report.pruned.len(),
+        if dry_run { "would be pruned" } else { "pruned" },
+        report.kept.len()
+    );
+}
+
+pub fn print_grep_report(report: &GrepReport) {
+    for file in &report.files {
+        for (idx, block) in file.blocks.iter().enumerate() {
+            if idx > 0 {
+                println!("--");
+            }
+            for line in block {
+                let sep = if line.is_match { ':' } else { '-' };
+                println!("{}{}{}{}{}", file.path, sep, line.number, sep, line.text);
+            }
+        }
+    }
+    let mut summary = format!(
+        "{} matching file(s), {} searched",
+        report.files.len(),
+        report.searched
+    );
+    if report.skipped_binary > 0 {
+        summary.push_str(&format!(", {} binary skipped", report.skipped_binary));
+    }
+    if report.skipped_large > 0 {
+        summary.push_str(&format!(
+            ", {} over {} skipped",
+            report.skipped_large,
+            human_size(MAX_GREP_FILE_BYTES)
+        ));
+    }
+    eprintln!("{}", summary);
+}
+
+/// Binary size with 1024-based units, e.g. "12.3 KiB".
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+pub fn print_timing_report(timings: &[BorgTiming]) {
+    if timings.is_empty() {
+        return;
+    }
+    eprintln!("borg invocations ({}):", timings.len());
+    for t in timings {
+        eprintln!(
+            "  {:<12} {:>8.2}s  out {:>10}  err {:>10}  {}",
+            t.action,
+            t.duration.as_secs_f64(),
+            human_size(t.stdout_bytes as u64),
+            human_size(t.stderr_bytes as u64),
+            if t.success { "ok" } else { "failed" }
+        );
+    }
+    let total: f64 = timings.iter().map(|t| t.duration.as_secs_f64()).sum();
+    let peak = timings
+        .iter()
+        .map(|t| t.stdout_bytes + t.stderr_bytes)
+        .max()
+        .unwrap_or(0);
+    eprintln!(
+        "  total {:.2}s in borg, peak output {}",
+        total,
+        human_size(peak as u64)
+    );
+}
+
+pub fn print_mounts(records: &[MountRecord]) {
+    if records.is_empty() {
+        println!("Nothing mounted by borg-tool");
+        return;
+    }
+    println!(
+        "{:<16} {:<12} {:>7}  {:<24} MOUNTPOINT",
+        "MOUNTED", "REPO", "PID", "ARCHIVE"
+    );
+    for record in records {
+        let mounted = chrono::DateTime::parse_from_rfc3339(&record.mounted_at)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| record.mounted_at.clone());
+        println!(
+            "{:<16} {:<12} {:>7}  {:<24} {}",
+            mounted,
+            record.repo,
+            record.pid,
+            record.label(),
+            record.mountpoint.display()
+        );
+    }
+}
+
+/// Backup runs holding their single-instance lock, above the `ps` process table.
+pub fn print_running_backups(holders: &[runlock::Holder]) {
+    if holders.is_empty() {
+        return;
+    }
+    println!("Backups running:");
+    for h in holders {
+        println!(
+            "  {:>7}  {}/{}  since {}",
+            h.pid, h.repo, h.preset, h.started
+        );
+    }
+    println!();
+}
+
+pub fn print_tool_processes(processes: &[ToolProcess]) {
+    if processes.is_empty() {
+        println!("No borg processes started by borg-tool are running");
+        return;
+    }
+    println!(
+        "{:>7}  {:<8} {:<16} {:<10} {:>9}  SESSION",
+        "PID", "COMMAND", "REPO", "OP", "RUNTIME"
+    );
+    for p in processes {
+        let runtime = p
+            .runtime
+            .map(|d| human_duration(d.as_secs()))
+            .unwrap_or_else(|| "-".to_string());
+        let session = if p.session_alive {
+            p.session.to_string()
+        } else {
+            format!("{} (exited)", p.session)
+        };
+        println!(
+            "{:>7}  {:<8} {:<16} {:<10} {:>9}  {}",
+            p.pid, p.command, p.repo, p.operation, runtime, session
+        );
+    }
+}
+
+/// Compact elapsed time, e.g. "42s", "5m07s", "3h02m".
+pub(crate) fn human_duration(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct MountInfo {
-    pub archive: String,
+    /// `None` when the whole repository is mounted
+    pub archive: Option<String>,
     pub mountpoint: PathBuf,
 }
+
+impl MountInfo {
+    pub fn label(&self) -> &str {
+        self.archive.as_deref().unwrap_or("all archives")
+    }
+}
@@ -3,6 +3,7 @@ use std::process::Command;
 
 use assert_cmd::prelude::*;
 use assert_fs::prelude::*;
+use predicates::prelude::*;
 use predicates::str::contains;
 use serde::Deserialize;
 use tempfile::TempDir;
@@ -321,3 +322,311 @@ repo = "{}"
 
     Ok(())
 }
+
+#[test]
+fn print_command_shows_invocation_without_running_borg() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = assert_fs::TempDir::new()?;
+    let repo_dir = temp.child("repo.borg");
+    repo_dir.create_dir_all()?;
+    let config_path = temp.child("config.toml");
+    config_path.write_str(&format!(
+        r#"borg_bin = "/nonexistent/borg"
+probe_ssh = false
+
+[[repos]]
+name = "local"
+repo = "{}"
+
+[[repos.backups]]
+name = "test"
+includes = ["/data"]
+compression = "zstd,3"
+"#,
+        repo_dir.path().display()
+    ))?;
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut cmd, temp.path());
+    cmd.arg("--config")
+        .arg(config_path.path())
+        .arg("--print-command")
+        .args(["backup", "test"])
+        .assert()
+        .success()
+        .stdout(contains(
+            "/nonexistent/borg create --log-json --compression zstd,3",
+        ))
+        .stdout(contains("/data"));
+
+    let mut list_cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut list_cmd, temp.path());
+    list_cmd
+        .arg("--config")
+        .arg(config_path.path())
+        .arg("--print-command")
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "/nonexistent/borg list --json --format '{{comment}}' {}",
+            repo_dir.path().display()
+        )));
+
+    // without an archive the whole repository is mounted
+    let mountpoint = temp.child("mnt");
+    let mut mount_cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut mount_cmd, temp.path());
+    mount_cmd
+        .arg("--config")
+        .arg(config_path.path())
+        .arg("--print-command")
+        .args(["mount", "--target"])
+        .arg(mountpoint.path())
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "/nonexistent/borg mount {} {}",
+            repo_dir.path().display(),
+            mountpoint.path().display()
+        )));
+
+    Ok(())
+}
+
+#[test]
+fn backup_dry_run_shows_only_the_passphrase_that_is_set() -> Result<(), Box<dyn std::error::Error>>
+{
+    let temp = assert_fs::TempDir::new()?;
+    let passphrase_file = temp.child("passphrase");
+    passphrase_file.write_str("secret\n")?;
+    let config_path = temp.child("config.toml");
+    config_path.write_str(&format!(
+        r#"borg_bin = "/nonexistent/borg"
+probe_ssh = false
+
+[[repos]]
+name = "plain"
+repo = "{0}"
+
+[[repos.backups]]
+name = "test"
+includes = ["/data"]
+
+[[repos]]
+name = "keyed"
+repo = "{0}"
+passphrase_file = "{1}"
+
+[[repos.backups]]
+name = "test"
+includes = ["/data"]
+"#,
+        temp.path().display(),
+        passphrase_file.path().display()
+    ))?;
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut cmd, temp.path());
+    cmd.env_remove("BORG_PASSPHRASE")
+        .arg("--config")
+        .arg(config_path.path())
+        .args(["--repo", "plain", "backup", "test", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(contains("/nonexistent/borg create"))
+        .stdout(contains("BORG_PASSPHRASE").not());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut cmd, temp.path());
+    cmd.env_remove("BORG_PASSPHRASE")
+        .arg("--config")
+        .arg(config_path.path())
+        .args(["--repo", "keyed", "backup", "test", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(contains(
+            "BORG_PASSPHRASE=<redacted> /nonexistent/borg create",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn print_command_backup_all_succeeds() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = assert_fs::TempDir::new()?;
+    let config_path = temp.child("config.toml");
+    config_path.write_str(&format!(
+        r#"borg_bin = "/nonexistent/borg"
+probe_ssh = false
+
+[[repos]]
+name = "local"
+repo = "{}"
+
+[[repos.backups]]
+name = "home"
+includes = ["/home"]
+
+[[repos.backups]]
+name = "etc"
+includes = ["/etc"]
+"#,
+        temp.path().display()
+    ))?;
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut cmd, temp.path());
+    cmd.arg("--config")
+        .arg(config_path.path())
+        .arg("--print-command")
+        .args(["backup", "--all"])
+        .assert()
+        .success()
+        .stdout(contains("/nonexistent/borg create"))
+        .stdout(contains("failed").not());
+
+    Ok(())
+}
+
+#[test]
+fn repo_url_works_without_config_file() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = assert_fs::TempDir::new()?;
+    let repo_dir = temp.child("rescue.borg");
+    repo_dir.create_dir_all()?;
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut cmd, temp.path());
+    cmd.current_dir(temp.path())
+        .arg("--repo-url")
+        .arg(repo_dir.path())
+        .arg("--print-command")
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "borg list --json --format '{{comment}}' {}",
+            repo_dir.path().display()
+        )));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn json_prints_archive_list() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = assert_fs::TempDir::new()?;
+    let fake_borg = temp.child("fake-borg");
+    fake_borg.write_str(
+        "#!/bin/sh\necho '{\"archives\": [{\"archive\": \"host-1\", \"time\": \"2024-05-01T10:00:00.000000\"}]}'\n",
+    )?;
+    std::fs::set_permissions(fake_borg.path(), std::fs::Permissions::from_mode(0o755))?;
+    let config_path = temp.child("config.toml");
+    config_path.write_str(&format!(
+        r#"borg_bin = "{}"
+probe_ssh = false
+
+[[repos]]
+name = "local"
+repo = "{}"
+"#,
+        fake_borg.path().display(),
+        temp.path().display()
+    ))?;
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut cmd, temp.path());
+    let output = cmd
+        .arg("--config")
+        .arg(config_path.path())
+        .args(["--json", "list"])
+        .output()?;
+    assert!(output.status.success());
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(parsed[0]["name"], "host-1");
+    assert_eq!(parsed[0]["time"], "2024-05-01T10:00:00.000000");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn check_health_exit_code_follows_the_archive_age() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = assert_fs::TempDir::new()?;
+    let time = (chrono::Local::now() - chrono::Duration::hours(30)).format("%Y-%m-%dT%H:%M:%S");
+    let fake_borg = temp.child("fake-borg");
+    fake_borg.write_str(&format!(
+        "#!/bin/sh\necho '{{\"archives\": [{{\"archive\": \"local-test-1\", \"time\": \"{}.000000\"}}]}}'\n",
+        time
+    ))?;
+    std::fs::set_permissions(fake_borg.path(), std::fs::Permissions::from_mode(0o755))?;
+    let config_path = temp.child("config.toml");
+    config_path.write_str(&format!(
+        r#"borg_bin = "{}"
+probe_ssh = false
+
+[[repos]]
+name = "local"
+repo = "{}"
+
+[[repos.backups]]
+name = "test"
+includes = ["/data"]
+"#,
+        fake_borg.path().display(),
+        temp.path().display()
+    ))?;
+
+    for (thresholds, code, state) in [
+        (["--warning", "2d", "--critical", "3d"], 0, "BORG OK"),
+        (["--warning", "1d", "--critical", "2d"], 1, "BORG WARNING"),
+        (["--warning", "1h", "--critical", "1d"], 2, "BORG CRITICAL"),
+    ] {
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+        apply_env(&mut cmd, temp.path());
+        cmd.arg("--config")
+            .arg(config_path.path())
+            .arg("check-health")
+            .args(thresholds)
+            .assert()
+            .code(code)
+            .stdout(contains(state));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn non_interactive_fails_instead_of_prompting() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = assert_fs::TempDir::new()?;
+    let config_path = temp.child("config.toml");
+    config_path.write_str(
+        r#"borg_bin = "/nonexistent/borg"
+probe_ssh = false
+
+[[repos]]
+name = "one"
+repo = "/srv/one.borg"
+
+[[repos]]
+name = "two"
+repo = "/srv/two.borg"
+"#,
+    )?;
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("borg-tool-rs"));
+    apply_env(&mut cmd, temp.path());
+    cmd.arg("--config")
+        .arg(config_path.path())
+        .arg("--non-interactive")
+        .arg("backup")
+        .assert()
+        .failure()
+        .stderr(contains("choose one with --repo <name> (one, two)"))
+        .stderr(contains("--non-interactive"));
+
+    Ok(())
+}